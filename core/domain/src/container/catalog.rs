@@ -0,0 +1,271 @@
+//! Declarative image catalog files
+//!
+//! As an alternative to the compact string representation of a [`ContainerImageSet`],
+//! images can be described in a YAML document which allows per-image settings:
+//!
+//! ```yaml
+//! images:
+//!   - image: webgrid/node-chrome:latest
+//!     browser:
+//!       name: chrome
+//!       version: "114.0"
+//!     platform: linux
+//!     defaultCapabilities:
+//!       goog:chromeOptions:
+//!         args: ["--disable-gpu"]
+//!     resources:
+//!       cpu: "2"
+//!       memory: 4Gi
+//!     maxSessions: 5
+//!     constraints:
+//!       webgrid:options.gpu: true
+//! ```
+
+use super::matching::{CapabilityConstraint, DEFAULT_PLATFORM};
+use super::{ContainerImage, ContainerImageSet};
+use crate::webdriver::Browser;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Error thrown while parsing an image catalog
+#[derive(Debug, Error)]
+pub enum ImageCatalogError {
+    /// The document is not a valid catalog
+    #[error("invalid image catalog")]
+    Invalid(#[from] serde_yaml::Error),
+    /// An image has a resource limit which can not be interpreted
+    #[error("invalid resource limit {limit:?} for image {image}")]
+    InvalidResourceLimit {
+        /// Identifier of the image
+        image: String,
+        /// Raw value of the limit
+        limit: String,
+    },
+}
+
+/// Upper bounds for the resources a single session may consume
+///
+/// Values use the Kubernetes quantity notation, e.g. `500m` or `1.5` for CPUs
+/// and `512Mi` or `2G` for memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct ResourceLimits {
+    /// Number of CPUs
+    pub cpu: Option<String>,
+    /// Amount of memory in bytes
+    pub memory: Option<String>,
+}
+
+impl ResourceLimits {
+    /// CPU limit in units of 10<sup>-9</sup> CPUs
+    pub fn nano_cpus(&self) -> Option<i64> {
+        let cpu = self.cpu.as_ref()?.trim();
+
+        let cpus = match cpu.strip_suffix('m') {
+            Some(millis) => millis.parse::<f64>().ok()? / 1_000.0,
+            None => cpu.parse::<f64>().ok()?,
+        };
+
+        if cpus.is_finite() && cpus > 0.0 {
+            Some((cpus * 1_000_000_000.0) as i64)
+        } else {
+            None
+        }
+    }
+
+    /// Memory limit in bytes
+    pub fn memory_bytes(&self) -> Option<i64> {
+        const SUFFIXES: [(&str, i64); 10] = [
+            ("Ki", 1 << 10),
+            ("Mi", 1 << 20),
+            ("Gi", 1 << 30),
+            ("Ti", 1 << 40),
+            ("Pi", 1 << 50),
+            ("k", 1_000),
+            ("M", 1_000_000),
+            ("G", 1_000_000_000),
+            ("T", 1_000_000_000_000),
+            ("P", 1_000_000_000_000_000),
+        ];
+
+        let memory = self.memory.as_ref()?.trim();
+        let (amount, multiplier) = SUFFIXES
+            .iter()
+            .find_map(|(suffix, multiplier)| {
+                memory
+                    .strip_suffix(suffix)
+                    .map(|amount| (amount, *multiplier))
+            })
+            .unwrap_or((memory, 1));
+
+        let bytes = amount.parse::<f64>().ok()? * multiplier as f64;
+
+        if bytes.is_finite() && bytes > 0.0 {
+            Some(bytes as i64)
+        } else {
+            None
+        }
+    }
+
+    fn validate(&self, image: &str) -> Result<(), ImageCatalogError> {
+        let invalid = |limit: &String| ImageCatalogError::InvalidResourceLimit {
+            image: image.to_owned(),
+            limit: limit.to_owned(),
+        };
+
+        if let (Some(cpu), None) = (&self.cpu, self.nano_cpus()) {
+            return Err(invalid(cpu));
+        }
+
+        if let (Some(memory), None) = (&self.memory, self.memory_bytes()) {
+            return Err(invalid(memory));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ImageCatalogBrowser {
+    name: String,
+    version: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ImageCatalogEntry {
+    image: String,
+    browser: ImageCatalogBrowser,
+    platform: Option<String>,
+    #[serde(default)]
+    default_capabilities: Map<String, Value>,
+    #[serde(default)]
+    resources: ResourceLimits,
+    max_sessions: Option<usize>,
+    #[serde(default)]
+    constraints: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ImageCatalog {
+    images: Vec<ImageCatalogEntry>,
+}
+
+impl ContainerImageSet {
+    /// Parses a YAML image catalog as described in the [module documentation](self)
+    pub fn from_catalog(catalog: &str) -> Result<Self, ImageCatalogError> {
+        let catalog: ImageCatalog = serde_yaml::from_str(catalog)?;
+        let mut images = Vec::with_capacity(catalog.images.len());
+
+        for entry in catalog.images {
+            entry.resources.validate(&entry.image)?;
+
+            images.push(ContainerImage {
+                identifier: entry.image,
+                browser: Browser {
+                    name: entry.browser.name,
+                    version: entry.browser.version,
+                },
+                platform: entry
+                    .platform
+                    .unwrap_or_else(|| DEFAULT_PLATFORM.to_owned()),
+                constraints: entry
+                    .constraints
+                    .into_iter()
+                    .map(|(path, value)| CapabilityConstraint { path, value })
+                    .collect(),
+                default_capabilities: entry.default_capabilities,
+                resources: entry.resources,
+                max_sessions: entry.max_sessions,
+            });
+        }
+
+        Ok(Self(images))
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use serde_json::json;
+
+    const CATALOG: &str = r#"
+images:
+  - image: webgrid/node-chrome
+    browser:
+      name: chrome
+      version: "114.0"
+    defaultCapabilities:
+      goog:chromeOptions:
+        args: ["--disable-gpu"]
+    resources:
+      cpu: 500m
+      memory: 2Gi
+    maxSessions: 5
+    constraints:
+      webgrid:options.gpu: true
+  - image: webgrid/node-firefox
+    browser:
+      name: firefox
+      version: 102.0esr
+    platform: windows
+"#;
+
+    #[test]
+    fn parse_catalog() {
+        let set = ContainerImageSet::from_catalog(CATALOG).unwrap();
+        let images = set.iter().collect::<Vec<_>>();
+
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].identifier, "webgrid/node-chrome");
+        assert_eq!(images[0].browser.version, "114.0");
+        assert_eq!(images[0].platform, DEFAULT_PLATFORM);
+        assert_eq!(images[0].max_sessions, Some(5));
+        assert_eq!(
+            images[0].default_capabilities["goog:chromeOptions"],
+            json!({ "args": ["--disable-gpu"] })
+        );
+        assert_eq!(images[0].constraints[0].path, "webgrid:options.gpu");
+        assert_eq!(images[1].platform, "windows");
+        assert_eq!(images[1].max_sessions, None);
+    }
+
+    #[test]
+    fn convert_resource_limits() {
+        let set = ContainerImageSet::from_catalog(CATALOG).unwrap();
+        let resources = &set.iter().next().unwrap().resources;
+
+        assert_eq!(resources.nano_cpus(), Some(500_000_000));
+        assert_eq!(resources.memory_bytes(), Some(2 * 1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn reject_invalid_resource_limits() {
+        let catalog = r#"
+images:
+  - image: webgrid/node-chrome
+    browser: { name: chrome, version: "114.0" }
+    resources: { memory: lots }
+"#;
+
+        assert!(matches!(
+            ContainerImageSet::from_catalog(catalog),
+            Err(ImageCatalogError::InvalidResourceLimit { .. })
+        ));
+    }
+
+    #[test]
+    fn reject_unknown_fields() {
+        let catalog = r#"
+images:
+  - image: webgrid/node-chrome
+    browser: { name: chrome, version: "114.0" }
+    maxSession: 5
+"#;
+
+        assert!(ContainerImageSet::from_catalog(catalog).is_err());
+    }
+}
@@ -0,0 +1,447 @@
+//! Building blocks for matching requested capabilities against images
+
+use library::helpers::split_into_two;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Platform of all images unless specified otherwise
+pub const DEFAULT_PLATFORM: &str = "linux";
+
+/// Groups of browser names that refer to the same browser, the first entry being the canonical name
+const BROWSER_ALIASES: &[&[&str]] = &[&["msedge", "microsoftedge", "edge"], &["firefox", "gecko"]];
+
+/// Suffixes of version strings which do not indicate a pre-release build (e.g. `68.7.0esr`)
+const RELEASE_SUFFIXES: &[&str] = &["esr"];
+
+/// Resolves aliases and casing of a browser name so that equivalent names compare equal
+///
+/// ```
+/// # use domain::container::matching::canonical_browser_name;
+/// assert_eq!(canonical_browser_name("MicrosoftEdge"), canonical_browser_name("edge"));
+/// assert_eq!(canonical_browser_name("gecko"), "firefox");
+/// ```
+pub fn canonical_browser_name(name: &str) -> String {
+    let name = name.to_lowercase();
+
+    BROWSER_ALIASES
+        .iter()
+        .find(|aliases| aliases.contains(&name.as_str()))
+        .map(|aliases| aliases[0].to_owned())
+        .unwrap_or(name)
+}
+
+/// Whether a requested `platformName` is satisfied by the platform of an image
+///
+/// As per the WebDriver specification, the comparison is case-insensitive and `any` matches all platforms.
+pub fn platform_matches(requested: &str, provided: &str) -> bool {
+    requested.eq_ignore_ascii_case("any") || requested.eq_ignore_ascii_case(provided)
+}
+
+/// Numeric representation of a browser version string
+///
+/// Only the leading, dot-separated numeric components are considered so that
+/// e.g. `68.7.0esr` is treated as `68.7.0`. Missing components compare as zero.
+#[derive(Debug, Clone)]
+pub struct BrowserVersion {
+    components: Vec<u64>,
+    pre_release: bool,
+}
+
+impl BrowserVersion {
+    /// Parses a version string, returning `None` if it does not start with a number
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.trim();
+        let mut components = Vec::new();
+        let mut consumed = 0;
+
+        for part in version.split('.') {
+            let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+
+            match digits.parse() {
+                Ok(component) => components.push(component),
+                Err(_) => break,
+            }
+
+            consumed += digits.len();
+
+            // Anything trailing the numeric portion (e.g. `0esr`, `0b3`) ends the version
+            if digits.len() != part.len() {
+                break;
+            }
+
+            // Account for the separator
+            consumed += 1;
+        }
+
+        if components.is_empty() {
+            return None;
+        }
+
+        let suffix = version
+            .get(consumed.min(version.len())..)
+            .unwrap_or_default()
+            .trim_start_matches(|c: char| c == '-' || c == '.' || c.is_whitespace())
+            .to_lowercase();
+
+        Some(Self {
+            components,
+            pre_release: !suffix.is_empty() && !RELEASE_SUFFIXES.contains(&suffix.as_str()),
+        })
+    }
+
+    /// Whether the version string denoted a pre-release build (e.g. beta or nightly)
+    pub fn is_pre_release(&self) -> bool {
+        self.pre_release
+    }
+
+    fn component(&self, index: usize) -> u64 {
+        self.components.get(index).copied().unwrap_or_default()
+    }
+
+    /// Whether all components of `prefix` equal the leading components of this version
+    fn starts_with(&self, prefix: &BrowserVersion) -> bool {
+        prefix
+            .components
+            .iter()
+            .enumerate()
+            .all(|(i, c)| self.components.get(i) == Some(c))
+    }
+}
+
+impl PartialEq for BrowserVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for BrowserVersion {}
+
+impl PartialOrd for BrowserVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BrowserVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let length = self.components.len().max(other.components.len());
+
+        (0..length)
+            .map(|i| self.component(i).cmp(&other.component(i)))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Error thrown while parsing a [`VersionRequirement`]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum VersionRequirementParseError {
+    /// The version following the operator is not a valid version
+    #[error("invalid version in requirement: {0}")]
+    InvalidVersion(String),
+}
+
+/// Constraint on the browser version as requested by a client through the `browserVersion` capability
+///
+/// Parsable from the following formats:
+///
+/// | Format              | Meaning                                                   |
+/// |---------------------|-----------------------------------------------------------|
+/// | `latest`, `*`       | Any version (also used if the value is empty)             |
+/// | `stable`            | Any version which is not a pre-release                    |
+/// | `81`, `81.0.4044`   | Versions whose leading components equal the given ones    |
+/// | `>=100`, `>100`     | Versions greater than (or equal to) the given one         |
+/// | `<=100`, `<100`     | Versions less than (or equal to) the given one            |
+/// | `=100`              | Versions comparing equal to the given one                 |
+/// | `~114`, `~114.1`    | At least the given version with the same major (or minor) version |
+/// | `^114`              | At least the given version with the same major version    |
+///
+/// ```
+/// # use domain::container::matching::{BrowserVersion, VersionRequirement};
+/// let requirement: VersionRequirement = ">=100".parse().unwrap();
+/// assert!(requirement.matches(&BrowserVersion::parse("114.0.5735.90").unwrap()));
+/// assert!(!requirement.matches(&BrowserVersion::parse("81.0").unwrap()));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionRequirement {
+    /// Any version is accepted
+    Latest,
+    /// Any version that is not a pre-release is accepted
+    Stable,
+    /// Leading version components have to match
+    Prefix(BrowserVersion),
+    /// Version has to compare equal
+    Exact(BrowserVersion),
+    /// Version has to be strictly greater
+    Greater(BrowserVersion),
+    /// Version has to be greater or equal
+    GreaterOrEqual(BrowserVersion),
+    /// Version has to be strictly less
+    Less(BrowserVersion),
+    /// Version has to be less or equal
+    LessOrEqual(BrowserVersion),
+    /// Version has to be greater or equal while sharing the major and, if given, minor version
+    Tilde(BrowserVersion),
+    /// Version has to be greater or equal while sharing the major version
+    Caret(BrowserVersion),
+}
+
+impl VersionRequirement {
+    /// Checks whether the given version satisfies the requirement
+    pub fn matches(&self, version: &BrowserVersion) -> bool {
+        match self {
+            Self::Latest => true,
+            Self::Stable => !version.is_pre_release(),
+            Self::Prefix(prefix) => version.starts_with(prefix),
+            Self::Exact(other) => version == other,
+            Self::Greater(other) => version > other,
+            Self::GreaterOrEqual(other) => version >= other,
+            Self::Less(other) => version < other,
+            Self::LessOrEqual(other) => version <= other,
+            Self::Tilde(other) => {
+                // `~114` pins the major version while `~114.1` and `~114.1.2` pin the minor version
+                let pinned = other.components.len().min(2);
+                let prefix = BrowserVersion {
+                    components: other.components.iter().copied().take(pinned).collect(),
+                    pre_release: false,
+                };
+
+                version >= other && version.starts_with(&prefix)
+            }
+            Self::Caret(other) => version >= other && version.component(0) == other.component(0),
+        }
+    }
+}
+
+impl FromStr for VersionRequirement {
+    type Err = VersionRequirementParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        match s.to_lowercase().as_str() {
+            "" | "*" | "latest" => return Ok(Self::Latest),
+            "stable" => return Ok(Self::Stable),
+            _ => {}
+        }
+
+        let operators: &[(&str, fn(BrowserVersion) -> Self)] = &[
+            (">=", Self::GreaterOrEqual),
+            ("<=", Self::LessOrEqual),
+            (">", Self::Greater),
+            ("<", Self::Less),
+            ("=", Self::Exact),
+            ("~", Self::Tilde),
+            ("^", Self::Caret),
+        ];
+
+        let (constructor, version): (fn(BrowserVersion) -> Self, &str) = operators
+            .iter()
+            .find_map(|(operator, constructor)| {
+                s.strip_prefix(operator)
+                    .map(|version| (*constructor, version))
+            })
+            .unwrap_or((Self::Prefix, s));
+
+        BrowserVersion::parse(version)
+            .map(constructor)
+            .ok_or_else(|| VersionRequirementParseError::InvalidVersion(s.to_owned()))
+    }
+}
+
+/// Looks up a capability by its path, treating `null` and `false` as absent
+///
+/// The first segment of the path denotes the top-level capability (e.g. `goog:chromeOptions`)
+/// while each following, dot-separated segment descends into a nested object.
+///
+/// ```
+/// # use domain::container::matching::resolve_capability;
+/// let capabilities = serde_json::json!({ "goog:chromeOptions": { "mobileEmulation": { "deviceName": "Pixel 2" } } });
+/// assert!(resolve_capability(&capabilities, "goog:chromeOptions.mobileEmulation").is_some());
+/// assert!(resolve_capability(&capabilities, "goog:chromeOptions.args").is_none());
+/// ```
+pub fn resolve_capability<'a>(capabilities: &'a Value, path: &str) -> Option<&'a Value> {
+    let value = path
+        .split('.')
+        .try_fold(capabilities, |value, segment| value.get(segment))?;
+
+    match value {
+        Value::Null | Value::Bool(false) => None,
+        value => Some(value),
+    }
+}
+
+/// Error thrown while parsing a [`CapabilityConstraint`] from string
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CapabilityConstraintParseError {
+    /// Missing `=` separator between path and value
+    #[error("missing = separator between capability path and value")]
+    MissingSeparator,
+}
+
+/// Capability an image provides which has to be matched by requests that ask for it
+///
+/// Within a [`ContainerImageSet`](super::ContainerImageSet), all paths constrained by at least one image
+/// are considered relevant. When a request contains a value for a relevant path, only images with a
+/// constraint on that path which [accepts](CapabilityConstraint::accepts) the value may be selected.
+/// Requests without a value for the path are unaffected.
+///
+/// Parsable from a string containing the path and a JSON value separated by `=`. Values that are
+/// not valid JSON are treated as strings.
+/// ```
+/// # use domain::container::matching::CapabilityConstraint;
+/// let constraint: CapabilityConstraint = "acme:locale=de-DE".parse().unwrap();
+/// assert_eq!(constraint.path, "acme:locale");
+/// assert_eq!(constraint.value, serde_json::json!("de-DE"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapabilityConstraint {
+    /// Dot-separated path to the capability, as used by [`resolve_capability`]
+    pub path: String,
+    /// Value provided by the image
+    pub value: Value,
+}
+
+impl CapabilityConstraint {
+    /// Whether a requested value is satisfied by the constraint
+    ///
+    /// Values have to be equal, with the exception of constraints with a value of `true` which
+    /// declare support for a feature and thus accept any requested value (e.g. an object
+    /// containing the configuration for said feature).
+    pub fn accepts(&self, requested: &Value) -> bool {
+        self.value == Value::Bool(true) || &self.value == requested
+    }
+}
+
+impl FromStr for CapabilityConstraint {
+    type Err = CapabilityConstraintParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, raw_value) =
+            split_into_two(s, "=").ok_or(CapabilityConstraintParseError::MissingSeparator)?;
+        let value = serde_json::from_str(&raw_value).unwrap_or(Value::String(raw_value));
+
+        Ok(Self {
+            path: path.trim().to_owned(),
+            value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    fn version(raw: &str) -> BrowserVersion {
+        BrowserVersion::parse(raw).unwrap()
+    }
+
+    fn requirement(raw: &str) -> VersionRequirement {
+        raw.parse().unwrap()
+    }
+
+    #[test]
+    fn resolve_browser_aliases() {
+        assert_eq!(canonical_browser_name("MicrosoftEdge"), "msedge");
+        assert_eq!(canonical_browser_name("msedge"), "msedge");
+        assert_eq!(canonical_browser_name("edge"), "msedge");
+        assert_eq!(canonical_browser_name("gecko"), "firefox");
+        assert_eq!(canonical_browser_name("Chrome"), "chrome");
+    }
+
+    #[test]
+    fn match_platforms() {
+        assert!(platform_matches("LINUX", "linux"));
+        assert!(platform_matches("any", "linux"));
+        assert!(!platform_matches("windows", "linux"));
+    }
+
+    #[test]
+    fn parse_versions_with_suffix() {
+        assert_eq!(version("68.7.0esr"), version("68.7.0"));
+        assert!(!version("68.7.0esr").is_pre_release());
+        assert!(!version("114.0.5735.90").is_pre_release());
+        assert!(version("115.0b3").is_pre_release());
+        assert!(version("116.0-beta").is_pre_release());
+        assert!(BrowserVersion::parse("esr").is_none());
+    }
+
+    #[test]
+    fn compare_versions_numerically() {
+        assert!(version("100.0") > version("99.1"));
+        assert!(version("81") < version("81.0.1"));
+        assert_eq!(version("81").cmp(&version("81.0.0")), Ordering::Equal);
+    }
+
+    #[test]
+    fn match_prefix_by_component() {
+        assert!(requirement("81").matches(&version("81.0.4044.122")));
+        assert!(!requirement("8").matches(&version("81.0.4044.122")));
+    }
+
+    #[test]
+    fn match_ranges() {
+        assert!(requirement(">=100").matches(&version("100.0")));
+        assert!(!requirement(">100").matches(&version("100.0")));
+        assert!(requirement("<100").matches(&version("99.9")));
+        assert!(requirement("~114").matches(&version("114.0.5735")));
+        assert!(!requirement("~114").matches(&version("115.0")));
+        assert!(requirement("~114.1").matches(&version("114.1.5")));
+        assert!(!requirement("~114.1").matches(&version("114.2")));
+        assert!(!requirement("~114.1").matches(&version("114.0")));
+        assert!(requirement("^114").matches(&version("114.9")));
+    }
+
+    #[test]
+    fn match_keywords() {
+        assert!(requirement("latest").matches(&version("115.0b3")));
+        assert!(!requirement("stable").matches(&version("115.0b3")));
+        assert!(requirement("stable").matches(&version("114.0")));
+    }
+
+    #[test]
+    fn parse_constraint_values() {
+        let boolean: CapabilityConstraint = "webgrid:options.gpu=true".parse().unwrap();
+        let string: CapabilityConstraint = "acme:locale=de".parse().unwrap();
+
+        assert_eq!(boolean.path, "webgrid:options.gpu");
+        assert_eq!(boolean.value, Value::Bool(true));
+        assert_eq!(string.value, Value::String("de".into()));
+        assert!("acme:locale".parse::<CapabilityConstraint>().is_err());
+    }
+
+    #[test]
+    fn accept_feature_configuration() {
+        let constraint: CapabilityConstraint =
+            "goog:chromeOptions.mobileEmulation=true".parse().unwrap();
+
+        assert!(constraint.accepts(&serde_json::json!({ "deviceName": "Pixel 2" })));
+    }
+
+    #[test]
+    fn accept_equal_values_only() {
+        let constraint: CapabilityConstraint = "acme:locale=de".parse().unwrap();
+
+        assert!(constraint.accepts(&serde_json::json!("de")));
+        assert!(!constraint.accepts(&serde_json::json!("en")));
+    }
+
+    #[test]
+    fn treat_null_and_false_as_absent() {
+        let capabilities = serde_json::json!({ "a": null, "b": false, "c": { "d": 1 } });
+
+        assert!(resolve_capability(&capabilities, "a").is_none());
+        assert!(resolve_capability(&capabilities, "b").is_none());
+        assert_eq!(
+            resolve_capability(&capabilities, "c.d"),
+            Some(&serde_json::json!(1))
+        );
+    }
+
+    #[test]
+    fn reject_invalid_requirements() {
+        assert!(">=abc".parse::<VersionRequirement>().is_err());
+    }
+}
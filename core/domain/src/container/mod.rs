@@ -0,0 +1,584 @@
+//! Logic related to container images
+
+use super::webdriver::{
+    Browser, BrowserParseError, Capabilities, CapabilitiesRequest, RawCapabilitiesRequest,
+};
+use catalog::ResourceLimits;
+use library::helpers::split_into_two;
+use matching::{
+    canonical_browser_name, platform_matches, resolve_capability, BrowserVersion,
+    CapabilityConstraint, CapabilityConstraintParseError, VersionRequirement, DEFAULT_PLATFORM,
+};
+use serde_json::{Map, Value};
+use std::collections::HashSet;
+use std::str::FromStr;
+use thiserror::Error;
+use tracing::{debug, instrument, trace};
+
+pub mod catalog;
+pub mod matching;
+
+/// Error thrown while parsing a container image definition from string
+#[derive(Debug, Error)]
+pub enum ContainerImageParseError {
+    /// Missing = separator between image and browser definition
+    #[error("missing = separator between image and browser definition")]
+    MissingImageBrowserSeparator,
+    /// At least one browser definition failed parsing
+    #[error("invalid browser definition")]
+    BrowserDefinitionInvalid(#[from] BrowserParseError),
+    /// At least one capability constraint failed parsing
+    #[error("invalid capability constraint")]
+    InvalidConstraint(#[from] CapabilityConstraintParseError),
+}
+
+/// Definition of a container image containing a web browser
+///
+/// Parsable from a custom string containing the image and [`Browser`] definition
+/// separated by `=`. Images parsed this way run on the [`DEFAULT_PLATFORM`].
+/// ```
+/// # use domain::{container::ContainerImage, webdriver::Browser};
+/// let image: ContainerImage = "webgrid/node-chrome=chrome::82".parse().unwrap();
+/// let browser = Browser { name: "chrome".into(), version: "82".into() };
+/// assert_eq!(image.identifier, "webgrid/node-chrome");
+/// assert_eq!(image.browser, browser)
+/// ```
+///
+/// The browser definition may be followed by [`CapabilityConstraints`](CapabilityConstraint),
+/// each prefixed with a `|`, e.g. `webgrid/node-chrome-gpu=chrome::82|webgrid:options.gpu=true`.
+/// Further per-image settings are only available when using an [image catalog](catalog).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerImage {
+    /// Combination of repository, image, and tag
+    pub identifier: String,
+    /// Browser contained within the image
+    pub browser: Browser,
+    /// Operating system the browser is running on, as reported by the `platformName` capability
+    pub platform: String,
+    /// Capabilities provided by the image which requests may ask for
+    pub constraints: Vec<CapabilityConstraint>,
+    /// Capabilities added to each request unless the client already provides them
+    pub default_capabilities: Map<String, Value>,
+    /// Resources available to each session
+    pub resources: ResourceLimits,
+    /// Maximum number of concurrent sessions using this image
+    pub max_sessions: Option<usize>,
+}
+
+impl ContainerImage {
+    /// Checks whether the image satisfies the browser name, version, and platform of a capability set
+    ///
+    /// Browser names are compared after resolving [aliases](canonical_browser_name) while versions
+    /// are evaluated as a [`VersionRequirement`]. Requests with an unparsable version never match.
+    pub fn matches(&self, capabilities: &Capabilities) -> bool {
+        let browser_match = capabilities
+            .browser_name
+            .as_ref()
+            .map(|name| canonical_browser_name(name) == canonical_browser_name(&self.browser.name))
+            .unwrap_or(true);
+
+        let version_match = match &capabilities.browser_version {
+            Some(requested) => match (
+                requested.parse::<VersionRequirement>(),
+                BrowserVersion::parse(&self.browser.version),
+            ) {
+                (Ok(requirement), Some(version)) => requirement.matches(&version),
+                // Fall back to a literal comparison for images with non-numeric versions
+                (Ok(VersionRequirement::Latest), None) => true,
+                (_, None) => requested == &self.browser.version,
+                (Err(_), Some(_)) => false,
+            },
+            None => true,
+        };
+
+        let platform_match = capabilities
+            .platform_name
+            .as_ref()
+            .map(|platform| platform_matches(platform, &self.platform))
+            .unwrap_or(true);
+
+        trace!(
+            browser_match,
+            version_match,
+            platform_match,
+            "Match results collected"
+        );
+
+        browser_match && version_match && platform_match
+    }
+
+    /// Checks whether the image provides all constrained capabilities contained in a request
+    ///
+    /// Every path in `constrained_paths` which is present in the `requested` capabilities has to be
+    /// [accepted](CapabilityConstraint::accepts) by a constraint of this image on the same path.
+    pub fn satisfies_constraints(
+        &self,
+        requested: &Value,
+        constrained_paths: &HashSet<&str>,
+    ) -> bool {
+        constrained_paths
+            .iter()
+            .all(|path| match resolve_capability(requested, path) {
+                Some(value) => self
+                    .constraints
+                    .iter()
+                    .any(|constraint| constraint.path == *path && constraint.accepts(value)),
+                None => true,
+            })
+    }
+
+    /// Adds the default capabilities of the image to the `alwaysMatch` set of a request
+    ///
+    /// Capabilities which are present in the `alwaysMatch` set or any `firstMatch` entry are left
+    /// untouched so that clients can overwrite the defaults and the request stays valid.
+    pub fn inject_default_capabilities(
+        &self,
+        raw_capabilities: &RawCapabilitiesRequest,
+    ) -> Result<RawCapabilitiesRequest, serde_json::Error> {
+        if self.default_capabilities.is_empty() {
+            return Ok(raw_capabilities.clone());
+        }
+
+        let mut request: Value = serde_json::from_str(raw_capabilities.as_str())?;
+
+        if let Some(request) = request.as_object_mut() {
+            let first_match_keys = request
+                .get("firstMatch")
+                .and_then(Value::as_array)
+                .map(|sets| {
+                    sets.iter()
+                        .filter_map(Value::as_object)
+                        .flat_map(|set| set.keys().cloned())
+                        .collect::<HashSet<_>>()
+                })
+                .unwrap_or_default();
+
+            let always_match = request
+                .entry("alwaysMatch")
+                .or_insert_with(|| Value::Object(Map::new()));
+
+            if let Some(always_match) = always_match.as_object_mut() {
+                for (key, value) in self.default_capabilities.iter() {
+                    if !first_match_keys.contains(key) && !always_match.contains_key(key) {
+                        always_match.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+
+        Ok(RawCapabilitiesRequest::new(serde_json::to_string(
+            &request,
+        )?))
+    }
+
+    fn version(&self) -> Option<BrowserVersion> {
+        BrowserVersion::parse(&self.browser.version)
+    }
+}
+
+impl FromStr for ContainerImage {
+    type Err = ContainerImageParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((identifier, raw_definition)) = split_into_two(s, "=") {
+            let mut parts = raw_definition.split('|');
+            let browser = parts.next().unwrap_or_default().parse()?;
+            let constraints = parts
+                .map(|constraint| constraint.parse())
+                .collect::<Result<Vec<_>, _>>()?;
+
+            Ok(ContainerImage {
+                identifier,
+                browser,
+                platform: DEFAULT_PLATFORM.to_owned(),
+                constraints,
+                default_capabilities: Map::new(),
+                resources: ResourceLimits::default(),
+                max_sessions: None,
+            })
+        } else {
+            Err(ContainerImageParseError::MissingImageBrowserSeparator)
+        }
+    }
+}
+
+/// Set containing [`ContainerImages`](ContainerImage)
+///
+/// Parsable from a custom string containing [`ContainerImages`](ContainerImage) separated by `,`
+#[derive(Debug, Clone)]
+pub struct ContainerImageSet(Vec<ContainerImage>);
+
+impl ContainerImageSet {
+    /// Returns `true` if this set contains no images.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over all contained images
+    pub fn iter(&self) -> impl Iterator<Item = &ContainerImage> {
+        self.0.iter()
+    }
+}
+
+impl FromStr for ContainerImageSet {
+    type Err = ContainerImageParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut images = Vec::new();
+
+        for image_string in s.split(',') {
+            images.push(image_string.parse()?);
+        }
+
+        Ok(Self(images))
+    }
+}
+
+impl ContainerImageSet {
+    /// Retrieves the [`ContainerImage`] best matching the given request
+    ///
+    /// In order to select a matching image, these steps are followed:
+    /// 1. Split the [`CapabilitiesRequest`] into distinct sets using [`into_sets`](CapabilitiesRequest::into_sets)
+    /// 2. If the list of sets is empty, return the first image from the underlying [`ContainerImageSet`]
+    /// 3. Iterate the set of [capabilities](super::webdriver::Capabilities) to find the first element where either
+    ///     - No browser name *and* version is set, returning the first image running on the requested platform
+    ///     - Any image [matches](ContainerImage::matches) the browser name, version, and platform,
+    ///       returning the one with the newest browser version
+    ///
+    /// In both cases, images have to [satisfy](ContainerImage::satisfies_constraints) the
+    /// [`CapabilityConstraints`](CapabilityConstraint) for all requested capabilities constrained by any image in the set.
+    ///
+    /// Browser names are compared case-insensitively with aliases like `MicrosoftEdge`, `msedge`, and `edge`
+    /// being treated as equal. Versions are compared numerically by their components and may contain ranges
+    /// or keywords as described by [`VersionRequirement`]. A plain version like `81` matches all versions
+    /// starting with the component `81`, e.g. `81.0.4044.122` but not `8` or `810.1`.
+    ///
+    /// When multiple images contain the newest version, the one defined first is returned.
+    #[instrument]
+    pub fn match_against_capabilities(
+        &self,
+        request: CapabilitiesRequest,
+    ) -> Option<&ContainerImage> {
+        debug!("Matching capability request against image set");
+
+        let first_image = self.0.first();
+        let capability_sets = request.into_sets();
+
+        // Short circuit if no capabilities are given
+        if capability_sets.is_empty() {
+            debug!("No capabilities provided, returning first image");
+            return first_image;
+        }
+
+        capability_sets
+            .into_iter()
+            .find_map(|c| self.match_against_capability_set(c))
+    }
+
+    #[instrument(skip(self))]
+    fn match_against_capability_set(
+        &self,
+        capability_set: Capabilities,
+    ) -> Option<&ContainerImage> {
+        let requested = serde_json::to_value(&capability_set).unwrap_or(Value::Null);
+        let constrained_paths = self
+            .0
+            .iter()
+            .flat_map(|image| image.constraints.iter())
+            .map(|constraint| constraint.path.as_str())
+            .collect::<HashSet<_>>();
+
+        let mut candidates = self.0.iter().filter(|image| {
+            trace!(?image, "Matching capability set against image");
+            image.matches(&capability_set)
+                && image.satisfies_constraints(&requested, &constrained_paths)
+        });
+
+        // Use the default image if no specific browser is requested
+        if capability_set.browser_name.is_none() && capability_set.browser_version.is_none() {
+            debug!("No browser name or version provided, returning first image");
+            return candidates.next();
+        }
+
+        // Iterating in reverse makes `max_by` return the first of multiple equal elements
+        let image = candidates
+            .rev()
+            .max_by(|a, b| a.version().cmp(&b.version()));
+
+        trace!(?image, "Match completed");
+        image
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn parse_image_string() {
+        let image: ContainerImage = "webgrid/node-chrome=chrome::82".parse().unwrap();
+        let browser = Browser {
+            name: "chrome".into(),
+            version: "82".into(),
+        };
+        assert_eq!(image.identifier, "webgrid/node-chrome");
+        assert_eq!(image.browser, browser);
+        assert_eq!(image.platform, DEFAULT_PLATFORM);
+    }
+
+    #[test]
+    #[should_panic]
+    fn fail_on_missing_image_separator() {
+        let _: Browser = "webgrid/node-chrome".parse().unwrap();
+    }
+
+    #[test]
+    fn parse_image_set_string_with_one_image() {
+        let set: ContainerImageSet = "webgrid/node-chrome=chrome::82".parse().unwrap();
+        assert_eq!(set.0.len(), 1);
+    }
+
+    #[test]
+    fn parse_image_set_string_with_multiple_images() {
+        let set: ContainerImageSet = "image=browser::version,image2=browser2::version2"
+            .parse()
+            .unwrap();
+        assert_eq!(set.0.len(), 2);
+    }
+
+    #[test]
+    fn match_empty_request() {
+        let request: CapabilitiesRequest = serde_json::from_str("{}").unwrap();
+        let images: ContainerImageSet =
+            "webgrid-node:firefox=firefox::68.7.0esr,webgrid-node:chrome=chrome::81.0.4044.122"
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            images
+                .match_against_capabilities(request)
+                .map(|c| &c.identifier),
+            Some(&"webgrid-node:firefox".to_owned())
+        );
+    }
+
+    #[test]
+    fn match_browser_name_only() {
+        let capabilities = "{\"firstMatch\":[{\"browserName\":\"chrome\"}]}";
+        let request: CapabilitiesRequest = serde_json::from_str(capabilities).unwrap();
+        let images: ContainerImageSet =
+            "webgrid-node:firefox=firefox::68.7.0esr,webgrid-node:chrome=chrome::81.0.4044.122"
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            images
+                .match_against_capabilities(request)
+                .map(|c| &c.identifier),
+            Some(&"webgrid-node:chrome".to_owned())
+        );
+    }
+
+    #[test]
+    fn match_browser_version_partially() {
+        let capabilities =
+            "{\"firstMatch\":[{\"browserName\":\"chrome\",\"browserVersion\":\"60.0.4044\"}]}";
+        let request: CapabilitiesRequest = serde_json::from_str(capabilities).unwrap();
+
+        let images: ContainerImageSet =
+            "webgrid-node:chrome1=chrome::60.0.4044.122,webgrid-node:chrome2=chrome::81.0.4044.122"
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            images
+                .match_against_capabilities(request)
+                .map(|c| &c.identifier),
+            Some(&"webgrid-node:chrome1".to_owned())
+        );
+    }
+
+    #[test]
+    fn match_browser_version_fully() {
+        let capabilities =
+            "{\"firstMatch\":[{\"browserName\":\"chrome\",\"browserVersion\":\"81.0.4044.122\"}]}";
+        let request: CapabilitiesRequest = serde_json::from_str(capabilities).unwrap();
+
+        let images: ContainerImageSet =
+            "webgrid-node:chrome1=chrome::60.0.4044.122,webgrid-node:chrome2=chrome::81.0.4044.122"
+                .parse()
+                .unwrap();
+
+        assert_eq!(
+            images
+                .match_against_capabilities(request)
+                .map(|c| &c.identifier),
+            Some(&"webgrid-node:chrome2".to_owned())
+        );
+    }
+
+    fn match_identifier(capabilities: &str, images: &str) -> Option<String> {
+        let request: CapabilitiesRequest = serde_json::from_str(capabilities).unwrap();
+        let images: ContainerImageSet = images.parse().unwrap();
+
+        images
+            .match_against_capabilities(request)
+            .map(|c| c.identifier.clone())
+    }
+
+    #[test]
+    fn not_match_version_by_string_prefix() {
+        let capabilities =
+            "{\"firstMatch\":[{\"browserName\":\"chrome\",\"browserVersion\":\"8\"}]}";
+
+        assert_eq!(
+            match_identifier(capabilities, "webgrid-node:chrome=chrome::81.0.4044.122"),
+            None
+        );
+    }
+
+    #[test]
+    fn match_browser_name_alias() {
+        let capabilities = "{\"firstMatch\":[{\"browserName\":\"MicrosoftEdge\"}]}";
+
+        assert_eq!(
+            match_identifier(capabilities, "webgrid-node:edge=edge::114.0"),
+            Some("webgrid-node:edge".to_owned())
+        );
+    }
+
+    #[test]
+    fn match_newest_version_in_range() {
+        let capabilities =
+            "{\"firstMatch\":[{\"browserName\":\"chrome\",\"browserVersion\":\">=100\"}]}";
+        let images = "chrome1=chrome::100.0,chrome3=chrome::99.0,chrome2=chrome::114.0";
+
+        assert_eq!(
+            match_identifier(capabilities, images),
+            Some("chrome2".to_owned())
+        );
+    }
+
+    #[test]
+    fn match_newest_image_deterministically() {
+        let capabilities = "{\"firstMatch\":[{\"browserName\":\"chrome\"}]}";
+        let images = "chrome1=chrome::114.0,chrome2=chrome::114.0,old=chrome::99.0";
+
+        assert_eq!(
+            match_identifier(capabilities, images),
+            Some("chrome1".to_owned())
+        );
+    }
+
+    #[test]
+    fn match_version_keywords() {
+        let capabilities =
+            "{\"firstMatch\":[{\"browserName\":\"firefox\",\"browserVersion\":\"stable\"}]}";
+        let images = "beta=firefox::116.0b3,release=firefox::115.0";
+
+        assert_eq!(
+            match_identifier(capabilities, images),
+            Some("release".to_owned())
+        );
+    }
+
+    #[test]
+    fn match_platform_name() {
+        let images = "webgrid-node:chrome=chrome::81.0";
+
+        assert_eq!(
+            match_identifier("{\"firstMatch\":[{\"platformName\":\"Linux\"}]}", images),
+            Some("webgrid-node:chrome".to_owned())
+        );
+        assert_eq!(
+            match_identifier("{\"firstMatch\":[{\"platformName\":\"windows\"}]}", images),
+            None
+        );
+    }
+
+    #[test]
+    fn parse_image_constraints() {
+        let image: ContainerImage =
+            "webgrid/node-chrome=chrome::82|webgrid:options.gpu=true|acme:locale=de-DE"
+                .parse()
+                .unwrap();
+
+        assert_eq!(image.browser.version, "82");
+        assert_eq!(image.constraints.len(), 2);
+        assert_eq!(image.constraints[0].path, "webgrid:options.gpu");
+        assert_eq!(image.constraints[1].value, serde_json::json!("de-DE"));
+    }
+
+    #[test]
+    fn match_constrained_image_only_when_requested() {
+        let images = "plain=chrome::81.0,gpu=chrome::81.0|webgrid:options.gpu=true";
+
+        assert_eq!(
+            match_identifier("{\"firstMatch\":[{\"browserName\":\"chrome\"}]}", images),
+            Some("plain".to_owned())
+        );
+        assert_eq!(
+            match_identifier(
+                "{\"firstMatch\":[{\"browserName\":\"chrome\",\"webgrid:options\":{\"gpu\":true}}]}",
+                images
+            ),
+            Some("gpu".to_owned())
+        );
+    }
+
+    #[test]
+    fn match_feature_constraint_against_configuration() {
+        let capabilities = "{\"firstMatch\":[{\"goog:chromeOptions\":{\"mobileEmulation\":{\"deviceName\":\"Pixel 2\"}}}]}";
+        let images =
+            "plain=chrome::81.0,mobile=chrome::81.0|goog:chromeOptions.mobileEmulation=true";
+
+        assert_eq!(
+            match_identifier(capabilities, images),
+            Some("mobile".to_owned())
+        );
+    }
+
+    #[test]
+    fn not_match_unsatisfied_constraint() {
+        let capabilities = "{\"firstMatch\":[{\"acme:locale\":\"fr-FR\"}]}";
+        let images = "de=chrome::81.0|acme:locale=de-DE";
+
+        assert_eq!(match_identifier(capabilities, images), None);
+    }
+
+    #[test]
+    fn inject_missing_default_capabilities() {
+        let images = ContainerImageSet::from_catalog(
+            r#"
+images:
+  - image: webgrid/node-chrome
+    browser: { name: chrome, version: "114.0" }
+    defaultCapabilities:
+      acceptInsecureCerts: true
+      acme:locale: de-DE
+      goog:chromeOptions: { args: ["--disable-gpu"] }
+"#,
+        )
+        .unwrap();
+        let image = images.iter().next().unwrap();
+        let raw = RawCapabilitiesRequest::new(
+            r#"{"alwaysMatch":{"acceptInsecureCerts":false},"firstMatch":[{"acme:locale":"en-US"}]}"#
+                .into(),
+        );
+
+        let injected: Value =
+            serde_json::from_str(image.inject_default_capabilities(&raw).unwrap().as_str())
+                .unwrap();
+
+        assert_eq!(
+            injected,
+            serde_json::json!({
+                "alwaysMatch": {
+                    "acceptInsecureCerts": false,
+                    "goog:chromeOptions": { "args": ["--disable-gpu"] }
+                },
+                "firstMatch": [{ "acme:locale": "en-US" }]
+            })
+        );
+    }
+}
@@ -9,6 +9,8 @@ pub enum WebgridServiceDescriptor {
     Api,
     /// [`Node`](crate::module::node) instance
     Node(SessionIdentifier),
+    /// Live view endpoint of a [`Node`](crate::module::node) instance
+    LiveView(SessionIdentifier),
 }
 
 impl ServiceDescriptor for WebgridServiceDescriptor {
@@ -16,6 +18,7 @@ impl ServiceDescriptor for WebgridServiceDescriptor {
         match self {
             WebgridServiceDescriptor::Api => "api".into(),
             WebgridServiceDescriptor::Node(identifier) => format!("node-{}", identifier),
+            WebgridServiceDescriptor::LiveView(identifier) => format!("live-{}", identifier),
         }
     }
 }
@@ -36,3 +36,51 @@ impl Notification for ProvisioningJobAssignedNotification {
         QueueDescriptor::new(QUEUE_KEY.into(), QUEUE_SIZE)
     }
 }
+
+const RETURNED_QUEUE_KEY: &str = "provisioner.job.returned";
+const RETURNED_QUEUE_SIZE: usize = QUEUE_SIZE_STARTUP_WORKFLOW;
+
+/// Provisioner has handed back a job it will not fulfil
+///
+/// Fired when a provisioner stops accepting work (e.g. because it is being drained)
+/// while [`ProvisioningJobAssignedNotifications`](ProvisioningJobAssignedNotification)
+/// are still queued for it. It is also published on behalf of provisioners which
+/// have stopped sending [`ProvisionerHeartbeatNotifications`](ProvisionerHeartbeatNotification).
+/// The jobs are expected to be scheduled again on a different provisioner.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProvisioningJobReturnedNotification {
+    /// Unique identifier of the returned session
+    pub session_id: SessionIdentifier,
+
+    /// Raw [`CapabilitiesRequest`](crate::domain::webdriver::CapabilitiesRequest) json string used for scheduling.
+    pub capabilities: RawCapabilitiesRequest,
+
+    /// Identifier of the provisioner the job has originally been assigned to
+    pub provisioner: ProvisionerIdentifier,
+}
+
+impl Notification for ProvisioningJobReturnedNotification {
+    fn queue() -> QueueDescriptor {
+        QueueDescriptor::new(RETURNED_QUEUE_KEY.into(), RETURNED_QUEUE_SIZE)
+    }
+}
+
+const HEARTBEAT_QUEUE_KEY: &str = "provisioner.heartbeat";
+const HEARTBEAT_QUEUE_SIZE: usize = QUEUE_SIZE_STARTUP_WORKFLOW;
+
+/// Provisioner is alive and consuming its job queue
+///
+/// Published periodically by every provisioner. When no heartbeat has been
+/// received from a provisioner for some time, it is considered dead and the
+/// jobs queued for it are redistributed.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProvisionerHeartbeatNotification {
+    /// Identifier of the provisioner which is alive
+    pub provisioner: ProvisionerIdentifier,
+}
+
+impl Notification for ProvisionerHeartbeatNotification {
+    fn queue() -> QueueDescriptor {
+        QueueDescriptor::new(HEARTBEAT_QUEUE_KEY.into(), HEARTBEAT_QUEUE_SIZE)
+    }
+}
@@ -1,5 +1,6 @@
 use super::super::super::QUEUE_SIZE_STARTUP_WORKFLOW;
 use super::SessionIdentifier;
+use crate::recording::{RecordingFormat, RecordingPause, RecordingProfile};
 use library::communication::event::{Notification, QueueDescriptor};
 use library::communication::BlackboxError;
 use library::{AccumulatedPerformanceMetrics, BoxedError};
@@ -21,8 +22,17 @@ pub enum DeathReason {
     Killed(String),
     /// Predetermined lifetime has been exceeded
     LifetimeExceeded,
+    /// Absolute maximum duration has been exceeded
+    MaxDurationExceeded,
     /// SIGINT or other process-external cause
     Terminated,
+    /// Browser or driver process exited unexpectedly
+    BrowserCrashed {
+        /// Exit status of the driver, if it was the driver that exited
+        exit_status: Option<String>,
+        /// Most recent output of the driver
+        log_tail: String,
+    },
 }
 
 /// Reason why a module has terminated
@@ -72,9 +82,20 @@ pub enum SessionTerminationReason {
     /// No requests have been received within the idle timeout period
     #[error("No requests have been received within the idle timeout period")]
     IdleTimeoutReached,
+    /// Session has been running for longer than its maximum duration
+    #[error("Session has been running for longer than its maximum duration")]
+    MaxDurationExceeded,
     /// External process signals terminated the session
     #[error("External process signals terminated the session")]
     TerminatedExternally,
+    /// Browser or driver process exited while the session was still in use
+    #[error("The browser or driver exited unexpectedly ({})", exit_status.as_deref().unwrap_or("browser process vanished"))]
+    BrowserCrashed {
+        /// Exit status of the driver, if it was the driver that exited
+        exit_status: Option<String>,
+        /// Most recent output of the driver
+        log_tail: String,
+    },
 }
 
 /// Session has terminated and is no longer reachable
@@ -94,6 +115,34 @@ pub struct SessionTerminatedNotification {
 
     /// Performance metrics collected for each process
     pub profiling_data: HashMap<String, AccumulatedPerformanceMetrics>,
+
+    /// Names of screenshots uploaded to the storage directory of the session
+    #[serde(default)]
+    pub screenshots: Vec<String>,
+
+    /// Intervals during which the recording has been paused by the client
+    #[serde(default)]
+    pub recording_pauses: Vec<RecordingPause>,
+
+    /// Codec profile used to encode the recording, empty if the session has not been recorded
+    #[serde(default)]
+    pub recording_profile: Option<RecordingProfile>,
+
+    /// Kind of recording, empty if the session has not been recorded
+    #[serde(default)]
+    pub recording_format: Option<RecordingFormat>,
+
+    /// Names of recording objects which could not be uploaded to the storage backend
+    #[serde(default)]
+    pub lost_segments: Vec<String>,
+
+    /// Whether the command journal has been uploaded to the storage directory of the session
+    #[serde(default)]
+    pub command_journal: bool,
+
+    /// Whether the driver log has been uploaded to the storage directory of the session
+    #[serde(default)]
+    pub driver_log: bool,
 }
 
 impl Notification for SessionTerminatedNotification {
@@ -110,6 +159,13 @@ impl SessionTerminatedNotification {
             reason: SessionTerminationReason::StartupFailed { error },
             recording_bytes: 0,
             profiling_data: HashMap::new(),
+            screenshots: Vec::new(),
+            recording_pauses: Vec::new(),
+            recording_profile: None,
+            recording_format: None,
+            lost_segments: Vec::new(),
+            command_journal: false,
+            driver_log: false,
         }
     }
 }
@@ -131,9 +187,19 @@ impl From<ModuleTerminationReason> for SessionTerminationReason {
             ModuleTerminationReason::HeartDied(DeathReason::LifetimeExceeded) => {
                 SessionTerminationReason::IdleTimeoutReached
             }
+            ModuleTerminationReason::HeartDied(DeathReason::MaxDurationExceeded) => {
+                SessionTerminationReason::MaxDurationExceeded
+            }
             ModuleTerminationReason::HeartDied(DeathReason::Terminated) => {
                 SessionTerminationReason::TerminatedExternally
             }
+            ModuleTerminationReason::HeartDied(DeathReason::BrowserCrashed {
+                exit_status,
+                log_tail,
+            }) => SessionTerminationReason::BrowserCrashed {
+                exit_status,
+                log_tail,
+            },
             ModuleTerminationReason::Timeout => SessionTerminationReason::ModuleTimeout,
             ModuleTerminationReason::ExitedNormally => unreachable!(),
         }
@@ -145,7 +211,12 @@ impl fmt::Display for DeathReason {
         match self {
             DeathReason::Killed(reason) => write!(w, "Killed ({})", reason),
             DeathReason::LifetimeExceeded => write!(w, "Lifetime was exceeded"),
+            DeathReason::MaxDurationExceeded => write!(w, "Maximum duration was exceeded"),
             DeathReason::Terminated => write!(w, "Terminated due to external signal"),
+            DeathReason::BrowserCrashed { exit_status, .. } => match exit_status {
+                Some(status) => write!(w, "Browser crashed ({})", status),
+                None => write!(w, "Browser crashed"),
+            },
         }
     }
 }
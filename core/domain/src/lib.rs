@@ -15,6 +15,7 @@ mod session;
 
 pub mod container;
 pub mod event;
+pub mod recording;
 pub mod request;
 pub mod webdriver;
 
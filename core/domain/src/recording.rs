@@ -0,0 +1,637 @@
+//! Processing of screen recordings stored as fragmented MP4 HLS playlists
+
+use crate::{event::SessionIdentifier, storage_path};
+use chrono::{DateTime, SecondsFormat, Utc};
+use library::storage::StorageBackend;
+use library::{BoxedError, EmptyResult};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write};
+use std::str::FromStr;
+use thiserror::Error;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tracing::debug;
+
+/// Name of the HLS playlist written by the screen recording
+pub const PLAYLIST_FILENAME: &str = "screen.m3u8";
+
+/// Name of the media playlist once `screen.m3u8` has been turned into a master playlist by [`attach_subtitles`]
+pub const VIDEO_PLAYLIST_FILENAME: &str = "video.m3u8";
+
+/// Name of the WebVTT subtitles attached to the recording
+pub const SUBTITLES_FILENAME: &str = "commands.vtt";
+
+/// Name of the HLS playlist referencing the subtitles
+pub const SUBTITLES_PLAYLIST_FILENAME: &str = "commands.m3u8";
+
+/// Name of the cached MP4 export in the storage directory of the session
+pub const EXPORT_FILENAME: &str = "screen.mp4";
+
+/// Name of the index listing all frames of a time-lapse recording
+pub const TIME_LAPSE_INDEX_FILENAME: &str = "timelapse.json";
+
+/// Kind of recording created for a session
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RecordingFormat {
+    /// Continuous video encoded with ffmpeg as an HLS playlist
+    Video,
+    /// Screenshots taken at a fixed interval, listed in a [`TimeLapseIndex`]
+    TimeLapse,
+}
+
+impl FromStr for RecordingFormat {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "video" => Ok(Self::Video),
+            "timelapse" => Ok(Self::TimeLapse),
+            _ => Err("unknown recording format"),
+        }
+    }
+}
+
+impl fmt::Display for RecordingFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RecordingFormat::Video => "video",
+            RecordingFormat::TimeLapse => "timeLapse",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// Single screenshot of a time-lapse recording
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeLapseFrame {
+    /// Object name relative to the storage directory of the session
+    pub name: String,
+    /// Time at which the screenshot has been taken
+    pub captured_at: DateTime<Utc>,
+}
+
+/// Index of a time-lapse recording which players use to show the frames in sequence
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeLapseIndex {
+    /// Seconds between two consecutive frames
+    pub interval: f64,
+    /// Frames in the order they have been captured
+    pub frames: Vec<TimeLapseFrame>,
+    /// Whether the session terminated and no more frames will be added
+    pub complete: bool,
+}
+
+impl TimeLapseIndex {
+    /// Creates an empty index for frames captured at the given interval
+    pub fn new(interval: f64) -> Self {
+        Self {
+            interval,
+            frames: Vec::new(),
+            complete: false,
+        }
+    }
+
+    /// Object name of the next frame to be captured
+    pub fn next_frame_name(&self) -> String {
+        format!("timelapse{}.jpg", self.frames.len())
+    }
+}
+
+/// Codec used to encode the screen recording
+///
+/// All profiles produce HLS playlists with fragmented MP4 segments.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingProfile {
+    /// H.264, cheap to encode and playable virtually everywhere
+    H264,
+    /// VP9, smaller than H.264 at the same quality for a moderate increase in CPU usage
+    Vp9,
+    /// AV1, archive-grade compression at a significantly higher CPU usage
+    Av1,
+}
+
+impl RecordingProfile {
+    /// Constant rate factor used unless configured otherwise
+    pub fn default_crf(&self) -> u8 {
+        match self {
+            RecordingProfile::H264 => 46,
+            RecordingProfile::Vp9 => 50,
+            RecordingProfile::Av1 => 52,
+        }
+    }
+
+    /// Upper bitrate bound in bits per second used unless configured otherwise
+    pub fn default_max_bitrate(&self) -> usize {
+        match self {
+            RecordingProfile::H264 => 450_000,
+            RecordingProfile::Vp9 => 350_000,
+            RecordingProfile::Av1 => 250_000,
+        }
+    }
+}
+
+impl FromStr for RecordingProfile {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "h264" => Ok(Self::H264),
+            "vp9" => Ok(Self::Vp9),
+            "av1" => Ok(Self::Av1),
+            _ => Err("unknown recording profile"),
+        }
+    }
+}
+
+impl fmt::Display for RecordingProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RecordingProfile::H264 => "h264",
+            RecordingProfile::Vp9 => "vp9",
+            RecordingProfile::Av1 => "av1",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+/// MIME type of objects belonging to a recording, based on their name
+pub fn content_type(name: &str) -> Option<&'static str> {
+    let extension = name.rsplit('.').next()?;
+
+    match extension {
+        "m3u8" => Some("application/vnd.apple.mpegurl"),
+        "m4s" => Some("video/iso.segment"),
+        "mp4" => Some("video/mp4"),
+        "vtt" => Some("text/vtt"),
+        "jpg" => Some("image/jpeg"),
+        "json" => Some("application/json"),
+        _ => None,
+    }
+}
+
+/// Interval during which the screen recording has been paused by the client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingPause {
+    /// Time at which the capture has been paused
+    pub start: DateTime<Utc>,
+    /// Time at which the capture has been resumed, empty while it is still paused
+    pub end: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Error, PartialEq)]
+enum ExportError {
+    #[error("playlist does not declare an initialization section")]
+    MissingInitialization,
+    #[error("playlist contains no segments")]
+    NoSegments,
+    #[error("playlist does not contain a program date time")]
+    MissingProgramDateTime,
+}
+
+/// Contents of a fragmented MP4 HLS playlist
+#[derive(Debug, PartialEq)]
+struct Playlist {
+    initialization: String,
+    segments: Vec<String>,
+    duration: f64,
+    started_at: Option<DateTime<Utc>>,
+    complete: bool,
+}
+
+impl Playlist {
+    fn parse(content: &str) -> Result<Self, ExportError> {
+        let mut initialization = None;
+        let mut segments = Vec::new();
+        let mut duration = 0.0;
+        let mut started_at = None;
+        let mut complete = false;
+
+        for line in content.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            if let Some(attributes) = line.strip_prefix("#EXT-X-MAP:") {
+                initialization = attributes
+                    .split(',')
+                    .find_map(|attribute| attribute.strip_prefix("URI="))
+                    .map(|uri| uri.trim_matches('"').to_owned());
+            } else if let Some(attributes) = line.strip_prefix("#EXTINF:") {
+                duration += attributes
+                    .split(',')
+                    .next()
+                    .and_then(|d| d.parse::<f64>().ok())
+                    .unwrap_or_default();
+            } else if let Some(date) = line.strip_prefix("#EXT-X-PROGRAM-DATE-TIME:") {
+                if started_at.is_none() {
+                    started_at = parse_program_date_time(date);
+                }
+            } else if line == "#EXT-X-ENDLIST" {
+                complete = true;
+            } else if !line.starts_with('#') {
+                segments.push(line.to_owned());
+            }
+        }
+
+        if segments.is_empty() {
+            return Err(ExportError::NoSegments);
+        }
+
+        Ok(Self {
+            initialization: initialization.ok_or(ExportError::MissingInitialization)?,
+            segments,
+            duration,
+            started_at,
+            complete,
+        })
+    }
+
+    /// Returns the location of the first variant if the given content is a master playlist
+    fn variant(content: &str) -> Option<&str> {
+        content
+            .lines()
+            .map(str::trim)
+            .skip_while(|line| !line.starts_with("#EXT-X-STREAM-INF:"))
+            .find(|line| !line.is_empty() && !line.starts_with('#'))
+    }
+}
+
+/// Loads and parses the media playlist of a recording, following the master playlist if there is one
+async fn load_playlist<S: StorageBackend>(
+    storage: &S,
+    session_id: SessionIdentifier,
+) -> Result<Playlist, BoxedError> {
+    let content = storage
+        .get_object(&object_path(session_id, PLAYLIST_FILENAME))
+        .await?;
+    let mut content = String::from_utf8_lossy(&content).into_owned();
+
+    if let Some(variant) = Playlist::variant(&content).map(str::to_owned) {
+        let media = storage
+            .get_object(&object_path(session_id, &variant))
+            .await?;
+        content = String::from_utf8_lossy(&media).into_owned();
+    }
+
+    Ok(Playlist::parse(&content)?)
+}
+
+fn parse_program_date_time(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f%z")
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Marks pauses in a media playlist
+///
+/// Each pause is announced by a discontinuity and a date range of the class `dev.webgrid.pause`
+/// in front of the first segment that started after the capture has been paused.
+pub fn annotate_pauses(playlist: &str, pauses: &[RecordingPause]) -> String {
+    let mut pending = pauses.iter().enumerate().peekable();
+    let mut output = String::with_capacity(playlist.len());
+
+    for line in playlist.lines() {
+        let segment_start = line
+            .strip_prefix("#EXT-X-PROGRAM-DATE-TIME:")
+            .and_then(parse_program_date_time);
+
+        if let Some(segment_start) = segment_start {
+            while let Some((index, pause)) = pending.next_if(|(_, p)| p.start <= segment_start) {
+                write!(
+                    output,
+                    "#EXT-X-DATERANGE:ID=\"pause-{}\",CLASS=\"dev.webgrid.pause\",START-DATE=\"{}\"",
+                    index + 1,
+                    pause.start.to_rfc3339_opts(SecondsFormat::Millis, true)
+                )
+                .ok();
+
+                if let Some(end) = pause.end {
+                    write!(
+                        output,
+                        ",END-DATE=\"{}\"",
+                        end.to_rfc3339_opts(SecondsFormat::Millis, true)
+                    )
+                    .ok();
+                }
+
+                output.push_str("\n#EXT-X-DISCONTINUITY\n");
+            }
+        }
+
+        output.push_str(line);
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Recording written by [`download_recording`]
+pub struct DownloadedRecording {
+    /// Number of bytes written
+    pub size: u64,
+    /// Total duration in seconds as announced by the playlist
+    pub duration: f64,
+    /// Whether the recording has finished, i.e. no more segments will be added
+    pub complete: bool,
+}
+
+/// Writes the recorded HLS segments of a session as a single fragmented MP4 file
+///
+/// Since the recording uses fMP4 segments, concatenating the initialization section with all media segments
+/// yields a playable file without re-encoding. Only a single segment is held in memory at a time.
+pub async fn download_recording<S, W>(
+    storage: &S,
+    session_id: SessionIdentifier,
+    writer: &mut W,
+) -> Result<DownloadedRecording, BoxedError>
+where
+    S: StorageBackend,
+    W: AsyncWrite + Unpin,
+{
+    let playlist = load_playlist(storage, session_id).await?;
+    let mut size = 0;
+
+    for name in std::iter::once(&playlist.initialization).chain(playlist.segments.iter()) {
+        let segment = storage.get_object(&object_path(session_id, name)).await?;
+        writer.write_all(&segment).await?;
+        size += segment.len() as u64;
+    }
+
+    writer.flush().await?;
+
+    Ok(DownloadedRecording {
+        size,
+        duration: playlist.duration,
+        complete: playlist.complete,
+    })
+}
+
+/// Source from which the MP4 export of a recording can be served
+pub enum RecordingExport {
+    /// Export which has been cached once the recording finished
+    Cached(Vec<u8>),
+    /// Storage paths of the initialization section and all media segments in playback order.
+    /// Concatenating their contents yields the export.
+    Segments(Vec<String>),
+}
+
+/// Provides the recording of a session as a single MP4 file
+///
+/// Exports of finished recordings may have been cached in the storage backend by [`cache_export`].
+/// For all other recordings, the segments are returned so that they can be fetched one at a time.
+pub async fn export_recording<S: StorageBackend>(
+    storage: &S,
+    session_id: SessionIdentifier,
+) -> Result<RecordingExport, BoxedError> {
+    if let Ok(cached) = storage
+        .get_object(&object_path(session_id, EXPORT_FILENAME))
+        .await
+    {
+        return Ok(RecordingExport::Cached(cached));
+    }
+
+    let playlist = load_playlist(storage, session_id).await?;
+    let paths = std::iter::once(&playlist.initialization)
+        .chain(playlist.segments.iter())
+        .map(|name| object_path(session_id, name))
+        .collect();
+
+    Ok(RecordingExport::Segments(paths))
+}
+
+/// Stores the video of a finished recording as the export of the session
+pub async fn cache_export<S: StorageBackend>(
+    storage: &S,
+    session_id: SessionIdentifier,
+    video: &[u8],
+) -> EmptyResult {
+    store_object(storage, session_id, EXPORT_FILENAME, video).await?;
+    debug!(?session_id, "Cached recording export");
+
+    Ok(())
+}
+
+/// Point in time at which the first frame of the recording has been captured
+pub async fn recording_start<S: StorageBackend>(
+    storage: &S,
+    session_id: SessionIdentifier,
+) -> Result<DateTime<Utc>, BoxedError> {
+    let playlist = load_playlist(storage, session_id).await?;
+    playlist
+        .started_at
+        .ok_or_else(|| ExportError::MissingProgramDateTime.into())
+}
+
+/// Adds a WebVTT subtitles rendition to a finished recording
+///
+/// The media playlist is moved to `video.m3u8` and `screen.m3u8` is replaced by a master playlist
+/// which references both the video and the subtitles. The bandwidth is announced to players as the peak bitrate.
+pub async fn attach_subtitles<S: StorageBackend>(
+    storage: &S,
+    session_id: SessionIdentifier,
+    webvtt: &str,
+    bandwidth: usize,
+) -> EmptyResult {
+    let media = storage
+        .get_object(&object_path(session_id, PLAYLIST_FILENAME))
+        .await?;
+    let media = String::from_utf8_lossy(&media);
+
+    // Subtitles have already been attached
+    if Playlist::variant(&media).is_some() {
+        return Ok(());
+    }
+
+    let playlist = Playlist::parse(&media)?;
+
+    // The master playlist is written last so that players never see a dangling reference
+    let objects = vec![
+        (VIDEO_PLAYLIST_FILENAME, media.to_string()),
+        (SUBTITLES_FILENAME, webvtt.to_owned()),
+        (
+            SUBTITLES_PLAYLIST_FILENAME,
+            subtitles_playlist(playlist.duration),
+        ),
+        (PLAYLIST_FILENAME, master_playlist(bandwidth)),
+    ];
+
+    for (name, content) in objects {
+        store_object(storage, session_id, name, content.as_bytes()).await?;
+    }
+
+    Ok(())
+}
+
+fn subtitles_playlist(duration: f64) -> String {
+    format!(
+        "#EXTM3U\n#EXT-X-VERSION:3\n#EXT-X-TARGETDURATION:{target}\n#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXTINF:{duration:.3},\n{uri}\n#EXT-X-ENDLIST\n",
+        target = duration.ceil().max(1.0),
+        duration = duration,
+        uri = SUBTITLES_FILENAME,
+    )
+}
+
+fn master_playlist(bandwidth: usize) -> String {
+    format!(
+        "#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-MEDIA:TYPE=SUBTITLES,GROUP-ID=\"commands\",NAME=\"Commands\",LANGUAGE=\"en\",DEFAULT=YES,AUTOSELECT=YES,URI=\"{subtitles}\"\n#EXT-X-STREAM-INF:BANDWIDTH={bandwidth},SUBTITLES=\"commands\"\n{video}\n",
+        subtitles = SUBTITLES_PLAYLIST_FILENAME,
+        bandwidth = bandwidth,
+        video = VIDEO_PLAYLIST_FILENAME,
+    )
+}
+
+/// Uploads an object belonging to the recording of a session along with its MIME type
+pub async fn store_object<S: StorageBackend>(
+    storage: &S,
+    session_id: SessionIdentifier,
+    name: &str,
+    content: &[u8],
+) -> EmptyResult {
+    let path = object_path(session_id, name);
+
+    match content_type(name) {
+        Some(content_type) => {
+            storage
+                .put_object_with_content_type(&path, content, content_type)
+                .await
+        }
+        None => storage.put_object(&path, content).await,
+    }
+}
+
+fn object_path(session_id: SessionIdentifier, name: &str) -> String {
+    storage_path(session_id, name)
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    const PLAYLIST: &str = r#"#EXTM3U
+#EXT-X-VERSION:7
+#EXT-X-TARGETDURATION:6
+#EXT-X-MEDIA-SEQUENCE:0
+#EXT-X-PLAYLIST-TYPE:EVENT
+#EXT-X-MAP:URI="init.mp4"
+#EXT-X-PROGRAM-DATE-TIME:2021-03-01T10:00:00.000+0000
+#EXTINF:6.000000,
+screen0.m4s
+#EXTINF:6.000000,
+screen1.m4s
+"#;
+
+    #[test]
+    fn parse_playlist() {
+        assert_eq!(
+            Playlist::parse(PLAYLIST),
+            Ok(Playlist {
+                initialization: "init.mp4".into(),
+                segments: vec!["screen0.m4s".into(), "screen1.m4s".into()],
+                duration: 12.0,
+                started_at: Some("2021-03-01T10:00:00Z".parse().unwrap()),
+                complete: false,
+            })
+        );
+    }
+
+    #[test]
+    fn detect_complete_playlist() {
+        let playlist = format!("{}#EXT-X-ENDLIST\n", PLAYLIST);
+        assert!(Playlist::parse(&playlist).unwrap().complete);
+    }
+
+    #[test]
+    fn reject_invalid_playlists() {
+        assert_eq!(
+            Playlist::parse("#EXTM3U\n#EXT-X-MAP:URI=\"init.mp4\"\n"),
+            Err(ExportError::NoSegments)
+        );
+        assert_eq!(
+            Playlist::parse("#EXTM3U\n#EXTINF:6.0,\nscreen0.m4s\n"),
+            Err(ExportError::MissingInitialization)
+        );
+    }
+
+    #[test]
+    fn follow_master_playlist() {
+        assert_eq!(
+            Playlist::variant(&master_playlist(450000)),
+            Some(VIDEO_PLAYLIST_FILENAME)
+        );
+        assert_eq!(Playlist::variant(PLAYLIST), None);
+    }
+
+    #[test]
+    fn reference_subtitles() {
+        let playlist = subtitles_playlist(12.5);
+
+        assert!(playlist.contains("#EXT-X-TARGETDURATION:13\n"));
+        assert!(playlist.contains("#EXTINF:12.500,\ncommands.vtt\n"));
+        assert!(master_playlist(450000).contains("URI=\"commands.m3u8\""));
+    }
+
+    #[test]
+    fn annotate_pauses_before_following_segment() {
+        let playlist = format!(
+            "{}#EXT-X-PROGRAM-DATE-TIME:2021-03-01T10:00:12.000+0000\n#EXTINF:6.000000,\nscreen2.m4s\n",
+            PLAYLIST
+        );
+        let pauses = vec![RecordingPause {
+            start: "2021-03-01T10:00:08Z".parse().unwrap(),
+            end: Some("2021-03-01T10:00:11.5Z".parse().unwrap()),
+        }];
+
+        let annotated = annotate_pauses(&playlist, &pauses);
+
+        assert!(annotated.contains(
+            "screen1.m4s\n#EXT-X-DATERANGE:ID=\"pause-1\",CLASS=\"dev.webgrid.pause\",START-DATE=\"2021-03-01T10:00:08.000Z\",END-DATE=\"2021-03-01T10:00:11.500Z\"\n#EXT-X-DISCONTINUITY\n#EXT-X-PROGRAM-DATE-TIME:2021-03-01T10:00:12.000+0000\n"
+        ));
+        assert_eq!(annotated.matches("#EXT-X-DISCONTINUITY").count(), 1);
+        assert_eq!(
+            Playlist::parse(&annotated).unwrap().segments,
+            vec!["screen0.m4s", "screen1.m4s", "screen2.m4s"]
+        );
+    }
+
+    #[test]
+    fn parse_recording_profile() {
+        for profile in [
+            RecordingProfile::H264,
+            RecordingProfile::Vp9,
+            RecordingProfile::Av1,
+        ] {
+            assert_eq!(profile.to_string().parse(), Ok(profile));
+            assert_eq!(
+                serde_json::to_string(&profile).unwrap(),
+                format!("\"{}\"", profile)
+            );
+        }
+
+        assert!("hevc".parse::<RecordingProfile>().is_err());
+    }
+
+    #[test]
+    fn parse_recording_format() {
+        for format in [RecordingFormat::Video, RecordingFormat::TimeLapse] {
+            assert_eq!(format.to_string().parse(), Ok(format));
+            assert_eq!(
+                serde_json::to_string(&format).unwrap(),
+                format!("\"{}\"", format)
+            );
+        }
+    }
+
+    #[test]
+    fn determine_content_type() {
+        assert_eq!(
+            content_type("screen.m3u8"),
+            Some("application/vnd.apple.mpegurl")
+        );
+        assert_eq!(content_type("/screen12.m4s"), Some("video/iso.segment"));
+        assert_eq!(content_type("ffmpeg.log"), None);
+    }
+}
@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use super::event::{
     ProvisionedSessionMetadata, ProvisionerIdentifier, SessionIdentifier, SessionTerminationReason,
 };
+use crate::recording::{RecordingFormat, RecordingPause, RecordingProfile};
 use bson::serde_helpers::uuid_as_binary;
 use chrono::{DateTime, Utc};
 use library::helpers::option_chrono_datetime_as_bson_datetime;
@@ -59,6 +60,33 @@ pub struct SessionMetadata {
     /// Performance metrics collected for each process
     pub profiling_data: HashMap<String, AccumulatedPerformanceMetrics>,
 
+    /// Names of screenshots uploaded to the storage directory of the session
+    #[serde(default)]
+    pub screenshots: Vec<String>,
+
+    /// Intervals during which the recording has been paused by the client
+    #[serde(default)]
+    pub recording_pauses: Vec<RecordingPause>,
+
+    /// Codec profile used to encode the recording
+    pub recording_profile: Option<RecordingProfile>,
+
+    /// Kind of recording
+    #[serde(default)]
+    pub recording_format: Option<RecordingFormat>,
+
+    /// Names of recording objects which could not be uploaded to the storage backend
+    #[serde(default)]
+    pub lost_segments: Vec<String>,
+
+    /// Whether the command journal has been uploaded to the storage directory of the session
+    #[serde(default)]
+    pub command_journal: bool,
+
+    /// Whether the driver log has been uploaded to the storage directory of the session
+    #[serde(default)]
+    pub driver_log: bool,
+
     /// Reason why the session terminated
     pub termination: Option<SessionTerminationReason>,
 }
@@ -80,6 +108,13 @@ impl SessionMetadata {
             client_metadata: HashMap::new(),
             recording_bytes: None,
             profiling_data: HashMap::new(),
+            screenshots: Vec::new(),
+            recording_pauses: Vec::new(),
+            recording_profile: None,
+            recording_format: None,
+            lost_segments: Vec::new(),
+            command_journal: false,
+            driver_log: false,
             termination: None,
         }
     }
@@ -2,6 +2,7 @@
 //!
 //! This module contains data structures to deserialize the capability object described in the [W3C WebDriver Specification](https://www.w3.org/TR/webdriver1/#capabilities).
 
+use crate::recording::{RecordingFormat, RecordingProfile};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -52,6 +53,32 @@ pub enum CapabilityUnhandledPromptBehavior {
     Ignore,
 }
 
+/// Verbosity of the log output written by the webdriver
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum DriverLogLevel {
+    /// Everything including protocol traffic
+    Trace,
+    /// Detailed diagnostic information
+    Debug,
+    /// Regular operational messages
+    Info,
+    /// Unexpected but recoverable conditions
+    Warn,
+    /// Failures only
+    Error,
+}
+
+/// Determines which recordings are kept in the storage backend
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RecordingMode {
+    /// Every recording is stored, even while the session is still running
+    Always,
+    /// Recordings are staged on the node and only stored if the session failed
+    OnFailure,
+}
+
 /// HTTP proxy settings
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
@@ -88,10 +115,50 @@ pub struct WebGridOptions {
     #[serde(default)]
     pub disable_recording: bool,
 
+    /// Controls whether the recording is kept regardless of the outcome of the session, defaults to `always`
+    ///
+    /// With `onFailure`, the recording is only stored if the session has been marked as failed by the client
+    /// or terminated abnormally, e.g. due to a crash or timeout.
+    pub recording_mode: Option<RecordingMode>,
+
+    /// Codec profile used to encode the recording, either `h264`, `vp9`, or `av1`
+    ///
+    /// When omitted, the default profile configured by the administrator is used.
+    pub recording_profile: Option<RecordingProfile>,
+
+    /// Kind of recording, either a `video` or a `timeLapse` of screenshots taken at a fixed interval
+    ///
+    /// Time-lapse recordings use significantly less CPU than videos. When omitted, the default format configured
+    /// by the administrator is used.
+    pub recording_format: Option<RecordingFormat>,
+
     /// Overwrites the default idle timeout for the session in seconds
     ///
     /// If no request from the client arrives within this duration, the session will terminate itself.
     pub idle_timeout: Option<u64>,
+
+    /// Lowers the maximum duration of the session in seconds
+    ///
+    /// Once the session has been running for this long, it will terminate regardless of client activity.
+    /// Values above the maximum duration configured for the grid are ignored.
+    pub max_duration: Option<u64>,
+
+    /// Screen resolution of the session formatted like `1366x768`
+    ///
+    /// Only resolutions which have been allowed by the administrator may be requested.
+    pub screen_resolution: Option<String>,
+
+    /// Verbosity of the webdriver log which is uploaded to the storage backend
+    ///
+    /// When omitted, the default verbosity of the driver is used.
+    pub driver_log_level: Option<DriverLogLevel>,
+
+    /// Additional options which are not interpreted by WebGrid itself
+    ///
+    /// They may still be used to select a specific image through its
+    /// [`CapabilityConstraints`](crate::container::matching::CapabilityConstraint).
+    #[serde(flatten)]
+    pub extension_options: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
@@ -121,6 +188,9 @@ pub struct Capabilities {
     /// Extension capabilities specific to WebGrid
     #[serde(rename = "webgrid:options")]
     pub webgrid_options: Option<WebGridOptions>,
+    /// Requests that files downloaded by the browser can be retrieved through the Selenium file endpoints.
+    #[serde(rename = "se:downloadsEnabled")]
+    pub downloads_enabled: Option<bool>,
 
     /// Additional capabilities that are not part of the W3C standard or added by WebGrid.
     #[serde(flatten)]
@@ -165,6 +235,7 @@ impl Capabilities {
             unhandled_prompt_behavior: None,
 
             webgrid_options: None,
+            downloads_enabled: None,
             extension_capabilities: HashMap::new(),
         }
     }
@@ -205,6 +276,7 @@ impl Capabilities {
                 .xor(other.unhandled_prompt_behavior),
 
             webgrid_options: self.webgrid_options.clone().xor(other.webgrid_options),
+            downloads_enabled: self.downloads_enabled.xor(other.downloads_enabled),
             extension_capabilities,
         }
     }
@@ -221,6 +293,15 @@ pub struct CapabilitiesRequest {
 }
 
 impl CapabilitiesRequest {
+    /// Screen resolution requested by the first capability set which specifies one
+    pub fn screen_resolution(&self) -> Option<&str> {
+        self.always_match
+            .iter()
+            .chain(self.first_match.iter().flatten())
+            .filter_map(|capabilities| capabilities.webgrid_options.as_ref())
+            .find_map(|options| options.screen_resolution.as_deref())
+    }
+
     /// Converts the request into a set of possible combinations
     pub fn into_sets(self) -> Vec<Capabilities> {
         let always_match = self.always_match.unwrap_or_else(Capabilities::empty);
@@ -284,6 +365,25 @@ mod do_deserialize {
         assert_eq!(sets, vec![expected_capabilities]);
     }
 
+    #[test]
+    fn screen_resolution() {
+        let capabilities = r#"{"firstMatch":[{"browserName":"chrome"},{"webgrid:options":{"screenResolution":"1366x768"}}]}"#;
+        let parsed: CapabilitiesRequest = serde_json::from_str(capabilities).unwrap();
+
+        assert_eq!(parsed.screen_resolution(), Some("1366x768"));
+        assert_eq!(CapabilitiesRequest::default().screen_resolution(), None);
+    }
+
+    #[test]
+    fn downloads_enabled() {
+        let capabilities = r#"{"alwaysMatch":{"browserName":"chrome","se:downloadsEnabled":true}}"#;
+        let parsed: CapabilitiesRequest = serde_json::from_str(capabilities).unwrap();
+        let sets = parsed.into_sets();
+
+        assert_eq!(sets[0].downloads_enabled, Some(true));
+        assert!(sets[0].extension_capabilities.is_empty());
+    }
+
     #[test]
     fn real_world_request() {
         let capabilities = "{\"firstMatch\":[{\"browserName\":\"chrome\",\"goog:chromeOptions\":{\"args\":[\"no-sandbox\",\"disable-gpu\",\"disable-extensions\",\"disable-infobars\",\"dns-prefetch-disable\",\"no-proxy-server\",\"window-size=1920,1080\",\"start-maximized\",\"window-position=0,0\",\"--test-type\",\"disable-dev-shm-usage\"],\"extensions\":[],\"prefs\":{\"profile.default_content_settings.popups\":0}},\"proxy\":{\"proxyType\":\"direct\"}}]}";
@@ -1,3 +1,5 @@
+use hyper::header::CONTENT_TYPE;
+use hyper::{Body, Response, StatusCode};
 use serde::{Deserialize, Serialize};
 
 use library::communication::BlackboxError;
@@ -8,6 +10,22 @@ pub enum WebdriverErrorCode {
     SessionNotCreated,
     /// An unknown error occurred in the remote end while processing the command.
     UnknownError,
+    /// The arguments passed to a command are either invalid or malformed.
+    InvalidArgument,
+    /// Indicates that a command that should have executed properly cannot be supported for some reason.
+    UnsupportedOperation,
+}
+
+impl WebdriverErrorCode {
+    /// HTTP status code which accompanies the error as defined by the specification
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            WebdriverErrorCode::InvalidArgument => StatusCode::BAD_REQUEST,
+            WebdriverErrorCode::SessionNotCreated
+            | WebdriverErrorCode::UnknownError
+            | WebdriverErrorCode::UnsupportedOperation => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
 }
 
 impl ToString for WebdriverErrorCode {
@@ -15,6 +33,8 @@ impl ToString for WebdriverErrorCode {
         match self {
             WebdriverErrorCode::SessionNotCreated => "session not created".into(),
             WebdriverErrorCode::UnknownError => "unknown error".into(),
+            WebdriverErrorCode::InvalidArgument => "invalid argument".into(),
+            WebdriverErrorCode::UnsupportedOperation => "unsupported operation".into(),
         }
     }
 }
@@ -63,3 +83,17 @@ impl From<(WebdriverErrorCode, BlackboxError)> for WebdriverError {
         }
     }
 }
+
+/// Builds a spec-compliant JSON response for an error which occurred while handling a request
+pub fn new_error_response(code: WebdriverErrorCode, error: BlackboxError) -> Response<Body> {
+    let status = code.status_code();
+    let webdriver_error: WebdriverError = (code, error).into();
+    let serialized = serde_json::to_string(&webdriver_error)
+        .unwrap_or_else(|_| "failed to serialize error".into());
+
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(serialized))
+        .unwrap()
+}
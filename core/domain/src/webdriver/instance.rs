@@ -1,18 +1,35 @@
+use super::capabilities::DriverLogLevel;
 use super::creation::SessionCreateResponse;
 use hyper::{
     http::{Method, Request},
     Body, Client,
 };
 use library::helpers::wait_for;
-use std::io::Error as IoError;
+use serde_json::{json, Map, Value};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{Error as IoError, Read, Seek, SeekFrom, Write};
 use std::net::SocketAddr;
 use std::path::Path;
-use std::process::Stdio;
+use std::process::{ExitStatus, Stdio};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex as StdMutex};
 use std::time::Duration;
 use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 use tokio::process::{Child, Command};
-use tracing::{debug, info, instrument, trace};
+use tokio::sync::Mutex;
+use tracing::{debug, info, instrument, trace, warn};
+
+/// Number of lines of driver output retained by the [`DriverLog`]
+const DRIVER_LOG_TAIL_LINES: usize = 50;
+
+/// Number of consecutive read errors after which capturing of driver output is abandoned
+const DRIVER_LOG_READ_ATTEMPTS: usize = 3;
+
+/// Number of bytes written to a driver log file before it is rotated
+const DRIVER_LOG_FILE_LIMIT: u64 = 8 * 1024 * 1024;
 
 struct WebDriverState {
     internal_session_id: String,
@@ -22,9 +39,27 @@ struct WebDriverState {
 /// Resolution of a graphical user interface
 ///
 /// Composited by width and height and parsable from strings like "1920x1080"
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ScreenResolution(u16, u16);
 
+impl ScreenResolution {
+    /// Horizontal number of pixels
+    pub fn width(&self) -> u16 {
+        self.0
+    }
+
+    /// Vertical number of pixels
+    pub fn height(&self) -> u16 {
+        self.1
+    }
+}
+
+impl fmt::Display for ScreenResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.0, self.1)
+    }
+}
+
 impl FromStr for ScreenResolution {
     type Err = &'static str;
 
@@ -106,6 +141,274 @@ impl WebDriverVariant {
             WebDriverVariant::Firefox => &["-p", "4444"],
         }
     }
+
+    fn log_level_arguments(&self, level: DriverLogLevel) -> Vec<String> {
+        match self {
+            WebDriverVariant::Chrome => {
+                let level = match level {
+                    DriverLogLevel::Trace => "ALL",
+                    DriverLogLevel::Debug => "DEBUG",
+                    DriverLogLevel::Info => "INFO",
+                    DriverLogLevel::Warn => "WARNING",
+                    DriverLogLevel::Error => "SEVERE",
+                };
+
+                vec![format!("--log-level={}", level)]
+            }
+            WebDriverVariant::Firefox => {
+                let level = match level {
+                    DriverLogLevel::Trace => "trace",
+                    DriverLogLevel::Debug => "debug",
+                    DriverLogLevel::Info => "info",
+                    DriverLogLevel::Warn => "warn",
+                    DriverLogLevel::Error => "error",
+                };
+
+                vec!["--log".into(), level.into()]
+            }
+            // safaridriver does not provide a configurable verbosity
+            WebDriverVariant::Safari => Vec::new(),
+        }
+    }
+
+    /// Whether the download directory of the browser can be configured
+    pub fn supports_download_directory(&self) -> bool {
+        !matches!(self, WebDriverVariant::Safari)
+    }
+
+    /// Vendor capability and browser preferences which direct downloads into the given directory
+    fn download_preferences(&self, directory: &Path) -> Option<(&'static str, Value)> {
+        let directory = directory.to_string_lossy();
+
+        match self {
+            WebDriverVariant::Chrome => Some((
+                "goog:chromeOptions",
+                json!({
+                    "download.default_directory": directory,
+                    "download.prompt_for_download": false,
+                    "download.directory_upgrade": true,
+                }),
+            )),
+            WebDriverVariant::Firefox => Some((
+                "moz:firefoxOptions",
+                json!({
+                    "browser.download.folderList": 2,
+                    "browser.download.dir": directory,
+                    "browser.download.useDownloadDir": true,
+                    "browser.download.manager.showWhenStarting": false,
+                    "browser.download.alwaysOpenPanel": false,
+                }),
+            )),
+            // safaridriver always downloads into the default location of the user
+            WebDriverVariant::Safari => None,
+        }
+    }
+}
+
+/// Merges browser preferences into the vendor options of a single capability set
+fn insert_preferences(set: &mut Value, key: &str, preferences: &Value) {
+    let options = match set.as_object_mut() {
+        Some(set) => set.entry(key).or_insert_with(|| json!({})),
+        None => return,
+    };
+
+    let target = match options.as_object_mut() {
+        Some(options) => options.entry("prefs").or_insert_with(|| json!({})),
+        None => return,
+    };
+
+    if let (Some(target), Some(preferences)) = (target.as_object_mut(), preferences.as_object()) {
+        for (name, value) in preferences {
+            target.insert(name.clone(), value.clone());
+        }
+    }
+}
+
+/// Adds browser preferences to a capabilities request which direct downloads into the given directory
+///
+/// The preferences are placed next to the vendor options provided by the client. As the specification prohibits keys
+/// which are present in both `alwaysMatch` and `firstMatch`, they are added to every `firstMatch` set if any of them
+/// contains vendor options and to `alwaysMatch` otherwise.
+fn with_download_directory(
+    capabilities: &str,
+    variant: WebDriverVariant,
+    directory: &Path,
+) -> Result<String, serde_json::Error> {
+    let (key, preferences) = match variant.download_preferences(directory) {
+        Some(preferences) => preferences,
+        None => return Ok(capabilities.to_owned()),
+    };
+
+    let mut request: Value = serde_json::from_str(capabilities)?;
+
+    let in_first_match = request
+        .get("firstMatch")
+        .and_then(Value::as_array)
+        .map_or(false, |sets| sets.iter().any(|set| set.get(key).is_some()));
+
+    if in_first_match {
+        if let Some(sets) = request.get_mut("firstMatch").and_then(Value::as_array_mut) {
+            for set in sets.iter_mut() {
+                insert_preferences(set, key, &preferences);
+            }
+        }
+    } else if let Some(request) = request.as_object_mut() {
+        let always_match = request
+            .entry("alwaysMatch")
+            .or_insert_with(|| Value::Object(Map::new()));
+        insert_preferences(always_match, key, &preferences);
+    }
+
+    serde_json::to_string(&request)
+}
+
+/// Temporary file which is replaced once it reaches a size limit, retaining only the previous generation
+#[derive(Debug)]
+struct RotatingFile {
+    current: File,
+    previous: Option<File>,
+    written: u64,
+    limit: u64,
+}
+
+impl RotatingFile {
+    fn new(limit: u64) -> Result<Self, IoError> {
+        Ok(Self {
+            current: tempfile::tempfile()?,
+            previous: None,
+            written: 0,
+            limit,
+        })
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), IoError> {
+        if self.written >= self.limit {
+            self.previous = Some(std::mem::replace(&mut self.current, tempfile::tempfile()?));
+            self.written = 0;
+        }
+
+        writeln!(self.current, "{}", line)?;
+        self.written += line.len() as u64 + 1;
+
+        Ok(())
+    }
+
+    fn contents(&mut self) -> Result<Vec<u8>, IoError> {
+        let mut contents = Vec::new();
+
+        for file in self
+            .previous
+            .iter_mut()
+            .chain(std::iter::once(&mut self.current))
+        {
+            file.seek(SeekFrom::Start(0))?;
+            file.read_to_end(&mut contents)?;
+            file.seek(SeekFrom::End(0))?;
+        }
+
+        Ok(contents)
+    }
+}
+
+/// Output written by the webdriver process
+///
+/// The most recent lines are kept in memory, while all output is written to a rotating temporary file.
+/// It is still forwarded to the stdout and stderr of the current process as well.
+#[derive(Debug, Clone, Default)]
+pub struct DriverLog {
+    lines: Arc<StdMutex<VecDeque<String>>>,
+    file: Option<Arc<StdMutex<RotatingFile>>>,
+}
+
+impl DriverLog {
+    /// Creates a new log which writes all output to a temporary file
+    pub fn with_file() -> Self {
+        let file = match RotatingFile::new(DRIVER_LOG_FILE_LIMIT) {
+            Ok(file) => Some(Arc::new(StdMutex::new(file))),
+            Err(error) => {
+                warn!(?error, "Unable to create driver log file");
+                None
+            }
+        };
+
+        Self {
+            lines: Default::default(),
+            file,
+        }
+    }
+
+    fn push(&self, line: String) {
+        if let Some(Ok(mut file)) = self.file.as_ref().map(|file| file.lock()) {
+            if let Err(error) = file.write_line(&line) {
+                warn!(?error, "Failed to write driver log");
+            }
+        }
+
+        if let Ok(mut lines) = self.lines.lock() {
+            if lines.len() >= DRIVER_LOG_TAIL_LINES {
+                lines.pop_front();
+            }
+
+            lines.push_back(line);
+        }
+    }
+
+    /// Complete output retained in the log file, limited to the two most recent file generations
+    pub fn contents(&self) -> Result<Vec<u8>, IoError> {
+        match self.file.as_ref().map(|file| file.lock()) {
+            Some(Ok(mut file)) => file.contents(),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Retained lines, separated by newlines
+    pub fn tail(&self) -> String {
+        self.lines
+            .lock()
+            .map(|lines| lines.iter().cloned().collect::<Vec<_>>().join("\n"))
+            .unwrap_or_default()
+    }
+
+    fn capture<R>(&self, reader: R, is_stderr: bool)
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let log = self.clone();
+
+        let stream = if is_stderr { "stderr" } else { "stdout" };
+
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(reader);
+            let mut buffer = Vec::new();
+            let mut failures = 0;
+
+            loop {
+                buffer.clear();
+
+                match reader.read_until(b'\n', &mut buffer).await {
+                    Ok(0) => break,
+                    Ok(_) => failures = 0,
+                    Err(error) => {
+                        failures += 1;
+                        warn!(?error, stream, "Failed to read driver output");
+
+                        if failures >= DRIVER_LOG_READ_ATTEMPTS {
+                            break;
+                        }
+
+                        continue;
+                    }
+                }
+
+                let line = String::from_utf8_lossy(&buffer)
+                    .trim_end_matches(['\n', '\r'])
+                    .to_owned();
+
+                info!(target: "webdriver", stream, "{}", line);
+                log.push(line);
+            }
+        });
+    }
 }
 
 /// Builder for a webdriver instance
@@ -115,7 +418,10 @@ pub struct WebDriver<'a> {
     variant: WebDriverVariant,
     resolution: ScreenResolution,
     capabilities: &'a str,
+    download_directory: Option<&'a Path>,
     startup_timeout: Duration,
+    log_level: Option<DriverLogLevel>,
+    log: DriverLog,
 }
 
 impl<'a> Default for WebDriver<'a> {
@@ -125,7 +431,10 @@ impl<'a> Default for WebDriver<'a> {
             variant: WebDriverVariant::Firefox,
             resolution: ScreenResolution(1920, 1080),
             capabilities: "{}",
+            download_directory: None,
             startup_timeout: Duration::from_secs(30),
+            log_level: None,
+            log: DriverLog::default(),
         }
     }
 }
@@ -155,19 +464,47 @@ impl<'a> WebDriver<'a> {
         self
     }
 
+    /// Overrides the default verbosity of the driver output
+    pub fn log_level(mut self, level: Option<DriverLogLevel>) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// Sets the log to which the driver output will be written
+    pub fn log(mut self, log: DriverLog) -> Self {
+        self.log = log;
+        self
+    }
+
     /// Determines the capabilities that will be used to create the session upon launch
     pub fn capabilities(mut self, capabilities: &'a str) -> Self {
         self.capabilities = capabilities;
         self
     }
 
+    /// Instructs the browser to store downloaded files in the given directory
+    pub fn download_directory(mut self, directory: Option<&'a Path>) -> Self {
+        self.download_directory = directory;
+        self
+    }
+
     /// Spawns an instance of the webdriver executable, creates a new session, and resizes the window
     #[instrument(err)]
     pub async fn launch(self) -> Result<WebDriverInstance, WebDriverError> {
         info!("Launching webdriver");
 
         debug!("Spawning WebDriver");
-        let process = self.spawn_process()?;
+        let log = self.log.clone();
+        let mut process = self.spawn_process()?;
+        let pid = process.id();
+
+        if let Some(stdout) = process.stdout.take() {
+            log.capture(stdout, false);
+        }
+
+        if let Some(stderr) = process.stderr.take() {
+            log.capture(stderr, true);
+        }
 
         debug!("Awaiting WebDriver startup");
         self.startup().await?;
@@ -180,7 +517,9 @@ impl<'a> WebDriver<'a> {
 
         Ok(WebDriverInstance {
             state,
-            process,
+            process: Arc::new(Mutex::new(process)),
+            pid,
+            log,
 
             socket_addr: self.socket_addr(),
         })
@@ -192,18 +531,28 @@ impl<'a> WebDriver<'a> {
 
     #[instrument(err)]
     fn spawn_process(&self) -> Result<Child, IoError> {
-        let args = self.variant.extra_arguments();
+        let mut args: Vec<String> = self
+            .variant
+            .extra_arguments()
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+
+        if let Some(level) = self.log_level {
+            args.extend(self.variant.log_level_arguments(level));
+        }
 
         trace!(?args, "Collected driver specific arguments");
 
         Command::new(&self.binary)
-            .args(args)
+            .args(&args)
             .current_dir("/")
             // TODO Re-add environment isolation
             // .env_clear()
             // .env("PATH", std::env::var("PATH").unwrap_or_default())
             .stdin(Stdio::null())
-            .stdout(Stdio::inherit())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
             .spawn()
     }
 
@@ -222,7 +571,12 @@ impl<'a> WebDriver<'a> {
     #[instrument(err)]
     async fn create_local_session(&self) -> Result<WebDriverState, WebDriverError> {
         let uri = format!("http://{}/session", self.socket_addr());
-        let body: Body = format!("{{\"capabilities\": {} }}", self.capabilities).into();
+        let capabilities = match self.download_directory {
+            Some(directory) => with_download_directory(self.capabilities, self.variant, directory)
+                .map_err(|e| WebDriverError::ParseFailure(self.capabilities.to_owned(), e))?,
+            None => self.capabilities.to_owned(),
+        };
+        let body: Body = format!("{{\"capabilities\": {} }}", capabilities).into();
 
         let client = Client::new();
         let req = Request::builder()
@@ -236,10 +590,17 @@ impl<'a> WebDriver<'a> {
 
         trace!("Parsing response body");
         let body = hyper::body::to_bytes(res.into_body()).await?;
-        let response: SessionCreateResponse = serde_json::from_slice(&body).map_err(|e| {
+        let mut response: SessionCreateResponse = serde_json::from_slice(&body).map_err(|e| {
             WebDriverError::ParseFailure(String::from_utf8_lossy(&body).to_string(), e)
         })?;
 
+        // Clients check the resulting capabilities before using the file endpoints
+        if self.download_directory.is_some() && self.variant.supports_download_directory() {
+            if let Some(capabilities) = response.value.capabilities.as_object_mut() {
+                capabilities.insert("se:downloadsEnabled".into(), Value::Bool(true));
+            }
+        }
+
         trace!("Parsing resulting capabilities");
         let capabilities = serde_json::to_string(&response.value.capabilities).map_err(|e| {
             WebDriverError::ParseFailure(String::from_utf8_lossy(&body).to_string(), e)
@@ -285,7 +646,9 @@ impl<'a> WebDriver<'a> {
 /// Running instance of a WebDriver executable
 pub struct WebDriverInstance {
     state: WebDriverState,
-    process: Child,
+    process: Arc<Mutex<Child>>,
+    pid: Option<u32>,
+    log: DriverLog,
     socket_addr: SocketAddr,
 }
 
@@ -306,16 +669,125 @@ impl WebDriverInstance {
     }
 
     /// Fetches the process id of the webdriver instance.
-    /// Returns `None` when it is no longer running.
+    /// Returns `None` when it has already exited before the launch completed.
     /// Note that this does not return the browser pid.
     pub fn pid(&self) -> Option<u32> {
-        self.process.id()
+        self.pid
+    }
+
+    /// Output written by the webdriver process
+    pub fn log(&self) -> &DriverLog {
+        &self.log
+    }
+
+    /// Creates a handle to observe the webdriver process independently of this instance
+    pub fn monitor(&self) -> WebDriverMonitor {
+        WebDriverMonitor {
+            process: self.process.clone(),
+            pid: self.pid,
+            log: self.log.clone(),
+        }
     }
 
     /// Attempts to kill the webdriver and waits for it to die in agony
-    #[instrument(err, skip(self), fields(pid = ?self.process.id(), addr = ?self.socket_addr))]
-    pub async fn kill(mut self) -> Result<(), IoError> {
+    #[instrument(err, skip(self), fields(pid = ?self.pid, addr = ?self.socket_addr))]
+    pub async fn kill(self) -> Result<(), IoError> {
         debug!("Killing webdriver process");
-        self.process.kill().await
+        self.process.lock().await.kill().await
+    }
+}
+
+/// Handle to observe a running [`WebDriverInstance`]
+#[derive(Clone)]
+pub struct WebDriverMonitor {
+    process: Arc<Mutex<Child>>,
+    pid: Option<u32>,
+    log: DriverLog,
+}
+
+impl WebDriverMonitor {
+    /// Process id of the webdriver, see [`WebDriverInstance::pid`]
+    pub fn pid(&self) -> Option<u32> {
+        self.pid
+    }
+
+    /// Returns the exit status if the webdriver process has exited, without waiting for it
+    pub async fn exit_status(&self) -> Result<Option<ExitStatus>, IoError> {
+        self.process.lock().await.try_wait()
+    }
+
+    /// Most recent output of the webdriver process
+    pub fn log(&self) -> &DriverLog {
+        &self.log
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn parse_screen_resolution() {
+        let resolution: ScreenResolution = "1366x768".parse().unwrap();
+
+        assert_eq!(resolution, ScreenResolution(1366, 768));
+        assert_eq!(resolution.to_string(), "1366x768");
+        assert!("1366".parse::<ScreenResolution>().is_err());
+        assert!("wide x tall".parse::<ScreenResolution>().is_err());
+    }
+
+    #[test]
+    fn inject_download_directory() {
+        let directory = Path::new("/tmp/downloads");
+
+        let capabilities = r#"{"alwaysMatch":{"browserName":"chrome"}}"#;
+        let injected = with_download_directory(capabilities, WebDriverVariant::Chrome, directory);
+        let injected: Value = serde_json::from_str(&injected.unwrap()).unwrap();
+        assert_eq!(
+            injected["alwaysMatch"]["goog:chromeOptions"]["prefs"]["download.default_directory"],
+            "/tmp/downloads"
+        );
+
+        let capabilities = r#"{"firstMatch":[{"moz:firefoxOptions":{"prefs":{"a":1}}},{}]}"#;
+        let injected = with_download_directory(capabilities, WebDriverVariant::Firefox, directory);
+        let injected: Value = serde_json::from_str(&injected.unwrap()).unwrap();
+        assert!(injected.get("alwaysMatch").is_none());
+        for set in injected["firstMatch"].as_array().unwrap() {
+            assert_eq!(
+                set["moz:firefoxOptions"]["prefs"]["browser.download.dir"],
+                "/tmp/downloads"
+            );
+        }
+        assert_eq!(
+            injected["firstMatch"][0]["moz:firefoxOptions"]["prefs"]["a"],
+            1
+        );
+    }
+
+    #[test]
+    fn rotate_driver_log_file() {
+        let mut file = RotatingFile::new(8).unwrap();
+
+        for line in ["first", "second", "third", "fourth"] {
+            file.write_line(line).unwrap();
+        }
+
+        assert_eq!(file.contents().unwrap(), b"first\nsecond\nthird\nfourth\n");
+
+        file.write_line("fifth").unwrap();
+        assert_eq!(file.contents().unwrap(), b"third\nfourth\nfifth\n");
+    }
+
+    #[test]
+    fn retain_most_recent_driver_output() {
+        let log = DriverLog::default();
+
+        for i in 0..DRIVER_LOG_TAIL_LINES + 2 {
+            log.push(i.to_string());
+        }
+
+        let tail = log.tail();
+        assert!(tail.starts_with("2\n"));
+        assert!(tail.ends_with(&(DRIVER_LOG_TAIL_LINES + 1).to_string()));
     }
 }
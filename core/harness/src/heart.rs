@@ -24,6 +24,8 @@ use tracing::{debug, error, instrument, trace, warn};
 pub enum HeartInteraction {
     /// Kill it for the given reason
     Kill(String),
+    /// Stop it with a specific cause of death
+    Stop(DeathReason),
     /// Reset its lifetime to the original value
     Rejuvenate,
 }
@@ -84,6 +86,7 @@ impl Heart {
                         trace!(?interaction, "Received interaction with heart");
                         match interaction {
                             HeartInteraction::Kill(reason) => return DeathReason::Killed(reason),
+                            HeartInteraction::Stop(reason) => return reason,
                             HeartInteraction::Rejuvenate => {
                                 *self.lifetime_start.lock().await = Instant::now();
                             }
@@ -158,6 +161,13 @@ impl HeartStone {
         self.send(HeartInteraction::Kill(reason)).await;
     }
 
+    /// Stop the associated heart with a specific cause of death
+    #[instrument(skip(self))]
+    pub async fn stop(&mut self, reason: DeathReason) {
+        debug!(?reason, "Stopping heart");
+        self.send(HeartInteraction::Stop(reason)).await;
+    }
+
     /// Reset the lifetime of the associated heart
     #[instrument(skip(self))]
     pub async fn reset_lifetime(&mut self) {
@@ -223,6 +233,15 @@ mod does {
         assert!(poll!(handle).is_ready());
     }
 
+    #[tokio::test]
+    async fn die_for_given_reason() {
+        let (mut heart, mut stone) = Heart::new();
+
+        stone.stop(DeathReason::LifetimeExceeded).await;
+
+        assert!(matches!(heart.death().await, DeathReason::LifetimeExceeded));
+    }
+
     #[tokio::test]
     async fn die_after_lifetime() {
         let lifetime = Duration::from_millis(10);
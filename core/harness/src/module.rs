@@ -10,6 +10,9 @@ use std::time::Duration;
 use tokio::time::timeout;
 use tracing::{debug, error, info, instrument};
 
+/// Time granted to [`Module::post_shutdown`] before it is aborted
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// Executable module
 #[async_trait]
 pub trait Module {
@@ -29,7 +32,8 @@ pub trait Module {
     /// Opportunity for modules to do something before all jobs will be terminated
     async fn pre_shutdown(&mut self, _scheduler: &JobScheduler) {}
 
-    /// Shutdown hook executed after the core loop and all associated jobs have terminated
+    /// Shutdown hook executed after the core loop and all associated jobs have terminated,
+    /// aborted once it exceeds the [`SHUTDOWN_TIMEOUT`]
     #[instrument(skip(self))]
     async fn post_shutdown(&mut self, termination_reason: ModuleTerminationReason) {
         match termination_reason {
@@ -62,7 +66,7 @@ impl Default for ModuleRunner {
     fn default() -> Self {
         Self {
             startup_timeout: Duration::from_secs(60),
-            shutdown_timeout: Duration::from_secs(60),
+            shutdown_timeout: SHUTDOWN_TIMEOUT,
             status_server_port: None,
         }
     }
@@ -0,0 +1,48 @@
+use super::factory::BoxedResourceHandleProvider;
+use super::resource::RedisResource;
+use library::BoxedError;
+use std::convert::TryInto;
+use std::time::Duration;
+use tracing::debug;
+
+/// Self-expiring redis key which can only be held by one instance at a time
+pub struct RedisLease {
+    url: String,
+    handle_provider: BoxedResourceHandleProvider,
+}
+
+impl RedisLease {
+    /// Creates a new instance which connects to the given URL
+    pub fn new(url: String, handle_provider: BoxedResourceHandleProvider) -> Self {
+        Self {
+            url,
+            handle_provider,
+        }
+    }
+
+    /// Attempts to acquire the lease, returns whether it has been granted
+    pub async fn acquire(
+        &self,
+        key: &str,
+        holder: &str,
+        duration: Duration,
+    ) -> Result<bool, BoxedError> {
+        let handle = self.handle_provider.create_handle();
+        let mut con = RedisResource::new(handle, &self.url).await?;
+        let millis: u64 = duration.as_millis().try_into().unwrap_or(u64::MAX);
+
+        let reply: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(holder)
+            .arg("NX")
+            .arg("PX")
+            .arg(millis)
+            .query_async(&mut con)
+            .await?;
+
+        let granted = reply.is_some();
+        debug!(?key, ?holder, granted, "Attempted to acquire lease");
+
+        Ok(granted)
+    }
+}
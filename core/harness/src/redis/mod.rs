@@ -1,8 +1,10 @@
 mod discovery;
 mod factory;
 mod handle;
+mod lease;
 mod pubsub;
 mod resource;
 
 pub use discovery::{RedisServiceAdvertisementJob, RedisServiceDiscoveryJob};
 pub use factory::{DummyResourceHandleProvider, RedisCommunicationFactory};
+pub use lease::RedisLease;
@@ -63,6 +63,19 @@ pub trait ConsumerExt {
     ) -> EmptyResult
     where
         Q: QueueProvider + Send + Sync;
+
+    /// Same as [`consume_queue`](ConsumerExt::consume_queue) but returns once no
+    /// new notification has been received within the given `idle_timeout`.
+    async fn consume_queue_until_idle<Q>(
+        &self,
+        provider: Q,
+        group: &ConsumerGroupDescriptor,
+        consumer: &str, // &ConsumerIdentifier
+        extension: &Option<QueueDescriptorExtension>,
+        idle_timeout: Option<Duration>,
+    ) -> EmptyResult
+    where
+        Q: QueueProvider + Send + Sync;
 }
 
 #[async_trait]
@@ -102,7 +115,6 @@ where
         Ok(())
     }
 
-    #[instrument(err, skip(self, provider, group), fields(group = ?group.identifier(), key = ?C::Notification::queue().key()))]
     async fn consume_queue<Q>(
         &self,
         provider: Q,
@@ -110,6 +122,22 @@ where
         consumer: &str, // &ConsumerIdentifier
         extension: &Option<QueueDescriptorExtension>,
     ) -> EmptyResult
+    where
+        Q: QueueProvider + Send + Sync,
+    {
+        self.consume_queue_until_idle(provider, group, consumer, extension, DEFAULT_IDLE_TIMEOUT)
+            .await
+    }
+
+    #[instrument(err, skip(self, provider, group), fields(group = ?group.identifier(), key = ?C::Notification::queue().key()))]
+    async fn consume_queue_until_idle<Q>(
+        &self,
+        provider: Q,
+        group: &ConsumerGroupDescriptor,
+        consumer: &str, // &ConsumerIdentifier
+        extension: &Option<QueueDescriptorExtension>,
+        idle_timeout: Option<Duration>,
+    ) -> EmptyResult
     where
         Q: QueueProvider + Send + Sync,
     {
@@ -119,7 +147,7 @@ where
                 group,
                 consumer,
                 DEFAULT_BATCH_SIZE,
-                DEFAULT_IDLE_TIMEOUT,
+                idle_timeout,
                 extension,
             )
             .await?;
@@ -139,3 +167,129 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use crate::communication::event::QueueDescriptor;
+    use crate::communication::implementation::json::JsonQueueEntry;
+    use futures::stream::{self, BoxStream};
+    use serde::{Deserialize, Serialize};
+    use std::sync::Mutex;
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct MockNotification(usize);
+
+    impl Notification for MockNotification {
+        fn queue() -> QueueDescriptor {
+            QueueDescriptor::new("mock".into(), 42)
+        }
+    }
+
+    struct FiniteQueueEntry {
+        payload: Vec<u8>,
+        acknowledged: Arc<AtomicU64>,
+    }
+
+    #[async_trait]
+    impl RawQueueEntry for FiniteQueueEntry {
+        fn payload(&self) -> &[u8] {
+            &self.payload
+        }
+
+        async fn acknowledge(&mut self) -> EmptyResult {
+            self.acknowledged.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    impl JsonQueueEntry for FiniteQueueEntry {}
+
+    /// Queue which ends as soon as all entries have been delivered, like an idle redis stream
+    #[derive(Clone, Default)]
+    struct FiniteQueueProvider {
+        notifications: Vec<usize>,
+        idle_timeout: Arc<Mutex<Option<Duration>>>,
+        acknowledged: Arc<AtomicU64>,
+    }
+
+    #[async_trait]
+    impl QueueProvider for FiniteQueueProvider {
+        type Entry = FiniteQueueEntry;
+
+        async fn consume(
+            &self,
+            _queue: QueueDescriptor,
+            _group: &ConsumerGroupDescriptor,
+            _consumer: &str,
+            _batch_size: usize,
+            idle_timeout: Option<Duration>,
+            _extension: &Option<QueueDescriptorExtension>,
+        ) -> Result<BoxStream<Result<Self::Entry, BoxedError>>, BoxedError> {
+            *self.idle_timeout.lock().unwrap() = idle_timeout;
+
+            let entries = self
+                .notifications
+                .iter()
+                .map(|value| {
+                    let frame = NotificationFrame::new(MockNotification(*value));
+                    Ok(FiniteQueueEntry {
+                        payload: serde_json::to_vec(&frame).unwrap(),
+                        acknowledged: self.acknowledged.clone(),
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            Ok(stream::iter(entries).boxed())
+        }
+    }
+
+    #[derive(Default)]
+    struct CollectingConsumer {
+        received: Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl Consumer for CollectingConsumer {
+        type Notification = MockNotification;
+
+        async fn consume(
+            &self,
+            notification: NotificationFrame<Self::Notification>,
+        ) -> EmptyResult {
+            self.received
+                .lock()
+                .unwrap()
+                .push(notification.into_inner().0);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn return_once_queue_is_idle() {
+        let provider = FiniteQueueProvider {
+            notifications: vec![1, 2, 3],
+            ..Default::default()
+        };
+        let consumer = CollectingConsumer::default();
+        let idle_timeout = Some(Duration::from_secs(1));
+
+        consumer
+            .consume_queue_until_idle(
+                provider.clone(),
+                &ConsumerGroupDescriptor::default(),
+                "consumer",
+                &None,
+                idle_timeout,
+            )
+            .await
+            .unwrap();
+
+        let mut received = consumer.received.lock().unwrap().clone();
+        received.sort_unstable();
+
+        assert_eq!(received, vec![1, 2, 3]);
+        assert_eq!(provider.acknowledged.load(Ordering::SeqCst), 3);
+        assert_eq!(*provider.idle_timeout.lock().unwrap(), idle_timeout);
+    }
+}
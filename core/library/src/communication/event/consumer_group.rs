@@ -9,6 +9,8 @@ pub enum ConsumerGroupIdentifier {
     Gangway(String),
     /// Collector instance
     Collector,
+    /// Manager instance
+    Manager(String),
     /// Unknown consumer group
     Other(String),
 }
@@ -19,6 +21,7 @@ impl ToString for ConsumerGroupIdentifier {
             Self::Worker => "worker".into(),
             Self::Gangway(id) => format!("gangway-{}", id),
             Self::Collector => "collector".into(),
+            Self::Manager(id) => format!("manager-{}", id),
             Self::Other(identifier) => identifier.to_owned(),
         }
     }
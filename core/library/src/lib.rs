@@ -16,7 +16,9 @@ pub mod http;
 pub mod storage;
 
 mod perfmon;
-pub use perfmon::{AccumulatedPerformanceMetrics, PerformanceMonitor, PerformanceMonitoringTarget};
+pub use perfmon::{
+    AccumulatedPerformanceMetrics, HostResources, PerformanceMonitor, PerformanceMonitoringTarget,
+};
 
 /// Generic error type
 pub type BoxedError = Box<dyn std::error::Error + Send + Sync + 'static>;
@@ -88,6 +88,17 @@ struct PerformanceMetrics {
     disk_write: u64,
 }
 
+/// Snapshot of the resources provided by the host system
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostResources {
+    /// Total physical memory in bytes
+    pub memory_total: u64,
+    /// Memory in bytes which can be allocated without swapping
+    pub memory_available: u64,
+    /// Number of logical CPUs
+    pub cpu_count: u64,
+}
+
 /// Tool to collect [`AccumulatedPerformanceMetrics`] for a given process
 pub struct PerformanceMonitor;
 
@@ -149,6 +160,18 @@ impl PerformanceMonitor {
         })
     }
 
+    /// Samples the memory and CPUs of the host system
+    pub async fn sample_host() -> Result<HostResources, BoxedError> {
+        let memory = heim::memory::memory().await?;
+        let cpu_count = heim::cpu::logical_count().await?;
+
+        Ok(HostResources {
+            memory_total: memory.total().get::<byte>(),
+            memory_available: memory.available().get::<byte>(),
+            cpu_count,
+        })
+    }
+
     async fn sample(process: &Process) -> Result<PerformanceMetrics, BoxedError> {
         let memory = process.memory().await?;
         let cpu_time = process.cpu_time().await?;
@@ -43,6 +43,14 @@ pub trait StorageBackend: Clone {
 
     /// Creates a new object at the given path
     async fn put_object(&self, path: &str, content: &[u8]) -> Result<(), BoxedError>;
+
+    /// Creates a new object at the given path which is served with the given MIME type
+    async fn put_object_with_content_type(
+        &self,
+        path: &str,
+        content: &[u8],
+        content_type: &str,
+    ) -> Result<(), BoxedError>;
 }
 
 /// Parses a storage backend URI and instantiates the matching backend
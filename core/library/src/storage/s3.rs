@@ -161,6 +161,20 @@ impl StorageBackend for S3StorageBackend {
         let response = self.bucket.put_object(path, content).await?;
         Ok(self.handle_response(response).map(|_| ())?)
     }
+
+    #[instrument(skip(self, content), fields(bytes = content.len()))]
+    async fn put_object_with_content_type(
+        &self,
+        path: &str,
+        content: &[u8],
+        content_type: &str,
+    ) -> Result<(), Box<dyn StdError + Send + Sync>> {
+        let response = self
+            .bucket
+            .put_object_with_content_type(path, content, content_type)
+            .await?;
+        Ok(self.handle_response(response).map(|_| ())?)
+    }
 }
 
 /// Tests for the S3 storage backend.
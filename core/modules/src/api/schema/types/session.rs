@@ -51,11 +51,36 @@ pub struct Metadata {
     provisioner: Vec<MetadataEntry>,
 }
 
+/// Interval during which the recording has been paused by the client
+#[derive(GraphQLObject)]
+pub struct VideoPause {
+    /// Time at which the recording has been paused
+    start: DateTime<Utc>,
+    /// Time at which the recording has been resumed, empty if it remained paused until the session terminated
+    end: Option<DateTime<Utc>>,
+}
+
 /// Details of the video recording
 #[derive(GraphQLObject)]
 pub struct Video {
     /// HLS m3u8 playlist location
     playlist: String,
+    /// Location of a single MP4 file containing the whole recording, suitable for downloading
+    download: String,
+    /// Location of a representative still image of the recording
+    thumbnail: String,
+    /// Location of a WebVTT index mapping time ranges to frames in a sprite sheet, e.g. for scrubbing previews
+    preview_sprites: String,
+    /// Intervals which have not been recorded on request of the client
+    pauses: Vec<VideoPause>,
+    /// Codec profile used to encode the recording, e.g. `h264`
+    profile: Option<String>,
+    /// Kind of recording, either `video` or `timeLapse`
+    format: Option<String>,
+    /// Location of the JSON index listing the screenshots of a time-lapse recording
+    time_lapse: String,
+    /// Names of recording objects which could not be uploaded, e.g. due to an outage of the storage
+    lost_segments: Vec<String>,
     /// Total number of bytes excluding metadata
     size: i32,
 }
@@ -108,10 +133,61 @@ impl Session {
     fn video(&self) -> Video {
         Video {
             playlist: format!("/storage/{}/screen.m3u8", &self.metadata.id),
+            download: format!("/storage/{}/screen.mp4", &self.metadata.id),
+            thumbnail: format!("/storage/{}/thumbnail.jpg", &self.metadata.id),
+            preview_sprites: format!("/storage/{}/sprites.vtt", &self.metadata.id),
+            pauses: self
+                .metadata
+                .recording_pauses
+                .iter()
+                .map(|pause| VideoPause {
+                    start: pause.start,
+                    end: pause.end,
+                })
+                .collect(),
+            profile: self
+                .metadata
+                .recording_profile
+                .map(|profile| profile.to_string()),
+            format: self
+                .metadata
+                .recording_format
+                .map(|format| format.to_string()),
+            time_lapse: format!("/storage/{}/timelapse.json", &self.metadata.id),
+            lost_segments: self.metadata.lost_segments.clone(),
             size: self.metadata.recording_bytes.unwrap_or_default() as i32,
         }
     }
 
+    /// Location of the WebDriver commands executed by the client, stored as one JSON object per line.
+    /// Empty if no storage backend is configured or the journal has not been uploaded.
+    fn command_journal(&self) -> Option<String> {
+        if self.metadata.command_journal {
+            Some(format!("/storage/{}/commands.jsonl", &self.metadata.id))
+        } else {
+            None
+        }
+    }
+
+    /// Location of the output written by the webdriver (e.g. chromedriver or geckodriver).
+    /// Empty if no storage backend is configured or the log has not been uploaded.
+    fn driver_log(&self) -> Option<String> {
+        if self.metadata.driver_log {
+            Some(format!("/storage/{}/driver.log", &self.metadata.id))
+        } else {
+            None
+        }
+    }
+
+    /// Locations of screenshots taken after failed commands and right before the session terminated
+    fn screenshots(&self) -> Vec<String> {
+        self.metadata
+            .screenshots
+            .iter()
+            .map(|name| format!("/storage/{}/{}", &self.metadata.id, name))
+            .collect()
+    }
+
     /// Can be empty when the session has not yet been provisioned.
     fn provisioner(&self) -> Option<Provisioner> {
         self.metadata
@@ -50,6 +50,13 @@ impl Consumer for TerminationWatcherService {
         metadata.termination = Some(notification.reason);
         metadata.recording_bytes = Some(notification.recording_bytes as i64);
         metadata.profiling_data = notification.profiling_data;
+        metadata.screenshots = notification.screenshots;
+        metadata.recording_pauses = notification.recording_pauses;
+        metadata.recording_profile = notification.recording_profile;
+        metadata.recording_format = notification.recording_format;
+        metadata.lost_segments = notification.lost_segments;
+        metadata.command_journal = notification.command_journal;
+        metadata.driver_log = notification.driver_log;
 
         self.collection.insert_one(metadata, None).await?;
         self.staging_collection.delete_one(query, None).await?;
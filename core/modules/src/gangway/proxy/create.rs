@@ -64,7 +64,7 @@ impl SessionCreationResponder {
         let error = SessionCreationError::SessionCreationFailed(id, error);
         let blackbox = BlackboxError::new(error);
 
-        super::error::new_error_response(WebdriverErrorCode::SessionNotCreated, blackbox)
+        domain::webdriver::new_error_response(WebdriverErrorCode::SessionNotCreated, blackbox)
     }
 
     #[inline]
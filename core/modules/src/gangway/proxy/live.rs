@@ -0,0 +1,187 @@
+use async_trait::async_trait;
+use domain::event::SessionIdentifier;
+use domain::WebgridServiceDescriptor;
+use futures::{Future, TryStreamExt};
+use hyper::client::HttpConnector;
+use hyper::header::UPGRADE;
+use hyper::http::{request::Parts, Method, Request, Response, StatusCode};
+use hyper::{upgrade, Body, Client};
+use library::communication::discovery::{DiscoveredServiceEndpoint, ServiceDiscoverer};
+use library::http::{uri_with_authority, MatchableString, Responder};
+use library::BoxedError;
+use std::convert::Infallible;
+use std::net::IpAddr;
+use thiserror::Error;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use super::session::SESSION_ID_LENGTH;
+
+const SESSION_PREFIX: &str = "/session/";
+const LIVE_VIEW_SUFFIX: &str = "/webgrid/live";
+
+#[derive(Debug, Error)]
+enum LiveViewResponderError {
+    #[error("endpoint discovery failed")]
+    EndpointDiscoveryFailure(#[source] BoxedError),
+    #[error("live view is not available for this session")]
+    NoEndpoint,
+    #[error("unable to construct destination URI")]
+    URIConstructionFailed(#[source] hyper::http::Error),
+    #[error("unable to forward request")]
+    UnableToForward(#[source] hyper::Error),
+}
+
+use LiveViewResponderError::*;
+
+/// Passes WebSocket connections to `/session/<id>/webgrid/live` through to the live view of the node
+///
+/// Regular requests to the node are forced to HTTP/2 which does not support upgrading connections,
+/// thus the live view is served separately and reached over HTTP/1.
+pub struct LiveViewResponder<D: ServiceDiscoverer<WebgridServiceDescriptor>> {
+    client: Client<HttpConnector>,
+    discoverer: D,
+}
+
+impl<D> LiveViewResponder<D>
+where
+    D: ServiceDiscoverer<WebgridServiceDescriptor> + Send + Sync,
+    D::I: Send + Sync,
+{
+    pub fn new(discoverer: D) -> Self {
+        Self {
+            client: Client::new(),
+            discoverer,
+        }
+    }
+
+    #[inline]
+    fn new_error_response(&self, error: LiveViewResponderError) -> Response<Body> {
+        let status = match error {
+            NoEndpoint => StatusCode::NOT_FOUND,
+            _ => StatusCode::BAD_GATEWAY,
+        };
+
+        Response::builder()
+            .status(status)
+            .body(Body::from(error.to_string()))
+            .unwrap()
+    }
+
+    #[inline]
+    fn match_request(&self, parts: &Parts) -> Option<SessionIdentifier> {
+        if parts.method != Method::GET || !parts.headers.contains_key(UPGRADE) {
+            return None;
+        }
+
+        let mut matchable = MatchableString::new(parts.uri.path());
+
+        matchable.consume_prefix(SESSION_PREFIX)?;
+        let identifier = matchable.consume_count(SESSION_ID_LENGTH)?;
+
+        if !matchable.current().eq_ignore_ascii_case(LIVE_VIEW_SUFFIX) {
+            return None;
+        }
+
+        Uuid::parse_str(identifier).ok()
+    }
+
+    async fn discover_endpoint(
+        &self,
+        identifier: SessionIdentifier,
+    ) -> Result<D::I, LiveViewResponderError> {
+        let descriptor = WebgridServiceDescriptor::LiveView(identifier);
+        let endpoint = self
+            .discoverer
+            .discover(descriptor)
+            .try_next()
+            .await
+            .map_err(EndpointDiscoveryFailure)?;
+        endpoint.ok_or(NoEndpoint)
+    }
+
+    async fn forward(
+        &self,
+        mut req: Request<Body>,
+        endpoint: &str,
+    ) -> Result<Response<Body>, LiveViewResponderError> {
+        let uri = uri_with_authority(&req, endpoint).map_err(URIConstructionFailed)?;
+
+        // Hop headers are passed on as they are required to upgrade the connection to the node
+        let mut forwarded = Request::builder()
+            .method(req.method().clone())
+            .uri(uri)
+            .body(Body::empty())
+            .map_err(URIConstructionFailed)?;
+        *forwarded.headers_mut() = req.headers().clone();
+
+        let mut response = self
+            .client
+            .request(forwarded)
+            .await
+            .map_err(UnableToForward)?;
+
+        if response.status() == StatusCode::SWITCHING_PROTOCOLS {
+            let client_upgrade = upgrade::on(&mut req);
+            let node_upgrade = upgrade::on(&mut response);
+
+            tokio::spawn(async move {
+                match futures::try_join!(client_upgrade, node_upgrade) {
+                    Ok((mut client, mut node)) => {
+                        debug!("Live view connection established");
+                        tokio::io::copy_bidirectional(&mut client, &mut node)
+                            .await
+                            .ok();
+                    }
+                    Err(error) => warn!(?error, "Failed to upgrade live view connection"),
+                }
+            });
+        }
+
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl<D> Responder for LiveViewResponder<D>
+where
+    D: ServiceDiscoverer<WebgridServiceDescriptor> + Send + Sync,
+    D::I: Send + Sync,
+{
+    #[inline]
+    async fn respond<F, Fut>(
+        &self,
+        parts: Parts,
+        body: Body,
+        client_ip: IpAddr,
+        next: F,
+    ) -> Result<Response<Body>, Infallible>
+    where
+        Fut: Future<Output = Result<Response<Body>, Infallible>> + Send,
+        F: FnOnce(Parts, Body, IpAddr) -> Fut + Send,
+    {
+        // Match the incoming request
+        let identifier = match self.match_request(&parts) {
+            Some(identifier) => identifier,
+            None => return next(parts, body, client_ip).await,
+        };
+
+        // Search for an endpoint
+        let endpoint = match self.discover_endpoint(identifier).await {
+            Ok(endpoint) => endpoint,
+            Err(e) => return Ok(self.new_error_response(e)),
+        };
+
+        // Forward the request and tunnel the upgraded connection
+        let req = Request::from_parts(parts, body);
+        let response = match self.forward(req, &endpoint).await {
+            Ok(response) => response,
+            Err(e) => {
+                endpoint.flag_unreachable().await;
+                self.new_error_response(e)
+            }
+        };
+
+        Ok(response)
+    }
+}
@@ -1,4 +1,5 @@
 use crate::gangway::proxy::api::ApiForwardingResponder;
+use crate::gangway::proxy::live::LiveViewResponder;
 use crate::gangway::proxy::storage::StorageResponder;
 use async_trait::async_trait;
 use domain::WebgridServiceDescriptor;
@@ -15,7 +16,7 @@ use super::SessionCreationCommunicationHandle;
 
 mod api;
 mod create;
-mod error;
+mod live;
 mod session;
 mod storage;
 
@@ -61,6 +62,7 @@ where
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
         let discoverer = self.discoverer.clone();
 
+        let live_view_responder = LiveViewResponder::new(discoverer.clone());
         let session_responder =
             SessionForwardingResponder::new(self.identifier.clone(), discoverer.clone());
         let creation_responder = SessionCreationResponder::new(self.handle.clone());
@@ -68,6 +70,7 @@ where
         let api_responder = ApiForwardingResponder::new(self.identifier.clone(), discoverer);
 
         let make_svc = make_responder_chain_service_fn! {
+            live_view_responder,
             session_responder,
             creation_responder,
             storage_responder,
@@ -68,7 +68,7 @@ where
         let error = SessionForwardingError::ForwardFailed(id, error);
         let blackbox = BlackboxError::new(error);
 
-        super::error::new_error_response(WebdriverErrorCode::UnknownError, blackbox)
+        domain::webdriver::new_error_response(WebdriverErrorCode::UnknownError, blackbox)
     }
 
     #[inline]
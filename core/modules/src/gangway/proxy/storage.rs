@@ -1,6 +1,7 @@
 use async_trait::async_trait;
+use domain::recording::{content_type, export_recording, RecordingExport, EXPORT_FILENAME};
 use domain::{event::SessionIdentifier, storage_path};
-use futures::Future;
+use futures::{stream, Future, StreamExt};
 use hyper::http::Method;
 use hyper::http::{request::Parts, Response, StatusCode};
 use hyper::Body;
@@ -21,7 +22,7 @@ pub struct StorageResponder<S: StorageBackend> {
 
 impl<S> StorageResponder<S>
 where
-    S: StorageBackend,
+    S: StorageBackend + Send + Sync + 'static,
 {
     pub fn new(storage: Option<S>) -> Self {
         Self { storage }
@@ -38,6 +39,45 @@ where
             .unwrap()
     }
 
+    async fn respond_with_export(&self, session_id: SessionIdentifier) -> Response<Body> {
+        let storage = match &self.storage {
+            Some(storage) => storage,
+            None => {
+                return self.new_error_response(
+                    "no storage backend configured",
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                )
+            }
+        };
+
+        let body = match export_recording(storage, session_id).await {
+            Ok(RecordingExport::Cached(video)) => Body::from(video),
+            Ok(RecordingExport::Segments(paths)) => {
+                // Segments are only fetched once the client is ready to receive them
+                let storage = storage.clone();
+                let segments = stream::iter(paths).then(move |path| {
+                    let storage = storage.clone();
+                    async move { storage.get_object(&path).await }
+                });
+
+                Body::wrap_stream(segments)
+            }
+            Err(e) => return self.new_error_response(&e.to_string(), StatusCode::NOT_FOUND),
+        };
+
+        Response::builder()
+            .header("Content-Type", "video/mp4")
+            .header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}.mp4\"", session_id),
+            )
+            .header("Access-Control-Allow-Origin", "*")
+            .header("Access-Control-Allow-Methods", "GET")
+            .status(StatusCode::OK)
+            .body(body)
+            .unwrap()
+    }
+
     #[inline]
     fn match_request<'a>(&self, parts: &'a Parts) -> Option<(SessionIdentifier, &'a str)> {
         let mut matchable = MatchableString::new(parts.uri.path());
@@ -61,7 +101,7 @@ where
 #[async_trait]
 impl<S> Responder for StorageResponder<S>
 where
-    S: StorageBackend + Send + Sync,
+    S: StorageBackend + Send + Sync + 'static,
 {
     #[inline]
     async fn respond<F, Fut>(
@@ -89,15 +129,20 @@ where
                 .unwrap());
         }
 
+        if filename.trim_start_matches('/') == EXPORT_FILENAME {
+            return Ok(self.respond_with_export(session_id).await);
+        }
+
         let path = storage_path(session_id, filename);
-        let mime = MimeGuess::from_path(&path)
+        let guessed_mime = MimeGuess::from_path(&path)
             .first()
             .unwrap_or(mime_guess::mime::APPLICATION_OCTET_STREAM);
+        let mime = content_type(filename).unwrap_or_else(|| guessed_mime.essence_str());
 
         if let Some(storage) = &self.storage {
             match storage.get_object(&path.to_string_lossy()).await {
                 Ok(object) => Ok(Response::builder()
-                    .header("Content-Type", mime.essence_str())
+                    .header("Content-Type", mime)
                     .header("Access-Control-Allow-Origin", "*")
                     .header("Access-Control-Allow-Methods", "GET")
                     .status(StatusCode::OK)
@@ -0,0 +1,149 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use domain::event::{ProvisionerHeartbeatNotification, ProvisionerIdentifier};
+use harness::Service;
+use library::communication::event::{Consumer, NotificationFrame};
+use library::communication::CommunicationFactory;
+use library::EmptyResult;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::trace;
+
+/// Keeps track of when each known provisioner has last been seen alive
+#[derive(Clone, Default)]
+pub struct ProvisionerLiveness {
+    last_seen: Arc<Mutex<HashMap<ProvisionerIdentifier, DateTime<Utc>>>>,
+}
+
+impl ProvisionerLiveness {
+    /// Records a sign of life from the given provisioner at the given point in time
+    pub async fn beat(&self, provisioner: ProvisionerIdentifier, time: DateTime<Utc>) {
+        let mut last_seen = self.last_seen.lock().await;
+        let entry = last_seen.entry(provisioner).or_insert(time);
+
+        if *entry < time {
+            *entry = time;
+        }
+    }
+
+    /// Removes and returns all provisioners that have not been seen within `timeout` before `now`
+    pub async fn take_dead(
+        &self,
+        now: DateTime<Utc>,
+        timeout: Duration,
+    ) -> Vec<ProvisionerIdentifier> {
+        let mut last_seen = self.last_seen.lock().await;
+        let dead = last_seen
+            .iter()
+            .filter(|(_, seen)| now - **seen > timeout)
+            .map(|(provisioner, _)| provisioner.to_owned())
+            .collect::<Vec<_>>();
+
+        for provisioner in dead.iter() {
+            last_seen.remove(provisioner);
+        }
+
+        dead
+    }
+}
+
+/// Records provisioner heartbeats in a [`ProvisionerLiveness`] instance
+///
+/// Consumes:
+/// - [`ProvisionerHeartbeatNotification`]
+pub struct HeartbeatWatcherService {
+    liveness: ProvisionerLiveness,
+}
+
+impl<F> Service<F> for HeartbeatWatcherService
+where
+    F: CommunicationFactory + Send + Sync,
+{
+    const NAME: &'static str = "HeartbeatWatcherService";
+    type Instance = HeartbeatWatcherService;
+    type Config = ProvisionerLiveness;
+
+    fn instantiate(_factory: F, config: &Self::Config) -> Self::Instance {
+        Self {
+            liveness: config.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl Consumer for HeartbeatWatcherService {
+    type Notification = ProvisionerHeartbeatNotification;
+
+    async fn consume(&self, notification: NotificationFrame<Self::Notification>) -> EmptyResult {
+        trace!(provisioner = ?notification.provisioner, "Received heartbeat");
+
+        let time = *notification.publication_time();
+        self.liveness
+            .beat(notification.into_inner().provisioner, time)
+            .await;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[tokio::test]
+    async fn report_silent_provisioners_once() {
+        let liveness = ProvisionerLiveness::default();
+        let now = Utc::now();
+        let timeout = Duration::seconds(60);
+
+        liveness.beat("alive".into(), now).await;
+        liveness
+            .beat("dead".into(), now - Duration::seconds(120))
+            .await;
+
+        assert_eq!(
+            liveness.take_dead(now, timeout).await,
+            vec!["dead".to_owned()]
+        );
+        assert!(liveness.take_dead(now, timeout).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn keep_most_recent_heartbeat() {
+        let liveness = ProvisionerLiveness::default();
+        let now = Utc::now();
+        let timeout = Duration::seconds(60);
+
+        liveness.beat("some-id".into(), now).await;
+        liveness
+            .beat("some-id".into(), now - Duration::seconds(120))
+            .await;
+
+        assert!(liveness.take_dead(now, timeout).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn consider_provisioners_alive_until_timeout_elapsed() {
+        let liveness = ProvisionerLiveness::default();
+        let seen = Utc::now();
+        let timeout = Duration::seconds(60);
+
+        liveness.beat("some-id".into(), seen).await;
+
+        assert!(liveness.take_dead(seen + timeout, timeout).await.is_empty());
+        assert_eq!(
+            liveness
+                .take_dead(seen + timeout + Duration::seconds(1), timeout)
+                .await,
+            vec!["some-id".to_owned()]
+        );
+
+        // Provisioners which come back to life are tracked again
+        liveness.beat("some-id".into(), seen + timeout * 2).await;
+        assert_eq!(
+            liveness.take_dead(seen + timeout * 4, timeout).await,
+            vec!["some-id".to_owned()]
+        );
+    }
+}
@@ -1,15 +1,23 @@
 //! Schedules newly created sessions, assigning them to provisioners
 
+mod liveness;
 mod options;
+mod reaper;
+mod rescheduling;
 mod scheduling;
 
 use std::collections::HashSet;
 
 use async_trait::async_trait;
 use harness::{Heart, Module, ServiceRunner};
-use jatsl::JobScheduler;
-use library::communication::event::ConsumerGroupDescriptor;
+use jatsl::{schedule, JobScheduler};
+use library::communication::event::{
+    ConsumerGroupDescriptor, ConsumerGroupIdentifier, QueueLocation,
+};
 use library::BoxedError;
+use liveness::{HeartbeatWatcherService, ProvisionerLiveness};
+use reaper::ProvisionerReaperJob;
+use rescheduling::ReschedulingService;
 use scheduling::SchedulingService;
 
 pub use options::Options;
@@ -35,11 +43,49 @@ impl Module for Manager {
         let group = ConsumerGroupDescriptor::default();
         let consumer = self.options.queueing.id.to_string();
 
-        let runner =
-            ServiceRunner::<SchedulingService<_>>::new(redis_url, group, consumer, HashSet::new());
+        let scheduling_service = ServiceRunner::<SchedulingService<_>>::new(
+            redis_url.clone(),
+            group.clone(),
+            consumer.clone(),
+            HashSet::new(),
+        );
 
-        debug!("Scheduling service");
-        scheduler.spawn_job(runner).await;
+        let rescheduling_service = ServiceRunner::<ReschedulingService<_>>::new(
+            redis_url.clone(),
+            group,
+            consumer.clone(),
+            (),
+        );
+
+        // Every manager needs to see all heartbeats, thus they each get their own group.
+        // Only heartbeats sent after startup are of interest, replaying the history is pointless.
+        let liveness = ProvisionerLiveness::default();
+        let heartbeat_group = ConsumerGroupDescriptor::new(
+            ConsumerGroupIdentifier::Manager(consumer.clone()),
+            QueueLocation::Tail,
+        );
+        let heartbeat_service = ServiceRunner::<HeartbeatWatcherService>::new(
+            redis_url.clone(),
+            heartbeat_group,
+            consumer.clone(),
+            liveness.clone(),
+        );
+
+        let reaper_job = ProvisionerReaperJob::new(
+            redis_url,
+            consumer,
+            liveness,
+            self.options.reaper_interval,
+            self.options.provisioner_timeout,
+        );
+
+        debug!("Scheduling services");
+        schedule!(scheduler, {
+            scheduling_service,
+            rescheduling_service,
+            heartbeat_service,
+            reaper_job,
+        });
 
         Ok(Some(Heart::without_heart_stone()))
     }
@@ -1,6 +1,7 @@
 use crate::options::{QueueingOptions, RedisOptions};
-use library::helpers::parse_string_list;
+use library::helpers::{parse_seconds, parse_string_list};
 use std::collections::HashSet;
+use std::time::Duration;
 use structopt::StructOpt;
 
 /// Options for the manager module
@@ -18,4 +19,13 @@ pub struct Options {
     /// Omitting this flag or setting an empty string will allow requests without metadata.
     #[structopt(long, env, default_value = "", parse(try_from_str = parse_string_list))]
     pub required_metadata: HashSet<String>,
+
+    /// Time without a heartbeat after which a provisioner is considered dead
+    /// and the jobs queued for it are scheduled on other provisioners.
+    #[structopt(long, env, default_value = "60", parse(try_from_str = parse_seconds))]
+    pub provisioner_timeout: Duration,
+
+    /// Interval in which provisioners are checked for missing heartbeats
+    #[structopt(long, env, default_value = "15", parse(try_from_str = parse_seconds))]
+    pub reaper_interval: Duration,
 }
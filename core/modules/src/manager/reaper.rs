@@ -0,0 +1,189 @@
+use super::liveness::ProvisionerLiveness;
+use async_trait::async_trait;
+use chrono::Utc;
+use domain::event::{
+    ProvisionerIdentifier, ProvisioningJobAssignedNotification, ProvisioningJobReturnedNotification,
+};
+use harness::{RedisCommunicationFactory, RedisLease, Service};
+use jatsl::{Job, JobManager};
+use library::communication::event::{
+    Consumer, ConsumerExt, ConsumerGroupDescriptor, NotificationFrame, NotificationPublisher,
+};
+use library::communication::CommunicationFactory;
+use library::EmptyResult;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{debug, error, info, instrument};
+
+/// Time without new entries after which the queue of a dead provisioner is considered empty
+const REAPING_IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Hands jobs queued for a dead provisioner back for rescheduling
+///
+/// Consumes:
+/// - [`ProvisioningJobAssignedNotification`]
+///
+/// Publishes:
+/// - [`ProvisioningJobReturnedNotification`]
+pub struct JobReturningService<F: CommunicationFactory> {
+    provisioner: ProvisionerIdentifier,
+    publisher: <F as CommunicationFactory>::NotificationPublisher,
+}
+
+impl<F> Service<F> for JobReturningService<F>
+where
+    F: CommunicationFactory + Send + Sync,
+{
+    const NAME: &'static str = "JobReturningService";
+    type Instance = JobReturningService<F>;
+    type Config = ProvisionerIdentifier;
+
+    fn instantiate(factory: F, config: &Self::Config) -> Self::Instance {
+        Self {
+            provisioner: config.clone(),
+            publisher: factory.notification_publisher(),
+        }
+    }
+}
+
+#[async_trait]
+impl<F> Consumer for JobReturningService<F>
+where
+    F: CommunicationFactory + Send + Sync,
+{
+    type Notification = ProvisioningJobAssignedNotification;
+
+    async fn consume(&self, notification: NotificationFrame<Self::Notification>) -> EmptyResult {
+        debug!(id = ?notification.session_id, provisioner = ?self.provisioner, "Returning orphaned job");
+
+        let notification = notification.into_inner();
+        let returned = ProvisioningJobReturnedNotification {
+            session_id: notification.session_id,
+            capabilities: notification.capabilities,
+            provisioner: self.provisioner.clone(),
+        };
+
+        self.publisher.publish(&returned).await
+    }
+}
+
+/// Key of the lease which grants a manager the right to reap the given provisioner
+fn lease_key(provisioner: &str) -> String {
+    format!("reaper:{}", provisioner)
+}
+
+/// Returns the jobs of provisioners which stopped sending heartbeats using the [`JobReturningService`]
+pub struct ProvisionerReaperJob {
+    redis_url: String,
+    identifier: String,
+    liveness: ProvisionerLiveness,
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl ProvisionerReaperJob {
+    pub fn new(
+        redis_url: String,
+        identifier: String,
+        liveness: ProvisionerLiveness,
+        interval: Duration,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            redis_url,
+            identifier,
+            liveness,
+            interval,
+            timeout,
+        }
+    }
+
+    #[instrument(skip(self, manager))]
+    async fn reap(
+        &self,
+        manager: Arc<JobManager>,
+        provisioner: ProvisionerIdentifier,
+    ) -> EmptyResult {
+        // Only one manager returns the jobs, the lease outlives the window in which the others notice the death
+        let lease = RedisLease::new(self.redis_url.clone(), manager.clone());
+        let key = lease_key(&provisioner);
+        if !lease.acquire(&key, &self.identifier, self.timeout).await? {
+            debug!("Provisioner is already being reaped by another manager");
+            return Ok(());
+        }
+
+        let factory = RedisCommunicationFactory::new(self.redis_url.clone(), manager);
+        let provider = factory.queue_provider();
+        let service = JobReturningService::instantiate(factory, &provisioner);
+
+        // Take over the consumer identity of the provisioner so its pending entries are delivered to us
+        service
+            .consume_queue_until_idle(
+                provider,
+                &ConsumerGroupDescriptor::default(),
+                &provisioner,
+                &Some(provisioner.clone()),
+                Some(REAPING_IDLE_TIMEOUT),
+            )
+            .await
+    }
+}
+
+#[async_trait]
+impl Job for ProvisionerReaperJob {
+    const NAME: &'static str = module_path!();
+
+    async fn execute(&self, manager: JobManager) -> EmptyResult {
+        let manager = Arc::new(manager);
+        let timeout = chrono::Duration::from_std(self.timeout)?;
+
+        manager.ready().await;
+
+        loop {
+            sleep(self.interval).await;
+
+            for provisioner in self.liveness.take_dead(Utc::now(), timeout).await {
+                info!(?provisioner, "Provisioner stopped sending heartbeats");
+
+                if let Err(error) = self.reap(manager.clone(), provisioner).await {
+                    error!(?error, "Failed to redistribute jobs of dead provisioner");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use domain::webdriver::RawCapabilitiesRequest;
+    use library::communication::implementation::mock::MockCommunicationFactory;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn return_jobs_on_behalf_of_provisioner() {
+        let session_id = Uuid::new_v4();
+        let provisioner: ProvisionerIdentifier = "dead-id".into();
+        let capabilities = RawCapabilitiesRequest::new("{}".into());
+
+        let assigned = ProvisioningJobAssignedNotification {
+            session_id,
+            capabilities: capabilities.clone(),
+        };
+
+        let returned = ProvisioningJobReturnedNotification {
+            session_id,
+            capabilities,
+            provisioner: provisioner.clone(),
+        };
+
+        let factory = MockCommunicationFactory::default();
+        factory.expect(&returned);
+
+        JobReturningService::instantiate(factory, &provisioner)
+            .consume(NotificationFrame::new(assigned))
+            .await
+            .unwrap();
+    }
+}
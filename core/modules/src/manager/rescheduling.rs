@@ -0,0 +1,209 @@
+use super::scheduling::{assign_provisioner, match_provisioner};
+use async_trait::async_trait;
+use domain::event::{
+    ProvisionerIdentifier, ProvisioningJobReturnedNotification, SessionTerminatedNotification,
+};
+use harness::Service;
+use library::communication::event::{Consumer, NotificationFrame, NotificationPublisher};
+use library::communication::request::RequestError;
+use library::communication::{BlackboxError, CommunicationFactory};
+use library::EmptyResult;
+use thiserror::Error;
+use tracing::{debug, info, instrument, warn};
+
+#[derive(Debug, Error)]
+enum ReschedulingServiceError {
+    #[error("capabilities parsing failed")]
+    ParsingFailed(#[from] serde_json::Error),
+
+    #[error("no other provisioner answered the matching request")]
+    NoProvisioner,
+
+    #[error("matching request failed")]
+    RequestFailure(#[from] RequestError),
+}
+
+/// Assigns a new provisioner to jobs handed back by their original one
+///
+/// Consumes:
+/// - [`ProvisioningJobReturnedNotification`]
+///
+/// Publishes:
+/// - [`SessionTerminatedNotification`]
+/// - [`ProvisioningJobAssignedNotification`](domain::event::ProvisioningJobAssignedNotification)
+/// - [`SessionScheduledNotification`](domain::event::SessionScheduledNotification)
+///
+/// Requests:
+/// - [`ProvisionerMatchRequest`](domain::request::ProvisionerMatchRequest)
+pub struct ReschedulingService<F: CommunicationFactory> {
+    publisher: <F as CommunicationFactory>::NotificationPublisher,
+    requestor: <F as CommunicationFactory>::Requestor,
+}
+
+impl<F> Service<F> for ReschedulingService<F>
+where
+    F: CommunicationFactory + Send + Sync,
+{
+    const NAME: &'static str = "ReschedulingService";
+    type Instance = ReschedulingService<F>;
+    type Config = ();
+
+    fn instantiate(factory: F, _config: &Self::Config) -> Self::Instance {
+        Self {
+            publisher: factory.notification_publisher(),
+            requestor: factory.requestor(),
+        }
+    }
+}
+
+impl<F> ReschedulingService<F>
+where
+    F: CommunicationFactory + Send + Sync,
+{
+    async fn handle_event(
+        &self,
+        notification: &<Self as Consumer>::Notification,
+    ) -> Result<ProvisionerIdentifier, ReschedulingServiceError> {
+        let capabilities = notification.capabilities.parse()?;
+
+        match_provisioner(
+            &self.requestor,
+            capabilities,
+            Some(&notification.provisioner),
+        )
+        .await?
+        .ok_or(ReschedulingServiceError::NoProvisioner)
+    }
+}
+
+#[async_trait]
+impl<F> Consumer for ReschedulingService<F>
+where
+    F: CommunicationFactory + Send + Sync,
+{
+    type Notification = ProvisioningJobReturnedNotification;
+
+    #[instrument(skip(self, notification), fields(id = ?notification.session_id, previous = ?notification.provisioner))]
+    async fn consume(&self, notification: NotificationFrame<Self::Notification>) -> EmptyResult {
+        debug!("Handling returned job");
+
+        match self.handle_event(&notification).await {
+            Err(ReschedulingServiceError::RequestFailure(e)) => {
+                warn!(error = ?e, "Session rescheduling failed");
+                Err(e.into())
+            }
+            Err(e) => {
+                warn!(error = ?e, "Session rescheduling failed");
+                let notification = SessionTerminatedNotification::new_for_startup_failure(
+                    notification.session_id,
+                    BlackboxError::new(e),
+                );
+
+                self.publisher.publish(&notification).await
+            }
+            Ok(provisioner) => {
+                info!(?provisioner, "Rescheduled session");
+
+                assign_provisioner(
+                    &self.publisher,
+                    notification.session_id,
+                    &notification.capabilities,
+                    provisioner,
+                )
+                .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use domain::event::{ProvisioningJobAssignedNotification, SessionScheduledNotification};
+    use domain::request::{ProvisionerMatchRequest, ProvisionerMatchResponse};
+    use domain::webdriver::{CapabilitiesRequest, RawCapabilitiesRequest};
+    use lazy_static::lazy_static;
+    use library::communication::implementation::mock::MockCommunicationFactory;
+    use uuid::Uuid;
+
+    lazy_static! {
+        static ref SESSION_ID: Uuid = Uuid::new_v4();
+        static ref PREVIOUS_ID: String = "previous-id".into();
+        static ref PROVISIONER_ID: String = "some-id".into();
+    }
+
+    fn returned_notification() -> (CapabilitiesRequest, ProvisioningJobReturnedNotification) {
+        let raw_capabilities = CapabilitiesRequest::default();
+        let capabilities =
+            RawCapabilitiesRequest::new(serde_json::to_string(&raw_capabilities).unwrap());
+
+        let returned = ProvisioningJobReturnedNotification {
+            session_id: *SESSION_ID,
+            capabilities,
+            provisioner: (*PREVIOUS_ID).clone(),
+        };
+
+        (raw_capabilities, returned)
+    }
+
+    #[tokio::test]
+    async fn assign_different_provisioner() {
+        let (raw_capabilities, returned) = returned_notification();
+
+        let job_assigned = ProvisioningJobAssignedNotification {
+            session_id: *SESSION_ID,
+            capabilities: returned.capabilities.clone(),
+        };
+
+        let scheduled = SessionScheduledNotification {
+            id: *SESSION_ID,
+            provisioner: (*PROVISIONER_ID).clone(),
+        };
+
+        let match_request = ProvisionerMatchRequest::new(raw_capabilities);
+        let match_response = vec![
+            ProvisionerMatchResponse {
+                provisioner: (*PREVIOUS_ID).clone(),
+            },
+            ProvisionerMatchResponse {
+                provisioner: (*PROVISIONER_ID).clone(),
+            },
+        ];
+
+        let factory = MockCommunicationFactory::default();
+
+        factory
+            .expect_and_respond(&match_request, match_response)
+            .expect_with_extension(&job_assigned, PROVISIONER_ID.to_string())
+            .expect(&scheduled);
+
+        ReschedulingService::instantiate(factory, &())
+            .consume(NotificationFrame::new(returned))
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn fail_without_other_provisioner() {
+        let (raw_capabilities, returned) = returned_notification();
+
+        let cause = BlackboxError::new(ReschedulingServiceError::NoProvisioner);
+        let failure = SessionTerminatedNotification::new_for_startup_failure(*SESSION_ID, cause);
+
+        let match_request = ProvisionerMatchRequest::new(raw_capabilities);
+        let match_response = vec![ProvisionerMatchResponse {
+            provisioner: (*PREVIOUS_ID).clone(),
+        }];
+
+        let factory = MockCommunicationFactory::default();
+
+        factory
+            .expect_and_respond(&match_request, match_response)
+            .expect(&failure);
+
+        ReschedulingService::instantiate(factory, &())
+            .consume(NotificationFrame::new(returned))
+            .await
+            .unwrap();
+    }
+}
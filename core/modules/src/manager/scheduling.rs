@@ -1,10 +1,11 @@
 use async_trait::async_trait;
 use domain::event::{
     ProvisionerIdentifier, ProvisioningJobAssignedNotification, SessionCreatedNotification,
-    SessionMetadataModifiedNotification, SessionScheduledNotification,
+    SessionIdentifier, SessionMetadataModifiedNotification, SessionScheduledNotification,
     SessionTerminatedNotification,
 };
 use domain::request::ProvisionerMatchRequest;
+use domain::webdriver::{CapabilitiesRequest, RawCapabilitiesRequest};
 use harness::Service;
 use library::communication::event::{Consumer, NotificationFrame, NotificationPublisher};
 use library::communication::request::{RequestError, Requestor, ResponseCollectionTimeout};
@@ -19,6 +20,57 @@ use tracing::{debug, info, instrument, warn};
 const MATCHING_TIMEOUT: ResponseCollectionTimeout =
     ResponseCollectionTimeout::Split(Duration::from_secs(10), Duration::from_millis(100));
 
+/// Asks around for provisioners that can handle the requirements and picks a random one,
+/// skipping the `excluded` provisioner if it happens to respond
+pub(super) async fn match_provisioner<R: Requestor + Send + Sync>(
+    requestor: &R,
+    capabilities: CapabilitiesRequest,
+    excluded: Option<&ProvisionerIdentifier>,
+) -> Result<Option<ProvisionerIdentifier>, RequestError> {
+    debug!("Requesting available provisioners");
+    let request = ProvisionerMatchRequest::new(capabilities);
+    let mut responses = requestor
+        .request(&request, None, MATCHING_TIMEOUT)
+        .await?
+        .into_iter()
+        .map(|r| r.provisioner)
+        .filter(|p| Some(p) != excluded)
+        .collect::<Vec<_>>();
+
+    // Provide some laymans load balancing until load factors are implemented
+    debug!(count = responses.len(), "Received available provisioners");
+    responses.shuffle(&mut thread_rng());
+
+    Ok(responses.pop())
+}
+
+/// Hands a job to the given provisioner and announces the scheduling decision
+pub(super) async fn assign_provisioner<P: NotificationPublisher + Send + Sync>(
+    publisher: &P,
+    session_id: SessionIdentifier,
+    capabilities: &RawCapabilitiesRequest,
+    provisioner: ProvisionerIdentifier,
+) -> EmptyResult {
+    // Let the provisioner know it has got a new job
+    let provisioner_queue_extension = provisioner.to_string();
+    let job_assignment_notification = ProvisioningJobAssignedNotification {
+        session_id,
+        capabilities: capabilities.to_owned(),
+    };
+
+    publisher
+        .publish_with_extension(&job_assignment_notification, provisioner_queue_extension)
+        .await?;
+
+    // Publish the notification for the scheduled event
+    let scheduled_notification = SessionScheduledNotification {
+        id: session_id,
+        provisioner,
+    };
+
+    publisher.publish(&scheduled_notification).await
+}
+
 #[derive(Debug, Error)]
 enum SchedulingServiceError {
     #[error("capabilities parsing failed")]
@@ -124,22 +176,9 @@ where
             ));
         }
 
-        // Ask around for provisioners that can handle the requirements
-        debug!("Requesting available provisioners");
-        let request = ProvisionerMatchRequest::new(capabilities);
-        let mut responses = self
-            .requestor
-            .request(&request, None, MATCHING_TIMEOUT)
-            .await?;
-
-        // Provide some laymans load balancing until load factors are implemented
-        debug!(count = responses.len(), "Received available provisioners");
-        responses.shuffle(&mut thread_rng());
-
-        responses
-            .pop()
+        match_provisioner(&self.requestor, capabilities, None)
+            .await?
             .ok_or(SchedulingServiceError::NoProvisioner)
-            .map(|r| r.provisioner)
     }
 }
 
@@ -172,27 +211,13 @@ where
             Ok(provisioner) => {
                 info!(?provisioner, "Scheduled session");
 
-                // Let the provisioner know it has got a new job
-                let provisioner_queue_extension = provisioner.to_string();
-                let job_assignment_notification = ProvisioningJobAssignedNotification {
-                    session_id: notification.id,
-                    capabilities: notification.capabilities.to_owned(),
-                };
-
-                self.publisher
-                    .publish_with_extension(
-                        &job_assignment_notification,
-                        provisioner_queue_extension,
-                    )
-                    .await?;
-
-                // Publish the notification for the scheduled event
-                let scheduled_notification = SessionScheduledNotification {
-                    id: notification.id,
+                assign_provisioner(
+                    &self.publisher,
+                    notification.id,
+                    &notification.capabilities,
                     provisioner,
-                };
-
-                self.publisher.publish(&scheduled_notification).await
+                )
+                .await
             }
         }
     }
@@ -204,9 +229,7 @@ mod does {
 
     use super::*;
     use domain::request::ProvisionerMatchResponse;
-    use domain::webdriver::{
-        Capabilities, CapabilitiesRequest, RawCapabilitiesRequest, WebGridOptions,
-    };
+    use domain::webdriver::{Capabilities, WebGridOptions};
     use lazy_static::lazy_static;
     use library::communication::implementation::mock::MockCommunicationFactory;
     use uuid::Uuid;
@@ -0,0 +1,98 @@
+use async_trait::async_trait;
+use domain::event::DeathReason;
+use domain::webdriver::WebDriverMonitor;
+use harness::HeartStone;
+use jatsl::{Job, JobManager};
+use library::{EmptyResult, PerformanceMonitor};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+use tracing::{error, warn};
+
+/// Time between two consecutive process checks
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Number of consecutive checks without any browser process before the browser is considered crashed.
+/// This prevents a regular session shutdown, during which the browser exits before the node does, from being reported.
+const BROWSER_ABSENCE_THRESHOLD: usize = 3;
+
+/// Watches the webdriver and browser processes and stops the heart if either exits unexpectedly
+pub struct CrashDetectionJob {
+    monitor: WebDriverMonitor,
+    heart_stone: Mutex<HeartStone>,
+}
+
+impl CrashDetectionJob {
+    pub fn new(monitor: WebDriverMonitor, heart_stone: HeartStone) -> Self {
+        Self {
+            monitor,
+            heart_stone: Mutex::new(heart_stone),
+        }
+    }
+
+    async fn browser_running(&self, pid: u32) -> Option<bool> {
+        match PerformanceMonitor::recursively_find_child_processes_of_pid(pid as i32).await {
+            Ok(children) => Some(!children.is_empty()),
+            Err(error) => {
+                error!(?error, "failed to fetch children of webdriver");
+                None
+            }
+        }
+    }
+
+    async fn crash(&self, exit_status: Option<String>) {
+        let log_tail = self.monitor.log().tail();
+        warn!(?exit_status, %log_tail, "Browser crashed");
+
+        self.heart_stone
+            .lock()
+            .await
+            .stop(DeathReason::BrowserCrashed {
+                exit_status,
+                log_tail,
+            })
+            .await;
+    }
+}
+
+#[async_trait]
+impl Job for CrashDetectionJob {
+    const NAME: &'static str = module_path!();
+
+    async fn execute(&self, manager: JobManager) -> EmptyResult {
+        manager.ready().await;
+
+        let mut browser_seen = false;
+        let mut browser_absent_count = 0;
+
+        loop {
+            sleep(CHECK_INTERVAL).await;
+
+            if let Some(status) = self.monitor.exit_status().await? {
+                self.crash(Some(status.to_string())).await;
+                return Ok(());
+            }
+
+            let pid = match self.monitor.pid() {
+                Some(pid) => pid,
+                None => continue,
+            };
+
+            match self.browser_running(pid).await {
+                Some(true) => {
+                    browser_seen = true;
+                    browser_absent_count = 0;
+                }
+                Some(false) if browser_seen => {
+                    browser_absent_count += 1;
+
+                    if browser_absent_count >= BROWSER_ABSENCE_THRESHOLD {
+                        self.crash(None).await;
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
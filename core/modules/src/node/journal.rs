@@ -0,0 +1,206 @@
+use chrono::{DateTime, Utc};
+use domain::{event::SessionIdentifier, storage_path};
+use library::storage::StorageBackend;
+use library::EmptyResult;
+use serde_json::{json, Value};
+use std::io::SeekFrom;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::tempfile;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// Name of the journal object in the storage directory of the session
+const JOURNAL_FILENAME: &str = "commands.jsonl";
+
+/// Maximum number of characters retained from request and response bodies
+const MAX_BODY_LENGTH: usize = 2048;
+
+/// Placeholder for values which may contain sensitive user input
+const REDACTED: &str = "[REDACTED]";
+
+/// Single command executed by a client
+pub struct JournalEntry {
+    pub method: String,
+    pub path: String,
+    pub started_at: DateTime<Utc>,
+    pub duration: Duration,
+    pub status: u16,
+    pub request: String,
+    pub response: String,
+}
+
+impl JournalEntry {
+    fn to_json(&self) -> Value {
+        let finished_at = self.started_at
+            + chrono::Duration::from_std(self.duration)
+                .unwrap_or_else(|_| chrono::Duration::zero());
+
+        json!({
+            "method": self.method,
+            "path": self.path,
+            "startedAt": self.started_at,
+            "finishedAt": finished_at,
+            "durationMs": self.duration.as_secs_f64() * 1000.0,
+            "status": self.status,
+            "request": truncate(&redact(&self.path, &self.request)),
+            "response": truncate(&self.response),
+        })
+    }
+}
+
+/// Append-only record of all WebDriver commands executed in a session
+///
+/// Entries are buffered in a temporary file and uploaded as `commands.jsonl` on termination.
+#[derive(Clone)]
+pub struct CommandJournal {
+    file: Arc<Mutex<Option<File>>>,
+}
+
+impl Default for CommandJournal {
+    fn default() -> Self {
+        let file = match tempfile() {
+            Ok(file) => Some(File::from_std(file)),
+            Err(error) => {
+                warn!(?error, "Unable to create command journal file");
+                None
+            }
+        };
+
+        Self {
+            file: Arc::new(Mutex::new(file)),
+        }
+    }
+}
+
+impl CommandJournal {
+    /// Appends an entry to the journal
+    pub async fn record(&self, entry: JournalEntry) {
+        let line = format!("{}\n", entry.to_json());
+
+        if let Some(file) = self.file.lock().await.as_mut() {
+            if let Err(error) = file.write_all(line.as_bytes()).await {
+                warn!(?error, "Failed to write command journal entry");
+            }
+        }
+    }
+
+    /// Uploads all recorded entries to the storage directory of the session
+    pub async fn upload<S: StorageBackend>(
+        &self,
+        session_id: SessionIdentifier,
+        storage: &S,
+    ) -> EmptyResult {
+        let mut content = Vec::new();
+
+        if let Some(file) = self.file.lock().await.as_mut() {
+            file.flush().await?;
+            file.seek(SeekFrom::Start(0)).await?;
+            file.read_to_end(&mut content).await?;
+        }
+
+        let path = storage_path(session_id, JOURNAL_FILENAME)
+            .to_string_lossy()
+            .into_owned();
+
+        storage.put_object(&path, &content).await
+    }
+}
+
+/// Replaces typed text in `sendKeys` and key action requests
+fn redact(path: &str, body: &str) -> String {
+    let mut value: Value = match serde_json::from_str(body) {
+        Ok(value) => value,
+        Err(_) => return body.to_owned(),
+    };
+
+    if path.contains("/element/") && path.ends_with("/value") {
+        if let Some(object) = value.as_object_mut() {
+            for key in ["text", "value"] {
+                if object.contains_key(key) {
+                    object.insert(key.to_owned(), REDACTED.into());
+                }
+            }
+        }
+    } else if path.ends_with("/actions") {
+        let sources = value
+            .get_mut("actions")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten()
+            .filter(|source| source.get("type") == Some(&json!("key")));
+
+        for source in sources {
+            let actions = source
+                .get_mut("actions")
+                .and_then(Value::as_array_mut)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_object_mut);
+
+            for action in actions {
+                if action.contains_key("value") {
+                    action.insert("value".to_owned(), REDACTED.into());
+                }
+            }
+        }
+    } else {
+        return body.to_owned();
+    }
+
+    value.to_string()
+}
+
+fn truncate(body: &str) -> &str {
+    match body.char_indices().nth(MAX_BODY_LENGTH) {
+        Some((index, _)) => &body[..index],
+        None => body,
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn redact_send_keys() {
+        let redacted = redact(
+            "/element/abc/value",
+            r#"{"text":"hunter2","value":["h","u"]}"#,
+        );
+
+        assert!(!redacted.contains("hunter2"));
+        assert_eq!(
+            serde_json::from_str::<Value>(&redacted).unwrap(),
+            json!({ "text": REDACTED, "value": REDACTED })
+        );
+    }
+
+    #[test]
+    fn redact_key_actions() {
+        let body = json!({ "actions": [
+            { "type": "key", "id": "keyboard", "actions": [{ "type": "keyDown", "value": "x" }] },
+            { "type": "pointer", "id": "mouse", "actions": [{ "type": "pointerMove", "x": 1 }] }
+        ]});
+
+        let redacted: Value = serde_json::from_str(&redact("/actions", &body.to_string())).unwrap();
+
+        assert_eq!(redacted["actions"][0]["actions"][0]["value"], REDACTED);
+        assert_eq!(redacted["actions"][1], body["actions"][1]);
+    }
+
+    #[test]
+    fn retain_other_requests() {
+        let body = r#"{"url":"https://example.com"}"#;
+        assert_eq!(redact("/url", body), body);
+    }
+
+    #[test]
+    fn truncate_long_bodies() {
+        let body = "ä".repeat(MAX_BODY_LENGTH + 10);
+        assert_eq!(truncate(&body).chars().count(), MAX_BODY_LENGTH);
+        assert_eq!(truncate("short"), "short");
+    }
+}
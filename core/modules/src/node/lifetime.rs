@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use domain::event::DeathReason;
+use harness::HeartStone;
+use jatsl::{Job, JobManager};
+use library::EmptyResult;
+use std::time::Instant;
+use tokio::sync::Mutex;
+use tokio::time::sleep_until;
+use tracing::info;
+
+/// Stops the heart once the maximum duration of the session has been reached
+pub struct MaxDurationJob {
+    deadline: Instant,
+    heart_stone: Mutex<HeartStone>,
+}
+
+impl MaxDurationJob {
+    pub fn new(deadline: Instant, heart_stone: HeartStone) -> Self {
+        Self {
+            deadline,
+            heart_stone: Mutex::new(heart_stone),
+        }
+    }
+}
+
+#[async_trait]
+impl Job for MaxDurationJob {
+    const NAME: &'static str = module_path!();
+
+    async fn execute(&self, manager: JobManager) -> EmptyResult {
+        manager.ready().await;
+
+        sleep_until(self.deadline.into()).await;
+
+        info!("Maximum session duration has been reached");
+        self.heart_stone
+            .lock()
+            .await
+            .stop(DeathReason::MaxDurationExceeded)
+            .await;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,270 @@
+use async_trait::async_trait;
+use futures::{join, SinkExt, StreamExt};
+use jatsl::{Job, JobManager};
+use library::EmptyResult;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use serde::Deserialize;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tracing::{debug, info, warn};
+use warp::http::StatusCode;
+use warp::ws::{Message, WebSocket, Ws};
+use warp::{Filter, Reply};
+
+/// Virtual display the browser is rendered to
+const DISPLAY: &str = ":42";
+
+/// Local port of the VNC server which accepts input events
+const VNC_PORT_INTERACTIVE: u16 = 5900;
+
+/// Local port of the VNC server which ignores input events
+const VNC_PORT_VIEW_ONLY: u16 = 5901;
+
+const TOKEN_LENGTH: usize = 32;
+
+/// Level of access granted to a live view connection
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum LiveViewMode {
+    View,
+    Interact,
+}
+
+impl LiveViewMode {
+    fn vnc_port(self) -> u16 {
+        match self {
+            LiveViewMode::View => VNC_PORT_VIEW_ONLY,
+            LiveViewMode::Interact => VNC_PORT_INTERACTIVE,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct LiveViewQuery {
+    token: String,
+    mode: Option<LiveViewMode>,
+}
+
+/// Creates a random secret which clients have to present to connect to the live view
+pub fn generate_token() -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// Compares two tokens in constant time to prevent guessing them by timing the responses
+fn tokens_match(provided: &str, expected: &str) -> bool {
+    provided.len() == expected.len()
+        && provided
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Serves the virtual display of the session as VNC-over-WebSocket at `/session/<id>/webgrid/live`
+#[derive(Clone)]
+pub struct LiveViewJob {
+    port: u16,
+    session_id: String,
+    token: String,
+    interactive: bool,
+}
+
+impl LiveViewJob {
+    pub fn new(port: u16, session_id: String, token: String, interactive: bool) -> Self {
+        Self {
+            port,
+            session_id,
+            token,
+            interactive,
+        }
+    }
+
+    fn launch_vnc_server(mode: LiveViewMode) -> std::io::Result<Child> {
+        let port = mode.vnc_port().to_string();
+        let mut args = vec![
+            "-display",
+            DISPLAY,
+            "-localhost",
+            "-rfbport",
+            &port,
+            "-shared",
+            "-forever",
+            "-nopw",
+            "-quiet",
+        ];
+
+        if mode == LiveViewMode::View {
+            args.push("-viewonly");
+        }
+
+        info!("Launching x11vnc {}", args.join(" "));
+        Command::new("x11vnc")
+            .args(&args)
+            .kill_on_drop(true)
+            .spawn()
+    }
+
+    fn authorize(
+        &self,
+        session_id: &str,
+        query: &LiveViewQuery,
+    ) -> Result<LiveViewMode, StatusCode> {
+        let mode = query.mode.unwrap_or(LiveViewMode::View);
+
+        if !session_id.eq_ignore_ascii_case(&self.session_id) {
+            Err(StatusCode::NOT_FOUND)
+        } else if !tokens_match(&query.token, &self.token)
+            || (mode == LiveViewMode::Interact && !self.interactive)
+        {
+            Err(StatusCode::FORBIDDEN)
+        } else {
+            Ok(mode)
+        }
+    }
+}
+
+#[async_trait]
+impl Job for LiveViewJob {
+    const NAME: &'static str = module_path!();
+    const SUPPORTS_GRACEFUL_TERMINATION: bool = true;
+
+    async fn execute(&self, manager: JobManager) -> EmptyResult {
+        // The servers are kept around until the job terminates and killed once dropped
+        let mut servers = vec![Self::launch_vnc_server(LiveViewMode::View)?];
+
+        if self.interactive {
+            servers.push(Self::launch_vnc_server(LiveViewMode::Interact)?);
+        }
+
+        let job = self.clone();
+        let route = warp::path!("session" / String / "webgrid" / "live")
+            .and(warp::query::<LiveViewQuery>())
+            .and(warp::ws())
+            .map(move |session_id: String, query: LiveViewQuery, ws: Ws| {
+                match job.authorize(&session_id, &query) {
+                    Ok(mode) => ws
+                        .on_upgrade(move |socket| bridge(socket, mode.vnc_port()))
+                        .into_response(),
+                    Err(status) => status.into_response(),
+                }
+            })
+            .with(warp::trace::named("live"));
+
+        let source_addr: SocketAddr = ([0, 0, 0, 0], self.port).into();
+        let (addr, server) = warp::serve(route)
+            .bind_with_graceful_shutdown(source_addr, manager.termination_signal());
+
+        info!(?addr, "Serving live view");
+        manager.ready().await;
+        server.await;
+
+        drop(servers);
+
+        Ok(())
+    }
+}
+
+/// Relays the RFB protocol between a WebSocket client and the local VNC server
+async fn bridge(socket: WebSocket, vnc_port: u16) {
+    let stream = match TcpStream::connect(("127.0.0.1", vnc_port)).await {
+        Ok(stream) => stream,
+        Err(error) => {
+            warn!(?error, vnc_port, "Failed to connect to VNC server");
+            return;
+        }
+    };
+
+    debug!(vnc_port, "Live view client connected");
+
+    let (mut vnc_rx, mut vnc_tx) = stream.into_split();
+    let (mut client_tx, mut client_rx) = socket.split();
+
+    let upstream = async move {
+        while let Some(Ok(message)) = client_rx.next().await {
+            if message.is_close() {
+                break;
+            }
+
+            if message.is_binary() && vnc_tx.write_all(message.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+
+        vnc_tx.shutdown().await.ok();
+    };
+
+    let downstream = async move {
+        let mut buffer = vec![0; 64 * 1024];
+
+        while let Ok(count) = vnc_rx.read(&mut buffer).await {
+            if count == 0
+                || client_tx
+                    .send(Message::binary(&buffer[..count]))
+                    .await
+                    .is_err()
+            {
+                break;
+            }
+        }
+
+        client_tx.close().await.ok();
+    };
+
+    join!(upstream, downstream);
+    debug!(vnc_port, "Live view client disconnected");
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    fn job(interactive: bool) -> LiveViewJob {
+        LiveViewJob::new(0, "session".into(), "secret".into(), interactive)
+    }
+
+    fn query(token: &str, mode: Option<LiveViewMode>) -> LiveViewQuery {
+        LiveViewQuery {
+            token: token.into(),
+            mode,
+        }
+    }
+
+    #[test]
+    fn generate_unique_tokens() {
+        let token = generate_token();
+
+        assert_eq!(token.len(), TOKEN_LENGTH);
+        assert_ne!(token, generate_token());
+    }
+
+    #[test]
+    fn reject_unauthorized_clients() {
+        assert_eq!(
+            job(false).authorize("session", &query("secret", None)),
+            Ok(LiveViewMode::View)
+        );
+        assert_eq!(
+            job(false).authorize("session", &query("guess", None)),
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            job(false).authorize("session", &query("secret", Some(LiveViewMode::Interact))),
+            Err(StatusCode::FORBIDDEN)
+        );
+        assert_eq!(
+            job(true).authorize("session", &query("secret", Some(LiveViewMode::Interact))),
+            Ok(LiveViewMode::Interact)
+        );
+        assert_eq!(
+            job(true).authorize("other", &query("secret", None)),
+            Err(StatusCode::NOT_FOUND)
+        );
+    }
+}
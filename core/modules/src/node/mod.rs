@@ -3,19 +3,26 @@
 use anyhow::anyhow;
 use async_trait::async_trait;
 use domain::event::{
-    ModuleTerminationReason, SessionClientMetadata, SessionOperationalNotification,
+    DeathReason, ModuleTerminationReason, SessionClientMetadata, SessionOperationalNotification,
     SessionTerminatedNotification, SessionTerminationReason,
 };
-use domain::webdriver::{Capabilities, WebDriver, WebDriverInstance};
-use domain::WebgridServiceDescriptor;
+use domain::recording::{
+    cache_export, download_recording, RecordingFormat, RecordingProfile, EXPORT_FILENAME,
+};
+use domain::webdriver::{
+    Capabilities, CapabilitiesRequest, DriverLog, RecordingMode, WebDriver, WebDriverInstance,
+    WebGridOptions,
+};
+use domain::{storage_path, WebgridServiceDescriptor};
 use harness::{
     DummyResourceHandleProvider, Heart, HeartStone, Module, RedisCommunicationFactory,
-    RedisServiceAdvertisementJob,
+    RedisServiceAdvertisementJob, SHUTDOWN_TIMEOUT,
 };
 use jatsl::{schedule, schedule_and_wait, JobScheduler};
 use library::communication::event::NotificationPublisher;
 use library::communication::{BlackboxError, CommunicationFactory};
 use library::storage::s3::S3StorageBackend;
+use library::storage::StorageBackend;
 use library::{
     AccumulatedPerformanceMetrics, BoxedError, EmptyResult, PerformanceMonitor,
     PerformanceMonitoringTarget,
@@ -23,22 +30,52 @@ use library::{
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
 use thiserror::Error;
+use tokio::fs::{self, File};
 use tokio::sync::mpsc::{self, UnboundedSender};
 use tokio::sync::Mutex;
-use tracing::{error, info};
+use tokio::time::timeout_at;
+use tracing::{error, info, warn};
 
+mod crash;
+mod journal;
+mod lifetime;
+mod live;
 mod metadata;
 mod options;
 mod proxy;
 mod recording;
+mod screenshot;
+mod subtitles;
 
 pub use options::Options;
 
+use self::crash::CrashDetectionJob;
+use self::journal::CommandJournal;
+use self::lifetime::MaxDurationJob;
+use self::live::{generate_token, LiveViewJob};
 use self::metadata::MetadataPublisherJob;
-use self::proxy::ProxyJob;
-use self::recording::RecordingJob;
+use self::proxy::{ProxyContext, ProxyJob, SessionOutcome};
+use self::recording::{
+    generate_previews, promote_staged, RecordingContext, RecordingControl, RecordingJob,
+    TimeLapseJob, UploadSpool, UploadSpoolJob,
+};
+use self::screenshot::ScreenshotCollector;
+use self::subtitles::SubtitleTrack;
+
+/// Time reserved at the end of the [`SHUTDOWN_TIMEOUT`] for publishing the termination notification
+const NOTIFICATION_RESERVE: Duration = Duration::from_secs(10);
+
+/// Largest recording which is cached as an MP4 export by the node
+const MAX_CACHED_EXPORT_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Time left before the [`SHUTDOWN_TIMEOUT`] at which post-processing of the recording is aborted
+const SHUTDOWN_MARGIN: Duration = Duration::from_secs(1);
+
+/// Time reserved before the notification deadline for bookkeeping after the recording has been finalized
+const RECORDING_RESERVE: Duration = Duration::from_secs(2);
 
 #[derive(Debug, Error)]
 enum NodeError {
@@ -52,20 +89,88 @@ enum NodeError {
 pub struct Node {
     options: Options,
     instance: Option<WebDriverInstance>,
+    driver_log: DriverLog,
+    driver_log_uploaded: bool,
     video_byte_count_total: Arc<AtomicUsize>,
+    recording_staging: Option<TempDir>,
+    upload_spool: Option<UploadSpool>,
+    lost_segments: Vec<String>,
+    recording_control: RecordingControl,
+    recording_profile: Option<RecordingProfile>,
+    recording_format: Option<RecordingFormat>,
+    outcome: SessionOutcome,
+    journal: CommandJournal,
+    journal_uploaded: bool,
+    subtitles: SubtitleTrack,
+    screenshots: ScreenshotCollector,
+    live_view_token: Option<String>,
+    download_directory: Option<TempDir>,
     profiling_tx: mpsc::UnboundedSender<Arc<Mutex<AccumulatedPerformanceMetrics>>>,
     profiling_rx: mpsc::UnboundedReceiver<Arc<Mutex<AccumulatedPerformanceMetrics>>>,
 }
 
+/// Capabilities requested by the client, using the first set if there are multiple alternatives
+fn requested_capabilities(raw: &str) -> Result<Capabilities, serde_json::Error> {
+    let request: CapabilitiesRequest = serde_json::from_str(raw)?;
+
+    Ok(request
+        .into_sets()
+        .into_iter()
+        .next()
+        .unwrap_or_else(Capabilities::empty))
+}
+
+/// Duration the session may run for, limited by the maximum duration configured for the grid
+fn max_duration(capabilities: &Capabilities, limit: Option<Duration>) -> Option<Duration> {
+    let requested = capabilities
+        .webgrid_options
+        .as_ref()
+        .and_then(|w| w.max_duration)
+        .map(Duration::from_secs);
+
+    match (requested, limit) {
+        (Some(requested), Some(limit)) => Some(requested.min(limit)),
+        (requested, limit) => requested.or(limit),
+    }
+}
+
 impl Node {
     /// Creates a new instance from raw parts
     pub fn new(options: Options) -> Self {
         let (profiling_tx, profiling_rx) = mpsc::unbounded_channel();
+        let recording_control = RecordingControl::default();
+        let screenshots = ScreenshotCollector::new(
+            options.id,
+            options.storage.backend.clone(),
+            options.error_screenshots,
+            recording_control.clone(),
+        );
+
+        let live_view_token = if options.live_view.enabled {
+            Some(generate_token())
+        } else {
+            None
+        };
 
         Self {
             options,
             instance: None,
+            driver_log: DriverLog::with_file(),
+            driver_log_uploaded: false,
             video_byte_count_total: Arc::new(AtomicUsize::new(0)),
+            recording_staging: None,
+            upload_spool: None,
+            lost_segments: Vec::new(),
+            recording_control,
+            recording_profile: None,
+            recording_format: None,
+            outcome: SessionOutcome::default(),
+            journal: CommandJournal::default(),
+            journal_uploaded: false,
+            subtitles: SubtitleTrack::default(),
+            screenshots,
+            live_view_token,
+            download_directory: None,
             profiling_tx,
             profiling_rx,
         }
@@ -127,17 +232,47 @@ impl Node {
         (heart, stone)
     }
 
+    fn max_duration(&self, capabilities: &Capabilities) -> Option<Duration> {
+        max_duration(capabilities, self.options.max_duration)
+    }
+
+    fn requested_capabilities(&self) -> Result<Capabilities, serde_json::Error> {
+        requested_capabilities(&self.options.webdriver.capabilities)
+    }
+
     async fn start_driver(&mut self) -> EmptyResult {
         info!("Starting webdriver");
+        let capabilities = self.requested_capabilities()?;
+        let log_level = capabilities
+            .webgrid_options
+            .and_then(|w| w.driver_log_level);
+
+        if capabilities.downloads_enabled == Some(true) {
+            if self.options.webdriver.variant.supports_download_directory() {
+                self.download_directory = Some(TempDir::new()?);
+            } else {
+                warn!("Downloads are not supported by this browser, ignoring se:downloadsEnabled");
+            }
+        }
+
         let webdriver = WebDriver::default()
             .binary(&self.options.webdriver.binary)
             .variant(self.options.webdriver.variant)
             .resolution(self.options.webdriver.resolution)
             .startup_timeout(self.options.webdriver.startup_timeout)
+            .log_level(log_level)
+            .log(self.driver_log.clone())
             .capabilities(&self.options.webdriver.capabilities)
+            .download_directory(self.download_directory.as_ref().map(TempDir::path))
             .launch()
             .await?;
 
+        let screenshot_url = format!(
+            "http://{}/session/{}/screenshot",
+            webdriver.socket_addr(),
+            webdriver.session_id()
+        );
+        self.screenshots.attach(screenshot_url).await;
         self.instance = Some(webdriver);
 
         Ok(())
@@ -211,6 +346,7 @@ impl Node {
         &self,
         heart_stone: HeartStone,
         metadata_tx: UnboundedSender<SessionClientMetadata>,
+        deadline: Option<Instant>,
     ) -> Result<ProxyJob, NodeError> {
         let instance = match &self.instance {
             Some(instance) => instance,
@@ -222,17 +358,43 @@ impl Node {
         let session_id_external = self.options.id.to_string();
         let identifier = format!("node-{}", session_id_external);
 
+        let context = ProxyContext {
+            heart_stone,
+            metadata_tx,
+            deadline,
+            journal: self.journal.clone(),
+            subtitles: self.subtitles.clone(),
+            outcome: self.outcome.clone(),
+            recording: self.recording_control.clone(),
+            screenshots: self.screenshots.clone(),
+            live_view_token: self.live_view_token.clone(),
+            max_upload_size: self.options.max_upload_size,
+            download_directory: self
+                .download_directory
+                .as_ref()
+                .map(|directory| directory.path().to_owned()),
+        };
+
         Ok(ProxyJob::new(
             crate::constants::PORT_NODE,
             identifier,
             authority,
             session_id_internal,
             session_id_external,
-            heart_stone,
-            metadata_tx,
+            context,
         ))
     }
 
+    fn build_crash_detection_job(
+        &self,
+        heart_stone: HeartStone,
+    ) -> Result<CrashDetectionJob, NodeError> {
+        match &self.instance {
+            Some(instance) => Ok(CrashDetectionJob::new(instance.monitor(), heart_stone)),
+            None => Err(NodeError::DriverNotInitialized),
+        }
+    }
+
     fn build_advertise_job(&self) -> RedisServiceAdvertisementJob<WebgridServiceDescriptor> {
         let endpoint = format!("{}:{}", self.options.host, crate::constants::PORT_NODE);
 
@@ -243,6 +405,31 @@ impl Node {
         )
     }
 
+    fn build_live_view_jobs(
+        &self,
+    ) -> Option<(
+        LiveViewJob,
+        RedisServiceAdvertisementJob<WebgridServiceDescriptor>,
+    )> {
+        let token = self.live_view_token.clone()?;
+        let endpoint = format!("{}:{}", self.options.host, crate::constants::PORT_LIVE);
+
+        let live_view_job = LiveViewJob::new(
+            crate::constants::PORT_LIVE,
+            self.options.id.to_string(),
+            token,
+            self.options.live_view.interactive,
+        );
+
+        let advertise_job = RedisServiceAdvertisementJob::new(
+            self.options.redis.url.clone(),
+            WebgridServiceDescriptor::LiveView(self.options.id),
+            endpoint,
+        );
+
+        Some((live_view_job, advertise_job))
+    }
+
     fn build_metadata_publisher_job(
         &self,
     ) -> (MetadataPublisherJob, UnboundedSender<SessionClientMetadata>) {
@@ -253,32 +440,93 @@ impl Node {
         )
     }
 
-    fn build_recording_job(
-        &self,
-        capabilities: Capabilities,
-    ) -> Option<RecordingJob<S3StorageBackend>> {
-        if capabilities
-            .webgrid_options
-            .map(|w| w.disable_recording)
-            .unwrap_or(false)
-        {
+    /// Determines whether and how the session is recorded and prepares the state shared by the recording
+    fn prepare_recording(
+        &mut self,
+        options: &WebGridOptions,
+    ) -> Result<Option<(S3StorageBackend, RecordingContext, RecordingFormat)>, BoxedError> {
+        let storage = match &self.options.storage.backend {
+            Some(storage) if !options.disable_recording => storage.clone(),
+            _ => return Ok(None),
+        };
+
+        // Recordings which may be discarded are kept on the node until the session terminates
+        let staging = if options.recording_mode == Some(RecordingMode::OnFailure) {
+            let directory = TempDir::new()?;
+            let path = directory.path().to_owned();
+            self.recording_staging = Some(directory);
+            Some(path)
+        } else {
             None
+        };
+
+        let format = options
+            .recording_format
+            .unwrap_or(self.options.recording.format);
+        self.recording_format = Some(format);
+
+        // Staged recordings are uploaded after the session terminated and thus never need to be spooled
+        let spool = if staging.is_none() {
+            let spool = UploadSpool::new()?;
+            self.upload_spool = Some(spool.clone());
+            Some(spool)
         } else {
-            let interval = if self.options.profile {
-                Some(self.options.profiler_sampling_interval)
-            } else {
-                None
-            };
+            None
+        };
 
-            Some(RecordingJob::new(
-                self.options.id,
-                self.options.recording.generate_arguments(),
-                self.options.storage.backend.clone()?,
-                self.video_byte_count_total.clone(),
-                self.profiling_tx.clone(),
-                interval,
-            ))
-        }
+        let context = RecordingContext {
+            staging,
+            spool,
+            byte_count_total: self.video_byte_count_total.clone(),
+            control: self.recording_control.clone(),
+        };
+
+        Ok(Some((storage, context, format)))
+    }
+
+    fn build_recording_job(
+        &mut self,
+        options: &WebGridOptions,
+        storage: S3StorageBackend,
+        context: RecordingContext,
+    ) -> RecordingJob<S3StorageBackend> {
+        let profile = options
+            .recording_profile
+            .unwrap_or(self.options.recording.profile);
+        self.recording_profile = Some(profile);
+
+        let interval = if self.options.profile {
+            Some(self.options.profiler_sampling_interval)
+        } else {
+            None
+        };
+
+        RecordingJob::new(
+            self.options.id,
+            self.options
+                .recording
+                .generate_arguments(self.options.webdriver.resolution, profile),
+            storage,
+            context,
+            self.profiling_tx.clone(),
+            interval,
+        )
+    }
+
+    fn build_time_lapse_job(
+        &self,
+        storage: S3StorageBackend,
+        context: RecordingContext,
+    ) -> TimeLapseJob<S3StorageBackend> {
+        TimeLapseJob::new(
+            self.options.id,
+            self.options
+                .recording
+                .generate_frame_arguments(self.options.webdriver.resolution),
+            self.options.recording.timelapse_interval,
+            storage,
+            context,
+        )
     }
 
     async fn send_alive_notification(&self) -> Result<(), NodeError> {
@@ -309,6 +557,135 @@ impl Node {
         }
     }
 
+    async fn upload_journal(&mut self) {
+        if let Some(storage) = &self.options.storage.backend {
+            info!("Uploading command journal");
+            match self.journal.upload(self.options.id, storage).await {
+                Ok(_) => self.journal_uploaded = true,
+                Err(error) => error!(?error, "Failed to upload command journal"),
+            }
+        }
+    }
+
+    /// Flushes spooled uploads and stores or discards a staged recording
+    async fn finalize_recording(&mut self, abnormal_termination: bool, deadline: Instant) {
+        let storage = match &self.options.storage.backend {
+            Some(storage) => storage,
+            None => return,
+        };
+
+        if let Some(spool) = self.upload_spool.take() {
+            info!("Flushing upload spool");
+            let budget = self
+                .options
+                .recording
+                .spool_flush_timeout
+                .min(deadline.saturating_duration_since(Instant::now()));
+            self.lost_segments = spool.flush(storage, self.options.id, budget).await;
+
+            if !self.lost_segments.is_empty() {
+                warn!(lost = ?self.lost_segments, "Failed to upload parts of the recording");
+            }
+        }
+
+        if let Some(staging) = self.recording_staging.take() {
+            if abnormal_termination || self.outcome.failed() {
+                info!("Promoting staged recording");
+                match promote_staged(staging.path(), self.options.id, storage, deadline).await {
+                    Ok(promotion) => {
+                        if !promotion.unpromoted.is_empty() {
+                            warn!(lost = ?promotion.unpromoted, "Failed to promote parts of the recording");
+                        }
+
+                        self.video_byte_count_total
+                            .store(promotion.byte_count, Ordering::Relaxed);
+                        self.lost_segments.extend(promotion.unpromoted);
+                    }
+                    Err(error) => error!(?error, "Failed to promote staged recording"),
+                }
+            } else {
+                info!("Discarding staged recording of successful session");
+                self.video_byte_count_total.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Whether a video recording has been stored for the session
+    fn has_video(&self) -> bool {
+        self.video_byte_count_total.load(Ordering::Relaxed) > 0
+            && self.recording_format == Some(RecordingFormat::Video)
+    }
+
+    /// Generates previews and caches the export of the stored video, neither of which is required for reporting the termination
+    async fn process_recording(&self) -> EmptyResult {
+        let storage = match &self.options.storage.backend {
+            Some(storage) if self.has_video() => storage,
+            _ => return Ok(()),
+        };
+
+        // The recording is copied to disk segment by segment as it may be too large to be held in memory
+        let directory = TempDir::new()?;
+        let video = directory.path().join(EXPORT_FILENAME);
+        let mut file = File::create(&video).await?;
+        let recording = download_recording(storage, self.options.id, &mut file).await?;
+        drop(file);
+
+        info!("Generating recording previews");
+        if let Err(error) =
+            generate_previews(self.options.id, storage, &video, recording.duration).await
+        {
+            warn!(?error, "Failed to generate recording previews");
+        }
+
+        // Larger exports would have to be held in memory for the upload, the gangway streams them from the segments instead
+        if recording.complete && recording.size <= MAX_CACHED_EXPORT_SIZE {
+            info!("Caching recording export");
+            cache_export(storage, self.options.id, &fs::read(&video).await?).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn upload_subtitles(&self) {
+        // Sessions without recordings have nothing to attach the subtitles to
+        if self.video_byte_count_total.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+
+        if let (Some(storage), Some(profile)) =
+            (&self.options.storage.backend, self.recording_profile)
+        {
+            info!("Attaching command subtitles to recording");
+            let bandwidth = self.options.recording.max_bitrate(profile);
+            if let Err(error) = self
+                .subtitles
+                .upload(self.options.id, storage, bandwidth)
+                .await
+            {
+                error!(?error, "Failed to attach command subtitles");
+            }
+        }
+    }
+
+    async fn upload_driver_log(&mut self) {
+        if let Some(storage) = &self.options.storage.backend {
+            info!("Uploading driver log");
+            let path = storage_path(self.options.id, "driver.log")
+                .to_string_lossy()
+                .into_owned();
+
+            let result = match self.driver_log.contents() {
+                Ok(contents) => storage.put_object(&path, &contents).await,
+                Err(error) => Err(error.into()),
+            };
+
+            match result {
+                Ok(_) => self.driver_log_uploaded = true,
+                Err(error) => error!(?error, "Failed to upload driver log"),
+            }
+        }
+    }
+
     async fn collect_profiling_data(&mut self) -> HashMap<String, AccumulatedPerformanceMetrics> {
         let mut profiling_data = HashMap::new();
 
@@ -345,11 +722,21 @@ impl Node {
                 let recording_bytes = self.video_byte_count_total.load(Ordering::Relaxed);
                 let profiling_data = self.collect_profiling_data().await;
 
+                let screenshots = self.screenshots.captured().await;
+                let recording_pauses = self.recording_control.pauses().await;
+
                 let notification = SessionTerminatedNotification {
                     id: self.options.id,
                     reason,
                     recording_bytes,
                     profiling_data,
+                    screenshots,
+                    recording_pauses,
+                    recording_profile: self.recording_profile,
+                    recording_format: self.recording_format,
+                    lost_segments: self.lost_segments.clone(),
+                    command_journal: self.journal_uploaded,
+                    driver_log: self.driver_log_uploaded,
                 };
 
                 publisher.publish(&notification).await
@@ -377,22 +764,56 @@ impl Module for Node {
     }
 
     async fn run(&mut self, scheduler: &JobScheduler) -> Result<Option<Heart>, BoxedError> {
-        let capabilities: Capabilities =
-            serde_json::from_str(&self.options.webdriver.capabilities)?;
+        let capabilities = self.requested_capabilities()?;
         let (heart, stone) = self.build_heart(&capabilities).await;
 
         let advertise_job = self.build_advertise_job();
         let (metadata_publisher_job, metadata_tx) = self.build_metadata_publisher_job();
-        let proxy_job = self.build_proxy_job(stone, metadata_tx)?;
+        let deadline = self
+            .max_duration(&capabilities)
+            .map(|duration| Instant::now() + duration);
+
+        let crash_detection_job = self.build_crash_detection_job(stone.clone())?;
+
+        if let Some(deadline) = deadline {
+            let max_duration_job = MaxDurationJob::new(deadline, stone.clone());
+            schedule!(scheduler, { max_duration_job });
+        }
+
+        let proxy_job = self.build_proxy_job(stone, metadata_tx, deadline)?;
+
+        let webgrid_options = capabilities.webgrid_options.unwrap_or_default();
+        if let Some((storage, context, format)) = self.prepare_recording(&webgrid_options)? {
+            if let Some(spool) = context.spool.clone() {
+                let upload_spool_job = UploadSpoolJob::new(spool, storage.clone(), self.options.id);
+                schedule!(scheduler, { upload_spool_job });
+            }
+
+            match format {
+                RecordingFormat::Video => {
+                    let recording_job =
+                        self.build_recording_job(&webgrid_options, storage, context);
+                    schedule!(scheduler, { recording_job });
+                }
+                RecordingFormat::TimeLapse => {
+                    let time_lapse_job = self.build_time_lapse_job(storage, context);
+                    schedule!(scheduler, { time_lapse_job });
+                }
+            }
+        }
 
-        if let Some(recording_job) = self.build_recording_job(capabilities) {
-            schedule!(scheduler, { recording_job });
+        if let Some((live_view_job, live_view_advertise_job)) = self.build_live_view_jobs() {
+            schedule!(scheduler, {
+                live_view_job,
+                live_view_advertise_job
+            });
         }
 
         schedule_and_wait!(scheduler, self.options.bind_timeout, {
             proxy_job,
             advertise_job,
-            metadata_publisher_job
+            metadata_publisher_job,
+            crash_detection_job
         });
 
         // TODO The ready-states are not yet indicative of the actual ready state as e.g. the HTTP server future is only polled after the ready signal is sent.
@@ -402,9 +823,83 @@ impl Module for Node {
     }
 
     async fn post_shutdown(&mut self, termination_reason: ModuleTerminationReason) {
+        let abnormal_termination = !matches!(
+            termination_reason,
+            ModuleTerminationReason::ExitedNormally
+                | ModuleTerminationReason::HeartDied(DeathReason::Killed(_))
+        );
+
+        // Everything reported by the termination notification has to be done before its deadline.
+        // Otherwise, the harness aborts the shutdown and the session is never marked as terminated.
+        let notification_deadline = Instant::now() + SHUTDOWN_TIMEOUT - NOTIFICATION_RESERVE;
+        let recording_deadline = notification_deadline - RECORDING_RESERVE;
+
         // These are only best-effort cleanup attempts. They may very well fail for one reason or another.
-        self.shutdown_driver().await;
+        let cleanup = async {
+            self.screenshots.capture_final().await;
+            self.shutdown_driver().await;
+            self.upload_journal().await;
+            self.upload_driver_log().await;
+            self.finalize_recording(abnormal_termination, recording_deadline)
+                .await;
+            self.upload_subtitles().await;
+        };
+
+        if timeout_at(notification_deadline.into(), cleanup)
+            .await
+            .is_err()
+        {
+            warn!("Shutdown cleanup exceeded its deadline");
+        }
+
         self.send_termination_notification(termination_reason.into())
             .await;
+
+        // Previews and the export are a convenience, so they are created with whatever time is left
+        let shutdown_deadline = notification_deadline + NOTIFICATION_RESERVE - SHUTDOWN_MARGIN;
+        match timeout_at(shutdown_deadline.into(), self.process_recording()).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(error)) => warn!(?error, "Failed to process recording"),
+            Err(_) => warn!("Processing the recording exceeded the shutdown timeout"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    const REQUEST: &str = r#"{
+        "alwaysMatch": { "webgrid:options": { "maxDuration": 60 } },
+        "firstMatch": [{ "browserName": "chrome" }, { "browserName": "firefox" }]
+    }"#;
+
+    #[test]
+    fn parse_capabilities_request() {
+        let capabilities = requested_capabilities(REQUEST).unwrap();
+
+        assert_eq!(capabilities.browser_name.as_deref(), Some("chrome"));
+        assert_eq!(
+            capabilities.webgrid_options.and_then(|w| w.max_duration),
+            Some(60)
+        );
+    }
+
+    #[test]
+    fn limit_requested_max_duration() {
+        let capabilities = requested_capabilities(REQUEST).unwrap();
+
+        assert_eq!(
+            max_duration(&capabilities, None),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            max_duration(&capabilities, Some(Duration::from_secs(30))),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            max_duration(&Capabilities::empty(), Some(Duration::from_secs(30))),
+            Some(Duration::from_secs(30))
+        );
     }
 }
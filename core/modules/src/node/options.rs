@@ -1,4 +1,5 @@
 use crate::options::{RedisOptions, StorageOptions};
+use domain::recording::{RecordingFormat, RecordingProfile};
 use domain::webdriver::{ScreenResolution, WebDriverVariant};
 use library::helpers::parse_seconds;
 use std::path::PathBuf;
@@ -30,6 +31,11 @@ pub struct Options {
     #[structopt(long, env, default_value = "120", parse(try_from_str = parse_seconds))]
     pub idle_timeout: Duration,
 
+    /// Maximum duration (in seconds) a session may run for, regardless of client activity.
+    /// Clients may request a shorter duration through the `maxDuration` WebGrid option.
+    #[structopt(long, env, parse(try_from_str = parse_seconds))]
+    pub max_duration: Option<Duration>,
+
     /// Options relating to the WebDriver
     #[structopt(flatten)]
     pub webdriver: WebDriverOptions,
@@ -38,6 +44,10 @@ pub struct Options {
     #[structopt(flatten)]
     pub recording: ScreenRecordingOptions,
 
+    /// Options about the live view
+    #[structopt(flatten)]
+    pub live_view: LiveViewOptions,
+
     /// Hostname or IP address where this instance can be reached by proxy services
     #[structopt(short, long, env)]
     pub host: String,
@@ -51,6 +61,15 @@ pub struct Options {
     #[structopt(flatten)]
     pub storage: StorageOptions,
 
+    /// Maximum number of screenshots captured when the driver responds with an error, 0 disables them.
+    /// Regardless of this value, a final screenshot is taken before the session terminates.
+    #[structopt(long, env, default_value = "0")]
+    pub error_screenshots: usize,
+
+    /// Maximum size of files uploaded by clients in bytes. Applies to both the request and the extracted archive.
+    #[structopt(long, env, default_value = "268435456")]
+    pub max_upload_size: usize,
+
     /// Enables CPU, memory, and disk usage profiling of all involved processes
     #[structopt(long, env)]
     pub profile: bool,
@@ -71,7 +90,8 @@ pub struct WebDriverOptions {
     #[structopt(long, env = "DRIVER_VARIANT")]
     pub variant: WebDriverVariant,
 
-    /// Screen resolution for new sessions
+    /// Screen resolution for new sessions, also used for the virtual display and screen recording.
+    /// Orchestrators overwrite it for sessions that request a specific resolution.
     #[structopt(long, env, default_value = "1920x1080")]
     pub resolution: ScreenResolution,
 
@@ -84,12 +104,43 @@ pub struct WebDriverOptions {
     pub capabilities: String,
 }
 
+/// Live view related options
+#[derive(Debug, StructOpt)]
+pub struct LiveViewOptions {
+    /// Serves the screen of the session as VNC-over-WebSocket so that it can be watched while it is running.
+    /// Clients need the per-session token returned by the `/session/<id>/webgrid/live` extension command to connect.
+    #[structopt(name = "live_view", long, env)]
+    pub enabled: bool,
+
+    /// Additionally allows live view clients to control the browser with their mouse and keyboard
+    #[structopt(name = "live_view_interactive", long, env)]
+    pub interactive: bool,
+}
+
 // Screen recording related options
 #[derive(Debug, StructOpt)]
 pub struct ScreenRecordingOptions {
-    /// Constant Rate Factor
+    /// Kind of recording for sessions which do not request a specific one (video or timelapse)
+    ///
+    /// Time-lapse recordings consist of screenshots taken at a fixed interval and use significantly less CPU than videos.
+    #[structopt(name = "recording_format", long, env, default_value = "video")]
+    pub format: RecordingFormat,
+
+    /// Interval (in seconds) between two screenshots of a time-lapse recording
+    #[structopt(long, env, default_value = "5", parse(try_from_str = parse_seconds))]
+    pub timelapse_interval: Duration,
+
+    /// Codec profile used for sessions which do not request a specific one (h264, vp9, or av1)
+    ///
+    /// H.264 is cheap to encode and plays everywhere, VP9 yields smaller files at a moderate CPU cost,
+    /// and AV1 offers archive-grade compression while being significantly more expensive to encode.
+    #[structopt(name = "recording_profile", long, env, default_value = "h264")]
+    pub profile: RecordingProfile,
+
+    /// Constant Rate Factor, defaults to 46 for H.264, 50 for VP9, and 52 for AV1
     ///
-    /// The range of the CRF scale is 0–51, where 0 is lossless, 23 is the default, and 51 is worst quality possible.
+    /// The value applies to all profiles even though their scales differ: 0–51 for H.264, 0–63 for VP9 and AV1.
+    /// For H.264, 0 is lossless, 23 is the default, and 51 is worst quality possible.
     /// A lower value generally leads to higher quality, and a subjectively sane range is 17–28.
     /// Consider 17 or 18 to be visually lossless or nearly so; it should look the same or nearly the same as the input but it isn't technically lossless.
     /// The range is exponential, so increasing the CRF value +6 results in roughly half the bitrate / file size, while -6 leads to roughly twice the bitrate.
@@ -98,10 +149,10 @@ pub struct ScreenRecordingOptions {
     /// For more details, consult the ffmpeg H.264 documentation (section "Constant Rate Factor"):
     ///
     /// https://trac.ffmpeg.org/wiki/Encode/H.264
-    #[structopt(long, env, default_value = "46")]
-    crf: u8,
+    #[structopt(long, env)]
+    crf: Option<u8>,
 
-    /// Upper bitrate bound in bytes
+    /// Upper bitrate bound in bits per second, defaults to 450000 for H.264, 350000 for VP9, and 250000 for AV1
     ///
     /// The average bitrate is determined by the constant rate factor and content
     /// however if the bitrate were to exceed this specified maximum bitrate limit, the codec will increase the CRF temporarily.
@@ -109,15 +160,17 @@ pub struct ScreenRecordingOptions {
     /// For more details, consult the ffmpeg H.264 documentation (section "Constrained encoding"):
     ///
     /// https://trac.ffmpeg.org/wiki/Encode/H.264
-    #[structopt(long, env, default_value = "450000")]
-    max_bitrate: usize,
+    #[structopt(long, env)]
+    max_bitrate: Option<usize>,
 
     /// ffmpeg input parameter specification
+    ///
+    /// The input is preceded by a `-video_size` parameter matching the screen resolution of the session.
     #[structopt(
         name = "recording_input",
         long,
         env,
-        default_value = "-rtbufsize 1500M -probesize 100M -video_size 1920x1080 -f x11grab -draw_mouse 0 -i :42"
+        default_value = "-rtbufsize 1500M -probesize 100M -f x11grab -draw_mouse 0 -i :42"
     )]
     pub input: String,
 
@@ -129,6 +182,14 @@ pub struct ScreenRecordingOptions {
     #[structopt(long, env, default_value = "5")]
     pub framerate: usize,
 
+    /// Maximum duration (in seconds) spent on uploading spooled recording objects when the session terminates
+    ///
+    /// Objects which could not be uploaded while the session was running are kept on disk and retried in the background.
+    /// Whatever is left after this duration is discarded and reported as lost. The duration is capped
+    /// so that the termination notification can still be published within the shutdown timeout.
+    #[structopt(long, env, default_value = "30", parse(try_from_str = parse_seconds))]
+    pub spool_flush_timeout: Duration,
+
     /// HLS segment duration in seconds
     ///
     /// For each segment of the given duration, a separate video file will be created. Reducing the duration allows for "faster"
@@ -138,26 +199,75 @@ pub struct ScreenRecordingOptions {
 }
 
 impl ScreenRecordingOptions {
-    pub fn generate_arguments(&self) -> String {
+    /// Upper bitrate bound in bits per second for the given profile
+    pub fn max_bitrate(&self, profile: RecordingProfile) -> usize {
+        self.max_bitrate
+            .unwrap_or_else(|| profile.default_max_bitrate())
+    }
+
+    fn codec_arguments(&self, profile: RecordingProfile) -> String {
+        let crf = self.crf.unwrap_or_else(|| profile.default_crf());
+        let maxrate = self.max_bitrate(profile);
+        let bufsize = maxrate * 2;
+        let keyint = self.framerate * 2;
+
+        match profile {
+            RecordingProfile::H264 => format!(
+                "-c:v libx264 -preset ultrafast -crf {crf} -maxrate {maxrate} -bufsize {bufsize} -pix_fmt yuv420p -tune stillimage -x264-params keyint={keyint}:scenecut=0:keyint_min={keyint} -g {framerate}",
+                crf = crf,
+                maxrate = maxrate,
+                bufsize = bufsize,
+                keyint = keyint,
+                framerate = self.framerate,
+            ),
+            RecordingProfile::Vp9 => format!(
+                "-c:v libvpx-vp9 -deadline realtime -cpu-used 8 -row-mt 1 -crf {crf} -b:v {maxrate} -maxrate {maxrate} -bufsize {bufsize} -pix_fmt yuv420p -g {keyint} -keyint_min {keyint}",
+                crf = crf,
+                maxrate = maxrate,
+                bufsize = bufsize,
+                keyint = keyint,
+            ),
+            RecordingProfile::Av1 => format!(
+                "-c:v libaom-av1 -usage realtime -cpu-used 8 -row-mt 1 -crf {crf} -b:v {maxrate} -maxrate {maxrate} -bufsize {bufsize} -pix_fmt yuv420p -g {keyint} -keyint_min {keyint}",
+                crf = crf,
+                maxrate = maxrate,
+                bufsize = bufsize,
+                keyint = keyint,
+            ),
+        }
+    }
+
+    /// ffmpeg arguments which grab a single JPEG frame of the screen and write it to stdout
+    pub fn generate_frame_arguments(&self, resolution: ScreenResolution) -> String {
+        format!(
+            "-video_size {resolution} {input} -frames:v 1 -vf scale=w=1280:h=720:force_original_aspect_ratio=decrease -q:v 5 -f image2pipe -c:v mjpeg -",
+            resolution = resolution,
+            input = self.input,
+        )
+    }
+
+    pub fn generate_arguments(
+        &self,
+        resolution: ScreenResolution,
+        profile: RecordingProfile,
+    ) -> String {
         let output = format!(
             "-method PUT http://127.0.0.1:{}/screen.m3u8",
             crate::constants::PORT_STORAGE
         );
 
         format!(r#"
-        -y -framerate {framerate} {input} -vf scale=w=1280:h=720:force_original_aspect_ratio=decrease
-        -c:v libx264 -preset ultrafast -crf {crf} -maxrate {maxrate} -bufsize {bufsize} -pix_fmt yuv420p -tune stillimage -x264-params keyint={keyint}:scenecut=0:keyint_min={keyint} -g {framerate}
+        -y -framerate {framerate} -video_size {resolution} {input} -vf scale=w=1280:h=720:force_original_aspect_ratio=decrease
+        {codec}
         -f hls -hls_playlist_type event -hls_time {segment_duration}
         -hls_segment_type fmp4 -hls_flags program_date_time
         {output}
                 "#,
                     input = self.input,
-                    keyint = self.framerate * 2,
+                    resolution = resolution,
                     framerate = self.framerate,
+                    codec = self.codec_arguments(profile),
                     output = output,
-                    crf = self.crf,
-                    maxrate = self.max_bitrate,
-                    bufsize = self.max_bitrate * 2,
                     segment_duration = self.segment_duration
                 ).trim().replace('\n', " ")
     }
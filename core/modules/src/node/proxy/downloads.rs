@@ -0,0 +1,253 @@
+use async_trait::async_trait;
+use async_zip::error::ZipError;
+use async_zip::write::{EntryOptions, ZipFileWriter};
+use async_zip::Compression;
+use domain::webdriver::{new_error_response, WebdriverErrorCode};
+use futures::Future;
+use hyper::header::CONTENT_TYPE;
+use hyper::{
+    http::{request::Parts, Method, Response},
+    Body,
+};
+use library::communication::BlackboxError;
+use library::http::Responder;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::io;
+use std::net::IpAddr;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+use tokio::fs;
+use tracing::debug;
+
+/// Suffixes of files which are still being written by the browser
+const PARTIAL_DOWNLOAD_SUFFIXES: [&str; 2] = [".crdownload", ".part"];
+
+#[derive(Deserialize)]
+struct DownloadRequest {
+    name: String,
+}
+
+#[derive(Debug, Error)]
+enum DownloadsInterceptorError {
+    #[error("downloads are not enabled, request them with the se:downloadsEnabled capability")]
+    NotEnabled,
+    #[error("incomplete request body")]
+    BodyError(#[from] hyper::Error),
+    #[error("invalid request format")]
+    RequestFormatInvalid(#[from] serde_json::Error),
+    #[error("invalid file name {0:?}")]
+    InvalidName(String),
+    #[error("no downloaded file named {0:?}")]
+    NoSuchFile(String),
+    #[error("failed to archive file")]
+    ArchiveFailure(ZipError),
+    #[error("failed to access download directory")]
+    Io(#[from] io::Error),
+}
+
+use DownloadsInterceptorError::*;
+
+impl DownloadsInterceptorError {
+    fn code(&self) -> WebdriverErrorCode {
+        match self {
+            NotEnabled => WebdriverErrorCode::UnsupportedOperation,
+            ArchiveFailure(_) | Io(_) => WebdriverErrorCode::UnknownError,
+            _ => WebdriverErrorCode::InvalidArgument,
+        }
+    }
+}
+
+/// Verifies that the name refers to a file directly within the download directory
+fn validate_name(name: &str) -> Result<(), DownloadsInterceptorError> {
+    let mut components = Path::new(name).components();
+
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => Err(InvalidName(name.to_owned())),
+    }
+}
+
+/// Implements the Selenium endpoints at `/session/<id>/se/files` for files downloaded by the browser
+///
+/// - `GET` lists the names of all downloaded files
+/// - `POST` returns a single file as a base64 encoded ZIP archive
+/// - `DELETE` removes all downloaded files
+pub struct DownloadsInterceptor {
+    directory: Option<PathBuf>,
+    session_id: String,
+}
+
+impl DownloadsInterceptor {
+    pub fn new(directory: Option<PathBuf>, session_id: String) -> Self {
+        Self {
+            directory,
+            session_id,
+        }
+    }
+
+    async fn list(directory: &Path) -> Result<Vec<String>, DownloadsInterceptorError> {
+        let mut names = Vec::new();
+        let mut entries = fs::read_dir(directory).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            if !entry.file_type().await?.is_file() {
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !PARTIAL_DOWNLOAD_SUFFIXES
+                .iter()
+                .any(|suffix| name.ends_with(suffix))
+            {
+                names.push(name);
+            }
+        }
+
+        names.sort();
+        Ok(names)
+    }
+
+    async fn fetch(directory: &Path, body: Body) -> Result<Value, DownloadsInterceptorError> {
+        let bytes = hyper::body::to_bytes(body).await?;
+        let request: DownloadRequest = serde_json::from_slice(&bytes)?;
+        validate_name(&request.name)?;
+
+        let path = directory.join(&request.name);
+        let content = match fs::read(&path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(NoSuchFile(request.name)),
+            Err(e) => return Err(Io(e)),
+        };
+
+        let mut archive = Vec::new();
+        let mut writer = ZipFileWriter::new(&mut archive);
+        let options = EntryOptions::new(request.name.clone(), Compression::Deflate);
+        writer
+            .write_entry_whole(options, &content)
+            .await
+            .map_err(ArchiveFailure)?;
+        writer.close().await.map_err(ArchiveFailure)?;
+
+        debug!(
+            name = ?request.name,
+            size = content.len(),
+            "Sending downloaded file to client"
+        );
+
+        Ok(json!({
+            "filename": request.name,
+            "contents": base64::encode(&archive),
+        }))
+    }
+
+    async fn clear(directory: &Path) -> Result<(), DownloadsInterceptorError> {
+        let mut entries = fs::read_dir(directory).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                fs::remove_dir_all(entry.path()).await?;
+            } else {
+                fs::remove_file(entry.path()).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle(
+        &self,
+        method: &Method,
+        body: Body,
+    ) -> Result<Value, DownloadsInterceptorError> {
+        let directory = self.directory.as_deref().ok_or(NotEnabled)?;
+
+        match *method {
+            Method::GET => Ok(json!({ "names": Self::list(directory).await? })),
+            Method::POST => Self::fetch(directory, body).await,
+            _ => Self::clear(directory).await.map(|_| Value::Null),
+        }
+    }
+}
+
+#[async_trait]
+impl Responder for DownloadsInterceptor {
+    #[inline]
+    async fn respond<F, Fut>(
+        &self,
+        parts: Parts,
+        body: Body,
+        client_ip: IpAddr,
+        next: F,
+    ) -> Result<Response<Body>, Infallible>
+    where
+        Fut: Future<Output = Result<Response<Body>, Infallible>> + Send,
+        F: FnOnce(Parts, Body, IpAddr) -> Fut + Send,
+    {
+        // Verify the method is supported
+        if !matches!(parts.method, Method::GET | Method::POST | Method::DELETE) {
+            return next(parts, body, client_ip).await;
+        }
+
+        // Verify the path matches the files endpoint
+        let path = format!("/session/{}/se/files", self.session_id);
+        if !parts.uri.path().eq_ignore_ascii_case(&path) {
+            return next(parts, body, client_ip).await;
+        }
+
+        match self.handle(&parts.method, body).await {
+            Ok(value) => {
+                let response = serde_json::to_string(&json!({ "value": value }))
+                    .unwrap_or_else(|_| "{}".into());
+
+                Ok(Response::builder()
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(response.into())
+                    .unwrap())
+            }
+            Err(e) => Ok(new_error_response(e.code(), BlackboxError::new(e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn accept_plain_file_names() {
+        assert!(validate_name("report.pdf").is_ok());
+        assert!(validate_name("export 2021-06-01.csv").is_ok());
+    }
+
+    #[test]
+    fn reject_file_names_outside_of_directory() {
+        assert!(validate_name("").is_err());
+        assert!(validate_name("..").is_err());
+        assert!(validate_name("../secret").is_err());
+        assert!(validate_name("nested/report.pdf").is_err());
+        assert!(validate_name("/etc/passwd").is_err());
+    }
+
+    #[tokio::test]
+    async fn list_and_clear_completed_downloads() {
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(directory.path().join("b.csv"), b"b")
+            .await
+            .unwrap();
+        fs::write(directory.path().join("a.pdf"), b"a")
+            .await
+            .unwrap();
+        fs::write(directory.path().join("c.zip.crdownload"), b"c")
+            .await
+            .unwrap();
+
+        let names = DownloadsInterceptor::list(directory.path()).await.unwrap();
+        assert_eq!(names, vec!["a.pdf".to_owned(), "b.csv".to_owned()]);
+
+        DownloadsInterceptor::clear(directory.path()).await.unwrap();
+        let mut entries = fs::read_dir(directory.path()).await.unwrap();
+        assert!(entries.next_entry().await.unwrap().is_none());
+    }
+}
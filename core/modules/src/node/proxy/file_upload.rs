@@ -1,30 +1,46 @@
 use async_trait::async_trait;
 use async_zip::error::ZipError;
-use async_zip::read::mem::ZipFileReader;
-use futures::Future;
+use async_zip::read::fs::ZipFileReader;
+use domain::webdriver::{new_error_response, WebdriverErrorCode};
+use futures::{Future, TryStreamExt};
 use harness::HeartStone;
 use hyper::header::CONTENT_TYPE;
 use hyper::{
     http::{request::Parts, Method, Response},
     Body,
 };
+use library::communication::BlackboxError;
 use library::http::Responder;
-use serde::Deserialize;
 use serde_json::json;
 use std::convert::Infallible;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read};
 use std::net::IpAddr;
+use std::path::{Component, Path, PathBuf};
 use std::sync::Arc;
 use tempfile::{tempdir, TempDir};
 use thiserror::Error;
-use tokio::fs::File;
-use tokio::io::copy;
+use tokio::fs::{self, File};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::Mutex;
 use tracing::debug;
 
-#[derive(Deserialize)]
-struct FileUploadRequest {
-    file: String,
-}
+/// Name of the file the request body is buffered in
+const REQUEST_FILENAME: &str = "request.json";
+
+/// Name of the decoded archive
+const ARCHIVE_FILENAME: &str = "archive.zip";
+
+/// Directory within the upload directory to which the archive is extracted
+const EXTRACTION_DIRECTORY: &str = "files";
+
+/// Upper bound for the number of entries in an archive
+const MAX_ENTRIES: usize = 1000;
+
+/// Key of the request object which contains the base64 encoded archive
+const FILE_KEY: &[u8] = b"file";
+
+/// Number of bytes of each object key that are retained while searching for the archive
+const MAX_KEY_LENGTH: usize = 64;
 
 #[derive(Debug, Error)]
 enum FileUploadInterceptorError {
@@ -32,54 +48,393 @@ enum FileUploadInterceptorError {
     InvalidArchive(ZipError),
     #[error("incomplete request body")]
     UploadError(#[from] hyper::Error),
-    #[error("invalid request format")]
-    RequestFormatInvalid(#[from] serde_json::Error),
+    #[error("invalid request format: {0}")]
+    RequestFormatInvalid(&'static str),
     #[error("invalid base64 encoded file string")]
-    FileStringInvalid(#[from] base64::DecodeError),
+    FileStringInvalid(#[source] io::Error),
+    #[error("upload exceeds the limit of {0} bytes")]
+    TooLarge(usize),
+    #[error("archive contains more than {0} entries")]
+    TooManyEntries(usize),
+    #[error("archive does not contain any files")]
+    EmptyArchive,
+    #[error("archive entry {0:?} points outside of the upload directory")]
+    UnsafePath(String),
+    #[error("failed to write upload to disk")]
+    Io(#[from] io::Error),
+}
+
+use FileUploadInterceptorError::*;
+
+impl FileUploadInterceptorError {
+    fn code(&self) -> WebdriverErrorCode {
+        match self {
+            Io(_) => WebdriverErrorCode::UnknownError,
+            _ => WebdriverErrorCode::InvalidArgument,
+        }
+    }
+}
+
+/// Resolves the name of an archive entry relative to the given directory, rejecting anything that might escape it
+fn entry_path(directory: &Path, name: &str) -> Result<PathBuf, FileUploadInterceptorError> {
+    let relative = Path::new(name);
+    let mut path = directory.to_path_buf();
+
+    for component in relative.components() {
+        match component {
+            Component::Normal(segment) => path.push(segment),
+            Component::CurDir => {}
+            _ => return Err(UnsafePath(name.to_owned())),
+        }
+    }
+
+    if path == directory {
+        return Err(UnsafePath(name.to_owned()));
+    }
+
+    Ok(path)
+}
+
+/// Returns the next byte of the reader without consuming it
+fn peek_byte(reader: &mut impl BufRead) -> io::Result<Option<u8>> {
+    Ok(reader.fill_buf()?.first().copied())
+}
+
+fn next_byte(reader: &mut impl BufRead) -> Result<u8, FileUploadInterceptorError> {
+    let byte = peek_byte(reader)?.ok_or(RequestFormatInvalid("unexpected end of request"))?;
+    reader.consume(1);
+    Ok(byte)
+}
+
+fn skip_whitespace(reader: &mut impl BufRead) -> Result<(), FileUploadInterceptorError> {
+    while let Some(byte) = peek_byte(reader)? {
+        if !byte.is_ascii_whitespace() {
+            break;
+        }
+
+        reader.consume(1);
+    }
+
+    Ok(())
+}
+
+fn expect_byte(
+    reader: &mut impl BufRead,
+    expected: u8,
+    error: &'static str,
+) -> Result<(), FileUploadInterceptorError> {
+    skip_whitespace(reader)?;
+
+    if next_byte(reader)? == expected {
+        Ok(())
+    } else {
+        Err(RequestFormatInvalid(error))
+    }
+}
+
+/// Consumes the remainder of a string whose opening quote has been read, retaining at most `limit` bytes of it
+fn read_string(
+    reader: &mut impl BufRead,
+    limit: usize,
+) -> Result<Vec<u8>, FileUploadInterceptorError> {
+    let mut content = Vec::new();
+
+    loop {
+        let byte = match next_byte(reader)? {
+            b'"' => return Ok(content),
+            b'\\' => {
+                // Escape sequences are retained verbatim as they only matter for comparisons
+                let escaped = next_byte(reader)?;
+                if content.len() < limit {
+                    content.push(b'\\');
+                }
+                escaped
+            }
+            byte => byte,
+        };
+
+        if content.len() < limit {
+            content.push(byte);
+        }
+    }
+}
+
+/// Consumes a value of any type, including nested objects and arrays
+fn skip_value(reader: &mut impl BufRead) -> Result<(), FileUploadInterceptorError> {
+    skip_whitespace(reader)?;
+    let mut depth = 0;
+
+    loop {
+        let byte = peek_byte(reader)?.ok_or(RequestFormatInvalid("unexpected end of request"))?;
+
+        // Delimiters following a top-level scalar belong to the enclosing object
+        if depth == 0 && matches!(byte, b',' | b'}' | b']') {
+            return Ok(());
+        }
+
+        reader.consume(1);
+
+        match byte {
+            b'"' => {
+                read_string(reader, 0)?;
+            }
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => depth -= 1,
+            _ => {}
+        }
+
+        if depth == 0 && matches!(byte, b'"' | b'}' | b']') {
+            return Ok(());
+        }
+    }
+}
+
+/// Advances the reader to the first byte of the `file` string in the request object
+///
+/// The request is scanned instead of deserialized so that the potentially huge archive is never held in memory.
+fn seek_file_value(reader: &mut impl BufRead) -> Result<(), FileUploadInterceptorError> {
+    expect_byte(reader, b'{', "request is not an object")?;
+    skip_whitespace(reader)?;
+
+    if peek_byte(reader)? == Some(b'}') {
+        return Err(RequestFormatInvalid("missing file"));
+    }
+
+    loop {
+        expect_byte(reader, b'"', "expected an object key")?;
+        let key = read_string(reader, MAX_KEY_LENGTH)?;
+        expect_byte(reader, b':', "expected a colon after the object key")?;
+
+        if key == FILE_KEY {
+            return expect_byte(reader, b'"', "file is not a string");
+        }
+
+        skip_value(reader)?;
+        skip_whitespace(reader)?;
+
+        match next_byte(reader)? {
+            b',' => {}
+            b'}' => return Err(RequestFormatInvalid("missing file")),
+            _ => return Err(RequestFormatInvalid("expected a comma between members")),
+        }
+    }
+}
+
+/// Yields the content of a JSON string whose opening quote has already been consumed
+///
+/// Base64 only contains characters that need no escaping, except for the solidus which some encoders escape anyway.
+/// All other escape sequences are rejected.
+struct JsonStringReader<R> {
+    inner: R,
+    done: bool,
 }
 
+impl<R> JsonStringReader<R> {
+    fn new(inner: R) -> Self {
+        Self { inner, done: false }
+    }
+}
+
+impl<R: BufRead> Read for JsonStringReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let available = self.inner.fill_buf()?;
+        let first = *available
+            .first()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "unterminated file string"))?;
+
+        match first {
+            b'"' => {
+                self.inner.consume(1);
+                self.done = true;
+                Ok(0)
+            }
+            b'\\' => {
+                self.inner.consume(1);
+
+                let mut escaped = [0];
+                self.inner.read_exact(&mut escaped)?;
+
+                if escaped[0] == b'/' {
+                    buf[0] = b'/';
+                    Ok(1)
+                } else {
+                    Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "unsupported escape sequence in file string",
+                    ))
+                }
+            }
+            _ => {
+                let count = available
+                    .iter()
+                    .take(buf.len())
+                    .take_while(|byte| !matches!(byte, b'"' | b'\\'))
+                    .count();
+
+                buf[..count].copy_from_slice(&available[..count]);
+                self.inner.consume(count);
+                Ok(count)
+            }
+        }
+    }
+}
+
+/// Handles uploads of ZIP archives to `/session/<id>/se/file`, as used by e.g. file inputs
+///
+/// All entries are extracted into a directory which lives as long as the session. Clients receive the path of the
+/// uploaded file or, if the archive contains multiple files, the path of the directory they have been extracted to.
 pub struct FileUploadInterceptor {
     heart_stone: Arc<Mutex<HeartStone>>,
     session_id: String,
+    size_limit: usize,
     file_handles: Arc<Mutex<Vec<TempDir>>>,
 }
 
 impl FileUploadInterceptor {
-    pub fn new(heart_stone: HeartStone, session_id: String) -> Self {
+    pub fn new(heart_stone: HeartStone, session_id: String, size_limit: usize) -> Self {
         Self {
             heart_stone: Arc::new(Mutex::new(heart_stone)),
             session_id,
+            size_limit,
             file_handles: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
-    async fn handle_body(&self, body: Body) -> Result<String, FileUploadInterceptorError> {
-        let request_bytes = hyper::body::to_bytes(body).await?;
-        let request: FileUploadRequest = serde_json::from_slice(&request_bytes)?;
-        let bytes = base64::decode(&request.file)?;
+    /// Writes the request body to disk without buffering it in memory
+    async fn receive(&self, mut body: Body, path: &Path) -> Result<(), FileUploadInterceptorError> {
+        let mut file = File::create(path).await?;
+        let mut size = 0;
 
-        // Get a handle on the first file in the ZIP
-        let mut zip_reader = ZipFileReader::new(&bytes)
-            .await
-            .map_err(FileUploadInterceptorError::InvalidArchive)?;
-        let mut file_reader = zip_reader
-            .entry_reader(0)
+        while let Some(chunk) = body.try_next().await? {
+            size += chunk.len();
+
+            if size > self.size_limit {
+                return Err(TooLarge(self.size_limit));
+            }
+
+            file.write_all(&chunk).await?;
+        }
+
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Decodes the base64 encoded archive contained in the request into a separate file, one chunk at a time
+    async fn decode(request: PathBuf, archive: PathBuf) -> Result<(), FileUploadInterceptorError> {
+        tokio::task::spawn_blocking(move || {
+            let mut reader = BufReader::new(std::fs::File::open(request)?);
+            seek_file_value(&mut reader)?;
+
+            let mut encoded = JsonStringReader::new(reader);
+            let mut decoder = base64::read::DecoderReader::new(&mut encoded, base64::STANDARD);
+            let mut file = std::fs::File::create(archive)?;
+
+            match io::copy(&mut decoder, &mut file) {
+                Ok(_) => Ok(()),
+                Err(e) if matches!(e.kind(), ErrorKind::InvalidData | ErrorKind::UnexpectedEof) => {
+                    Err(FileStringInvalid(e))
+                }
+                Err(e) => Err(Io(e)),
+            }
+        })
+        .await
+        .map_err(|e| Io(e.into()))?
+    }
+
+    /// Extracts all entries of the archive and returns the paths of the extracted files
+    async fn extract(
+        &self,
+        archive: &Path,
+        directory: &Path,
+    ) -> Result<Vec<PathBuf>, FileUploadInterceptorError> {
+        let mut reader = ZipFileReader::new(archive.to_string_lossy().into_owned())
             .await
-            .map_err(FileUploadInterceptorError::InvalidArchive)?;
+            .map_err(InvalidArchive)?;
+
+        let entries: Vec<(String, bool)> = reader
+            .entries()
+            .iter()
+            .map(|entry| (entry.name().to_owned(), entry.name().ends_with('/')))
+            .collect();
+
+        if entries.len() > MAX_ENTRIES {
+            return Err(TooManyEntries(MAX_ENTRIES));
+        }
+
+        let mut files = Vec::new();
+        let mut size = 0;
+        let mut buffer = vec![0; 64 * 1024];
+
+        for (index, (name, is_directory)) in entries.into_iter().enumerate() {
+            let path = entry_path(directory, &name)?;
+
+            if is_directory {
+                fs::create_dir_all(&path).await?;
+                continue;
+            }
+
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
 
-        // Create a file on disk
-        let name = file_reader.entry().name();
-        let directory = tempdir().unwrap();
-        let file_path = directory.path().join(name);
-        let mut file_writer = File::create(&file_path).await.unwrap();
+            let mut entry_reader = reader.entry_reader(index).await.map_err(InvalidArchive)?;
+            let mut file = File::create(&path).await?;
 
-        // Write the contents and retain the handle
-        copy(&mut file_reader, &mut file_writer).await.unwrap();
+            // The sizes stated in the archive can not be trusted, so the extracted bytes are counted instead
+            loop {
+                let count = entry_reader.read(&mut buffer).await?;
+                if count == 0 {
+                    break;
+                }
+
+                size += count;
+                if size > self.size_limit {
+                    return Err(TooLarge(self.size_limit));
+                }
+
+                file.write_all(&buffer[..count]).await?;
+            }
+
+            file.flush().await?;
+            files.push(path);
+        }
+
+        Ok(files)
+    }
+
+    async fn handle_body(&self, body: Body) -> Result<String, FileUploadInterceptorError> {
+        let directory = tempdir()?;
+        let request = directory.path().join(REQUEST_FILENAME);
+        let archive = directory.path().join(ARCHIVE_FILENAME);
+        let extraction_directory = directory.path().join(EXTRACTION_DIRECTORY);
+
+        self.receive(body, &request).await?;
+        Self::decode(request.clone(), archive.clone()).await?;
+        fs::remove_file(&request).await?;
+
+        fs::create_dir(&extraction_directory).await?;
+        let files = self.extract(&archive, &extraction_directory).await?;
+        fs::remove_file(&archive).await?;
+
+        let path = match files.as_slice() {
+            [] => return Err(EmptyArchive),
+            [file] => file.clone(),
+            _ => extraction_directory,
+        };
+
+        // Retain the handle so that the files live as long as the session
         self.file_handles.lock().await.push(directory);
 
-        // Send the path to the caller
-        debug!(?file_path, "Received file upload from client");
-        Ok(file_path.display().to_string())
+        debug!(
+            ?path,
+            count = files.len(),
+            "Received file upload from client"
+        );
+        Ok(path.display().to_string())
     }
 }
 
@@ -107,24 +462,81 @@ impl Responder for FileUploadInterceptor {
         let is_file_upload_request = method == Method::POST
             && path.eq_ignore_ascii_case(&format!("/session/{}/se/file", self.session_id));
 
+        if !is_file_upload_request {
+            return next(parts, body, client_ip).await;
+        }
+
         // Handle the file and json-ify the result
-        if is_file_upload_request {
-            let response_value = match self.handle_body(body).await {
-                Ok(path) => json!({ "value": path }),
-                Err(e) => json!({
-                    "status": "error",
-                    "error": e.to_string()
-                }),
-            };
-
-            // Build a json response and send it
-            let response = serde_json::to_string(&response_value).unwrap_or_else(|_| "{}".into());
-            Ok(Response::builder()
-                .header(CONTENT_TYPE, "application/json")
-                .body(response.into())
-                .unwrap())
-        } else {
-            next(parts, body, client_ip).await
+        match self.handle_body(body).await {
+            Ok(path) => {
+                let response = serde_json::to_string(&json!({ "value": path }))
+                    .unwrap_or_else(|_| "{}".into());
+
+                Ok(Response::builder()
+                    .header(CONTENT_TYPE, "application/json")
+                    .body(response.into())
+                    .unwrap())
+            }
+            Err(e) => Ok(new_error_response(e.code(), BlackboxError::new(e))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn resolve_nested_entries() {
+        let directory = Path::new("/tmp/upload");
+
+        assert_eq!(
+            entry_path(directory, "docs/./report.pdf").unwrap(),
+            directory.join("docs/report.pdf")
+        );
+        assert_eq!(
+            entry_path(directory, "docs/").unwrap(),
+            directory.join("docs")
+        );
+    }
+
+    #[test]
+    fn reject_path_traversal() {
+        let directory = Path::new("/tmp/upload");
+
+        assert!(entry_path(directory, "../etc/passwd").is_err());
+        assert!(entry_path(directory, "docs/../../etc/passwd").is_err());
+        assert!(entry_path(directory, "/etc/passwd").is_err());
+        assert!(entry_path(directory, "./").is_err());
+    }
+
+    #[test]
+    fn stream_file_value_from_request() {
+        let request =
+            br#"{ "meta": {"names": ["a\"}", 1]}, "count": 12, "file" : "UEsD\/A==", "x": null }"#;
+        let mut reader = io::Cursor::new(&request[..]);
+
+        seek_file_value(&mut reader).unwrap();
+        let mut content = String::new();
+        JsonStringReader::new(reader)
+            .read_to_string(&mut content)
+            .unwrap();
+
+        assert_eq!(content, "UEsD/A==");
+    }
+
+    #[test]
+    fn reject_requests_without_file() {
+        let requests: [&[u8]; 4] = [
+            br#"{}"#,
+            br#"{"files": "UEsD"}"#,
+            br#"{"file": 42}"#,
+            br#"["UEsD"]"#,
+        ];
+
+        for request in requests {
+            let mut reader = io::Cursor::new(request);
+            assert!(seek_file_value(&mut reader).is_err());
         }
     }
 }
@@ -0,0 +1,144 @@
+use crate::node::journal::{CommandJournal, JournalEntry};
+use crate::node::subtitles::SubtitleTrack;
+use async_trait::async_trait;
+use chrono::Utc;
+use domain::webdriver::{new_error_response, WebdriverErrorCode};
+use futures::Future;
+use hyper::body::{to_bytes, Bytes, HttpBody};
+use hyper::{
+    http::{request::Parts, Response},
+    Body,
+};
+use library::communication::BlackboxError;
+use library::http::Responder;
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::time::Instant;
+use thiserror::Error;
+
+/// Largest body which is buffered for the journal, larger or unsized bodies are streamed through
+const MAX_BUFFERED_BODY: u64 = 1024 * 1024;
+
+/// Placeholder for bodies which have not been buffered
+const OMITTED: &str = "[OMITTED]";
+
+#[derive(Debug, Error)]
+enum JournalResponderError {
+    #[error("incomplete request body")]
+    RequestBody(#[source] hyper::Error),
+    #[error("incomplete response body")]
+    ResponseBody(#[source] hyper::Error),
+}
+
+use JournalResponderError::*;
+
+impl JournalResponderError {
+    fn code(&self) -> WebdriverErrorCode {
+        match self {
+            RequestBody(_) => WebdriverErrorCode::InvalidArgument,
+            ResponseBody(_) => WebdriverErrorCode::UnknownError,
+        }
+    }
+}
+
+/// Buffers the body if its size is known and below [`MAX_BUFFERED_BODY`]
+async fn buffer(body: Body) -> Result<Result<Bytes, Body>, hyper::Error> {
+    match body.size_hint().upper() {
+        Some(size) if size <= MAX_BUFFERED_BODY => to_bytes(body).await.map(Ok),
+        _ => Ok(Err(body)),
+    }
+}
+
+/// Textual representation of a body for the journal
+fn describe(body: &Result<Bytes, Body>) -> String {
+    match body {
+        Ok(bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        Err(_) => OMITTED.to_owned(),
+    }
+}
+
+/// Turns a possibly buffered body back into a forwardable one
+fn restore(body: Result<Bytes, Body>) -> Body {
+    match body {
+        Ok(bytes) => Body::from(bytes),
+        Err(body) => body,
+    }
+}
+
+fn error_response(error: JournalResponderError) -> Response<Body> {
+    new_error_response(error.code(), BlackboxError::new(error))
+}
+
+/// Records every command passed on to the next responder in the [`CommandJournal`] and [`SubtitleTrack`]
+pub struct JournalResponder {
+    journal: CommandJournal,
+    subtitles: SubtitleTrack,
+    session_id: String,
+}
+
+impl JournalResponder {
+    pub fn new(journal: CommandJournal, subtitles: SubtitleTrack, session_id: String) -> Self {
+        Self {
+            journal,
+            subtitles,
+            session_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Responder for JournalResponder {
+    #[inline]
+    async fn respond<F, Fut>(
+        &self,
+        parts: Parts,
+        body: Body,
+        client_ip: IpAddr,
+        next: F,
+    ) -> Result<Response<Body>, Infallible>
+    where
+        Fut: Future<Output = Result<Response<Body>, Infallible>> + Send,
+        F: FnOnce(Parts, Body, IpAddr) -> Fut + Send,
+    {
+        // Only journal commands of this session
+        let session_prefix = format!("/session/{}", self.session_id);
+        let path = match parts.uri.path().strip_prefix(&session_prefix) {
+            Some(remainder) => remainder.to_owned(),
+            None => return next(parts, body, client_ip).await,
+        };
+
+        let method = parts.method.to_string();
+        let started_at = Utc::now();
+        let start = Instant::now();
+
+        // Buffer small bodies so they can be both recorded and forwarded
+        let request = match buffer(body).await {
+            Ok(request) => request,
+            Err(e) => return Ok(error_response(RequestBody(e))),
+        };
+
+        let request_text = describe(&request);
+        let response = next(parts, restore(request), client_ip).await?;
+        let (response_parts, response_body) = response.into_parts();
+
+        let response_body = match buffer(response_body).await {
+            Ok(response_body) => response_body,
+            Err(e) => return Ok(error_response(ResponseBody(e))),
+        };
+
+        let entry = JournalEntry {
+            method,
+            path,
+            started_at,
+            duration: start.elapsed(),
+            status: response_parts.status.as_u16(),
+            request: request_text,
+            response: describe(&response_body),
+        };
+
+        self.subtitles.record(&entry).await;
+        self.journal.record(entry).await;
+
+        Ok(Response::from_parts(response_parts, restore(response_body)))
+    }
+}
@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use futures::Future;
+use hyper::header::CONTENT_TYPE;
+use hyper::{
+    http::{request::Parts, Method, Response},
+    Body,
+};
+use library::http::Responder;
+use serde_json::json;
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::time::Instant;
+
+/// Reports the remaining lifetime of the session at `/session/<id>/webgrid/lifetime`
+pub struct LifetimeExtensionInterceptor {
+    session_id: String,
+    deadline: Option<Instant>,
+}
+
+impl LifetimeExtensionInterceptor {
+    pub fn new(session_id: String, deadline: Option<Instant>) -> Self {
+        Self {
+            session_id,
+            deadline,
+        }
+    }
+}
+
+#[async_trait]
+impl Responder for LifetimeExtensionInterceptor {
+    #[inline]
+    async fn respond<F, Fut>(
+        &self,
+        parts: Parts,
+        body: Body,
+        client_ip: IpAddr,
+        next: F,
+    ) -> Result<Response<Body>, Infallible>
+    where
+        Fut: Future<Output = Result<Response<Body>, Infallible>> + Send,
+        F: FnOnce(Parts, Body, IpAddr) -> Fut + Send,
+    {
+        // Verify the method is GET
+        if parts.method != Method::GET {
+            return next(parts, body, client_ip).await;
+        }
+
+        // Verify the path matches the lifetime extension url
+        if !parts
+            .uri
+            .path()
+            .eq_ignore_ascii_case(&format!("/session/{}/webgrid/lifetime", self.session_id))
+        {
+            return next(parts, body, client_ip).await;
+        }
+
+        // Sessions without a maximum duration report `null`
+        let remaining = self.deadline.map(|deadline| {
+            deadline
+                .saturating_duration_since(Instant::now())
+                .as_secs_f64()
+        });
+
+        let response_value = json!({ "value": { "remaining": remaining } });
+
+        // Build a json response and send it
+        let response = serde_json::to_string(&response_value).unwrap_or_else(|_| "{}".into());
+        Ok(Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(response.into())
+            .unwrap())
+    }
+}
@@ -0,0 +1,79 @@
+use async_trait::async_trait;
+use futures::Future;
+use hyper::header::CONTENT_TYPE;
+use hyper::{
+    http::{request::Parts, Method, Response, StatusCode},
+    Body,
+};
+use library::http::Responder;
+use serde_json::json;
+use std::convert::Infallible;
+use std::net::IpAddr;
+
+/// Hands out the live view token of the session at `/session/<id>/webgrid/live`
+///
+/// Only the client which created the session is able to send extension commands to it, thus the token is only known to it
+/// and whoever it is shared with.
+pub struct LiveViewExtensionInterceptor {
+    token: Option<String>,
+    session_id: String,
+}
+
+impl LiveViewExtensionInterceptor {
+    pub fn new(token: Option<String>, session_id: String) -> Self {
+        Self { token, session_id }
+    }
+}
+
+#[async_trait]
+impl Responder for LiveViewExtensionInterceptor {
+    #[inline]
+    async fn respond<F, Fut>(
+        &self,
+        parts: Parts,
+        body: Body,
+        client_ip: IpAddr,
+        next: F,
+    ) -> Result<Response<Body>, Infallible>
+    where
+        Fut: Future<Output = Result<Response<Body>, Infallible>> + Send,
+        F: FnOnce(Parts, Body, IpAddr) -> Fut + Send,
+    {
+        // Verify the method is POST
+        if parts.method != Method::POST {
+            return next(parts, body, client_ip).await;
+        }
+
+        // Verify the path matches the live view extension url
+        let path = format!("/session/{}/webgrid/live", self.session_id);
+        if !parts.uri.path().eq_ignore_ascii_case(&path) {
+            return next(parts, body, client_ip).await;
+        }
+
+        let (status, response_value) = match &self.token {
+            Some(token) => (
+                StatusCode::OK,
+                json!({
+                    "status": "success",
+                    "token": token,
+                    "path": format!("{}?token={}", path, token)
+                }),
+            ),
+            None => (
+                StatusCode::NOT_FOUND,
+                json!({
+                    "status": "error",
+                    "error": "live view is not enabled"
+                }),
+            ),
+        };
+
+        // Build a json response and send it
+        let response = serde_json::to_string(&response_value).unwrap_or_else(|_| "{}".into());
+        Ok(Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, "application/json")
+            .body(response.into())
+            .unwrap())
+    }
+}
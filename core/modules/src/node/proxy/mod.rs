@@ -2,30 +2,69 @@ use async_trait::async_trait;
 use hyper::Server;
 use jatsl::Job;
 use std::net::SocketAddr;
+use std::time::Instant;
 use tokio::sync::mpsc::UnboundedSender;
 use tracing::info;
 
+use self::downloads::DownloadsInterceptor;
 use self::file_upload::FileUploadInterceptor;
 use self::forwarding::ForwardingResponder;
+use self::journal::JournalResponder;
+use self::lifetime_extension::LifetimeExtensionInterceptor;
+use self::live_extension::LiveViewExtensionInterceptor;
+use self::recording_extension::RecordingExtensionInterceptor;
+use self::screenshot::ScreenshotInterceptor;
+use self::status_extension::StatusExtensionInterceptor;
 use self::terminate::TerminationInterceptor;
+use crate::node::journal::CommandJournal;
 use crate::node::proxy::metadata_extension::MetadataExtensionInterceptor;
+use crate::node::recording::RecordingControl;
+use crate::node::screenshot::ScreenshotCollector;
+use crate::node::subtitles::SubtitleTrack;
 use domain::event::SessionClientMetadata;
 use harness::HeartStone;
 use library::{http::Responder, make_responder_chain_service_fn, responder_chain};
+use std::path::PathBuf;
 
+mod downloads;
 mod file_upload;
 mod forwarding;
+mod journal;
+mod lifetime_extension;
+mod live_extension;
 mod metadata_extension;
+mod recording_extension;
+mod screenshot;
+mod status_extension;
 mod terminate;
 
+pub use self::status_extension::SessionOutcome;
+
+/// Session state shared between the proxy and the remaining parts of the node
+pub struct ProxyContext {
+    pub heart_stone: HeartStone,
+    pub metadata_tx: UnboundedSender<SessionClientMetadata>,
+    pub deadline: Option<Instant>,
+    pub journal: CommandJournal,
+    pub subtitles: SubtitleTrack,
+    pub outcome: SessionOutcome,
+    pub recording: RecordingControl,
+    pub screenshots: ScreenshotCollector,
+    /// Secret required to connect to the live view, if it is enabled
+    pub live_view_token: Option<String>,
+    /// Upper bound for the size of files uploaded by the client in bytes
+    pub max_upload_size: usize,
+    /// Directory the browser stores downloaded files in, if downloads are enabled
+    pub download_directory: Option<PathBuf>,
+}
+
 pub struct ProxyJob {
     port: u16,
     identifier: String,
     authority: String,
     session_id_internal: String,
     session_id_external: String,
-    heart_stone: HeartStone,
-    metadata_tx: UnboundedSender<SessionClientMetadata>,
+    context: ProxyContext,
 }
 
 impl ProxyJob {
@@ -35,8 +74,7 @@ impl ProxyJob {
         authority: String,
         session_id_internal: String,
         session_id_external: String,
-        heart_stone: HeartStone,
-        metadata_tx: UnboundedSender<SessionClientMetadata>,
+        context: ProxyContext,
     ) -> Self {
         Self {
             port,
@@ -44,8 +82,7 @@ impl ProxyJob {
             authority,
             session_id_internal,
             session_id_external,
-            heart_stone,
-            metadata_tx,
+            context,
         }
     }
 }
@@ -59,12 +96,45 @@ impl Job for ProxyJob {
         &self,
         manager: jatsl::JobManager,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-        let termination_interceptor =
-            TerminationInterceptor::new(self.heart_stone.clone(), self.session_id_external.clone());
+        let context = &self.context;
+
+        let termination_interceptor = TerminationInterceptor::new(
+            context.heart_stone.clone(),
+            self.session_id_external.clone(),
+        );
 
         let metadata_extension_interceptor = MetadataExtensionInterceptor::new(
-            self.metadata_tx.clone(),
-            self.heart_stone.clone(),
+            context.metadata_tx.clone(),
+            context.heart_stone.clone(),
+            self.session_id_external.clone(),
+        );
+
+        let lifetime_extension_interceptor =
+            LifetimeExtensionInterceptor::new(self.session_id_external.clone(), context.deadline);
+
+        let status_extension_interceptor = StatusExtensionInterceptor::new(
+            context.outcome.clone(),
+            self.session_id_external.clone(),
+        );
+
+        let recording_extension_interceptor = RecordingExtensionInterceptor::new(
+            context.recording.clone(),
+            self.session_id_external.clone(),
+        );
+
+        let live_extension_interceptor = LiveViewExtensionInterceptor::new(
+            context.live_view_token.clone(),
+            self.session_id_external.clone(),
+        );
+
+        let screenshot_interceptor = ScreenshotInterceptor::new(
+            context.screenshots.clone(),
+            self.session_id_external.clone(),
+        );
+
+        let journal_responder = JournalResponder::new(
+            context.journal.clone(),
+            context.subtitles.clone(),
             self.session_id_external.clone(),
         );
 
@@ -73,16 +143,31 @@ impl Job for ProxyJob {
             self.authority.clone(),
             self.session_id_internal.clone(),
             self.session_id_external.clone(),
-            self.heart_stone.clone(),
+            context.heart_stone.clone(),
+        );
+
+        let file_upload_interceptor = FileUploadInterceptor::new(
+            context.heart_stone.clone(),
+            self.session_id_external.clone(),
+            context.max_upload_size,
         );
 
-        let file_upload_interceptor =
-            FileUploadInterceptor::new(self.heart_stone.clone(), self.session_id_external.clone());
+        let downloads_interceptor = DownloadsInterceptor::new(
+            context.download_directory.clone(),
+            self.session_id_external.clone(),
+        );
 
         let make_svc = make_responder_chain_service_fn! {
             termination_interceptor,
             metadata_extension_interceptor,
+            lifetime_extension_interceptor,
+            status_extension_interceptor,
+            recording_extension_interceptor,
+            live_extension_interceptor,
             file_upload_interceptor,
+            downloads_interceptor,
+            screenshot_interceptor,
+            journal_responder,
             forwarding_responder
         };
 
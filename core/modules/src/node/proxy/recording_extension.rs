@@ -0,0 +1,76 @@
+use crate::node::recording::RecordingControl;
+use async_trait::async_trait;
+use futures::Future;
+use hyper::header::CONTENT_TYPE;
+use hyper::{
+    http::{request::Parts, Method, Response, StatusCode},
+    Body,
+};
+use library::http::Responder;
+use serde_json::json;
+use std::convert::Infallible;
+use std::net::IpAddr;
+
+/// Allows clients to pause and resume the screen recording at `/session/<id>/webgrid/recording/{pause,resume}`
+pub struct RecordingExtensionInterceptor {
+    control: RecordingControl,
+    session_id: String,
+}
+
+impl RecordingExtensionInterceptor {
+    pub fn new(control: RecordingControl, session_id: String) -> Self {
+        Self {
+            control,
+            session_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Responder for RecordingExtensionInterceptor {
+    #[inline]
+    async fn respond<F, Fut>(
+        &self,
+        parts: Parts,
+        body: Body,
+        client_ip: IpAddr,
+        next: F,
+    ) -> Result<Response<Body>, Infallible>
+    where
+        Fut: Future<Output = Result<Response<Body>, Infallible>> + Send,
+        F: FnOnce(Parts, Body, IpAddr) -> Fut + Send,
+    {
+        // Verify the method is POST
+        if parts.method != Method::POST {
+            return next(parts, body, client_ip).await;
+        }
+
+        // Verify the path matches one of the recording extension urls
+        let prefix = format!("/session/{}/webgrid/recording/", self.session_id);
+        let path = parts.uri.path().to_ascii_lowercase();
+        let result = match path.strip_prefix(&prefix) {
+            Some("pause") => self.control.pause().await,
+            Some("resume") => self.control.resume().await,
+            _ => return next(parts, body, client_ip).await,
+        };
+
+        let (status, response_value) = match result {
+            Ok(_) => (StatusCode::OK, json!({ "status": "success" })),
+            Err(e) => (
+                StatusCode::CONFLICT,
+                json!({
+                    "status": "error",
+                    "error": e.to_string()
+                }),
+            ),
+        };
+
+        // Build a json response and send it
+        let response = serde_json::to_string(&response_value).unwrap_or_else(|_| "{}".into());
+        Ok(Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, "application/json")
+            .body(response.into())
+            .unwrap())
+    }
+}
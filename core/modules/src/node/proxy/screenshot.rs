@@ -0,0 +1,66 @@
+use crate::node::screenshot::ScreenshotCollector;
+use async_trait::async_trait;
+use futures::Future;
+use hyper::{
+    http::{request::Parts, Method, Response, StatusCode},
+    Body,
+};
+use library::http::Responder;
+use std::convert::Infallible;
+use std::net::IpAddr;
+
+/// Captures screenshots before the client deletes the session and after failed commands
+pub struct ScreenshotInterceptor {
+    collector: ScreenshotCollector,
+    session_id: String,
+}
+
+impl ScreenshotInterceptor {
+    pub fn new(collector: ScreenshotCollector, session_id: String) -> Self {
+        Self {
+            collector,
+            session_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Responder for ScreenshotInterceptor {
+    #[inline]
+    async fn respond<F, Fut>(
+        &self,
+        parts: Parts,
+        body: Body,
+        client_ip: IpAddr,
+        next: F,
+    ) -> Result<Response<Body>, Infallible>
+    where
+        Fut: Future<Output = Result<Response<Body>, Infallible>> + Send,
+        F: FnOnce(Parts, Body, IpAddr) -> Fut + Send,
+    {
+        let session_path = format!("/session/{}", self.session_id);
+        let path = parts.uri.path();
+
+        if !path.starts_with(&session_path) {
+            return next(parts, body, client_ip).await;
+        }
+
+        // The browser is gone once the deletion has been forwarded
+        if parts.method == Method::DELETE && path.eq_ignore_ascii_case(&session_path) {
+            self.collector.capture_final().await;
+            return next(parts, body, client_ip).await;
+        }
+
+        let response = next(parts, body, client_ip).await?;
+
+        // Missing elements or windows are routinely probed for by clients and not worth a screenshot
+        let status = response.status();
+        let failed = status.is_client_error() || status.is_server_error();
+
+        if failed && status != StatusCode::NOT_FOUND {
+            self.collector.capture_error().await;
+        }
+
+        Ok(response)
+    }
+}
@@ -0,0 +1,128 @@
+use async_trait::async_trait;
+use futures::Future;
+use hyper::body::to_bytes;
+use hyper::header::CONTENT_TYPE;
+use hyper::{
+    http::{request::Parts, Method, Response},
+    Body,
+};
+use library::http::Responder;
+use serde::Deserialize;
+use serde_json::json;
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::debug;
+
+#[derive(Debug, Error)]
+enum StatusExtensionInterceptorError {
+    #[error("collecting request bytes failed")]
+    StreamingError(#[from] hyper::Error),
+    #[error("failed to deserialize status")]
+    DeserializationError(#[from] serde_json::Error),
+}
+
+/// Outcome of a session as reported by the client
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+enum SessionStatus {
+    Passed,
+    Failed,
+}
+
+#[derive(Deserialize)]
+struct StatusRequest {
+    status: SessionStatus,
+}
+
+/// Shared flag indicating whether the client marked the session as failed
+#[derive(Clone, Default)]
+pub struct SessionOutcome {
+    failed: Arc<AtomicBool>,
+}
+
+impl SessionOutcome {
+    /// Whether the session has been marked as failed
+    pub fn failed(&self) -> bool {
+        self.failed.load(Ordering::Acquire)
+    }
+}
+
+/// Allows clients to mark the session as passed or failed at `/session/<id>/webgrid/status`
+pub struct StatusExtensionInterceptor {
+    outcome: SessionOutcome,
+    session_id: String,
+}
+
+impl StatusExtensionInterceptor {
+    pub fn new(outcome: SessionOutcome, session_id: String) -> Self {
+        Self {
+            outcome,
+            session_id,
+        }
+    }
+
+    async fn handle_body(&self, body: Body) -> Result<(), StatusExtensionInterceptorError> {
+        let bytes = to_bytes(body).await?;
+        let request: StatusRequest = serde_json::from_slice(&bytes)?;
+
+        debug!(status = ?request.status, "Received session status from client");
+        self.outcome
+            .failed
+            .store(request.status == SessionStatus::Failed, Ordering::Release);
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Responder for StatusExtensionInterceptor {
+    #[inline]
+    async fn respond<F, Fut>(
+        &self,
+        parts: Parts,
+        body: Body,
+        client_ip: IpAddr,
+        next: F,
+    ) -> Result<Response<Body>, Infallible>
+    where
+        Fut: Future<Output = Result<Response<Body>, Infallible>> + Send,
+        F: FnOnce(Parts, Body, IpAddr) -> Fut + Send,
+    {
+        // Verify the method is POST
+        if parts.method != Method::POST {
+            return next(parts, body, client_ip).await;
+        }
+
+        // Verify the path matches the status extension url
+        if !parts
+            .uri
+            .path()
+            .eq_ignore_ascii_case(&format!("/session/{}/webgrid/status", self.session_id))
+        {
+            return next(parts, body, client_ip).await;
+        }
+
+        // Handle the status and json-ify the result
+        let response_value = match self.handle_body(body).await {
+            Ok(_) => {
+                json!({ "status": "success" })
+            }
+            Err(e) => {
+                json!({
+                    "status": "error",
+                    "error": e.to_string()
+                })
+            }
+        };
+
+        // Build a json response and send it
+        let response = serde_json::to_string(&response_value).unwrap_or_else(|_| "{}".into());
+        Ok(Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(response.into())
+            .unwrap())
+    }
+}
@@ -0,0 +1,131 @@
+use chrono::Utc;
+use domain::recording::RecordingPause;
+use heim::process;
+use heim::process::os::unix::{ProcessExt, Signal};
+use library::EmptyResult;
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tracing::info;
+
+#[derive(Debug, Error)]
+pub enum RecordingControlError {
+    #[error("the session is not being recorded")]
+    NotRecording,
+    #[error("the recording is already paused")]
+    AlreadyPaused,
+    #[error("the recording is not paused")]
+    NotPaused,
+}
+
+#[derive(Default)]
+struct RecordingControlState {
+    active: bool,
+    pid: Option<i32>,
+    pauses: Vec<RecordingPause>,
+}
+
+impl RecordingControlState {
+    fn paused(&self) -> bool {
+        self.pauses.last().map(|p| p.end.is_none()).unwrap_or(false)
+    }
+}
+
+/// Handle to pause and resume the screen capture of a running recording
+///
+/// Pausing suspends the ffmpeg process so that no frames are captured until it is resumed.
+/// Recordings without a long-running process check [`RecordingControl::recording_interval`] around each capture instead.
+#[derive(Clone, Default)]
+pub struct RecordingControl {
+    state: Arc<Mutex<RecordingControlState>>,
+}
+
+impl RecordingControl {
+    /// Binds the handle to the ffmpeg process of the recording
+    pub async fn attach(&self, pid: i32) {
+        let mut state = self.state.lock().await;
+        state.active = true;
+        state.pid = Some(pid);
+    }
+
+    /// Marks the session as recorded by captures which are not bound to a single process
+    pub async fn activate(&self) {
+        self.state.lock().await.active = true;
+    }
+
+    /// Suspends the screen capture
+    pub async fn pause(&self) -> EmptyResult {
+        let mut state = self.state.lock().await;
+
+        if !state.active {
+            return Err(RecordingControlError::NotRecording.into());
+        } else if state.paused() {
+            return Err(RecordingControlError::AlreadyPaused.into());
+        }
+
+        if let Some(pid) = state.pid {
+            process::get(pid).await?.signal(Signal::Stop).await?;
+        }
+        info!("Paused recording");
+
+        state.pauses.push(RecordingPause {
+            start: Utc::now(),
+            end: None,
+        });
+
+        Ok(())
+    }
+
+    /// Continues a previously paused screen capture
+    pub async fn resume(&self) -> EmptyResult {
+        let mut state = self.state.lock().await;
+
+        if !state.active {
+            return Err(RecordingControlError::NotRecording.into());
+        } else if !state.paused() {
+            return Err(RecordingControlError::NotPaused.into());
+        }
+
+        if let Some(pid) = state.pid {
+            process::get(pid).await?.signal(Signal::Cont).await?;
+        }
+        info!("Resumed recording");
+
+        if let Some(pause) = state.pauses.last_mut() {
+            pause.end = Some(Utc::now());
+        }
+
+        Ok(())
+    }
+
+    /// Lets a suspended ffmpeg process continue without ending the pause
+    ///
+    /// Used on termination so that ffmpeg can handle a pending SIGTERM while the rest of the session stays unrecorded.
+    pub async fn release(&self) -> EmptyResult {
+        let state = self.state.lock().await;
+
+        if let (Some(pid), true) = (state.pid, state.paused()) {
+            process::get(pid).await?.signal(Signal::Cont).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Identifies the current unpaused interval, empty while the capture is suspended
+    ///
+    /// Captures which take a while compare it before and after grabbing a frame to detect overlapping pauses.
+    pub async fn recording_interval(&self) -> Option<usize> {
+        let state = self.state.lock().await;
+
+        if state.paused() {
+            None
+        } else {
+            Some(state.pauses.len())
+        }
+    }
+
+    /// All pauses of the recording so far
+    pub async fn pauses(&self) -> Vec<RecordingPause> {
+        self.state.lock().await.pauses.clone()
+    }
+}
@@ -1,4 +1,5 @@
 use async_trait::async_trait;
+use domain::recording::store_object;
 use domain::{event::SessionIdentifier, storage_path};
 use futures::join;
 use heim::process;
@@ -10,6 +11,7 @@ use library::{http::Responder, make_responder_chain_service_fn, responder_chain}
 use library::{AccumulatedPerformanceMetrics, EmptyResult, PerformanceMonitor};
 use std::io::Read;
 use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
@@ -20,7 +22,18 @@ use tokio::process::Command;
 use tokio::sync::{mpsc, oneshot, Mutex};
 use tracing::{error, info};
 
+mod control;
+mod preview;
+mod spool;
+mod staging;
 mod storage;
+mod timelapse;
+
+pub use control::RecordingControl;
+pub use preview::generate_previews;
+pub use spool::{UploadSpool, UploadSpoolJob};
+pub use staging::promote_staged;
+pub use timelapse::TimeLapseJob;
 
 #[derive(Debug, Error)]
 enum RecordingError {
@@ -30,12 +43,40 @@ enum RecordingError {
     NotRestartable,
 }
 
+/// Recording state shared between the recording and the remaining parts of the node
+#[derive(Clone)]
+pub struct RecordingContext {
+    /// Local directory to keep the recording in instead of uploading it right away
+    pub staging: Option<PathBuf>,
+    /// Local buffer for objects which could not be uploaded right away
+    pub spool: Option<UploadSpool>,
+    pub byte_count_total: Arc<AtomicUsize>,
+    pub control: RecordingControl,
+}
+
+impl RecordingContext {
+    /// Writes an object of the recording to the staging directory, the upload spool, or the storage backend
+    pub async fn store<S: StorageBackend>(
+        &self,
+        storage: &S,
+        session_id: SessionIdentifier,
+        name: &str,
+        content: &[u8],
+    ) -> EmptyResult {
+        match (&self.staging, &self.spool) {
+            (Some(directory), _) => staging::stage_object(directory, name, content).await,
+            (None, Some(spool)) => spool.store(storage, session_id, name, content).await,
+            (None, None) => store_object(storage, session_id, name, content).await,
+        }
+    }
+}
+
 pub struct RecordingJob<S: StorageBackend> {
     arguments: String,
     storage: S,
+    context: RecordingContext,
     session_id: SessionIdentifier,
     started: AtomicBool,
-    byte_count_total: Arc<AtomicUsize>,
     profiling_tx: mpsc::UnboundedSender<Arc<Mutex<AccumulatedPerformanceMetrics>>>,
     profiling_interval: Option<Duration>,
 }
@@ -48,16 +89,16 @@ where
         session_id: SessionIdentifier,
         arguments: String,
         storage: S,
-        byte_count_total: Arc<AtomicUsize>,
+        context: RecordingContext,
         profiling_tx: mpsc::UnboundedSender<Arc<Mutex<AccumulatedPerformanceMetrics>>>,
         profiling_interval: Option<Duration>,
     ) -> Self {
         Self {
             arguments,
             storage,
+            context,
             session_id,
             started: AtomicBool::new(false),
-            byte_count_total,
             profiling_tx,
             profiling_interval,
         }
@@ -73,11 +114,8 @@ where
     const SUPPORTS_GRACEFUL_TERMINATION: bool = true;
 
     async fn execute(&self, manager: jatsl::JobManager) -> EmptyResult {
-        let storage_responder = StorageResponder::new(
-            self.session_id,
-            self.storage.clone(),
-            self.byte_count_total.clone(),
-        );
+        let storage_responder =
+            StorageResponder::new(self.session_id, self.storage.clone(), self.context.clone());
         let make_svc = make_responder_chain_service_fn!(storage_responder);
 
         let addr = SocketAddr::from(([127, 0, 0, 1], crate::constants::PORT_STORAGE));
@@ -110,6 +148,8 @@ where
         // Prepare the three step termination process
         let pid = ffmpeg.id().ok_or(RecordingError::NoPIDFound)? as i32;
         let process = process::get(pid).await?;
+        let control = self.context.control.clone();
+        control.attach(pid).await;
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
 
         // Profile ffmpeg (if enabled)
@@ -125,6 +165,11 @@ where
             if let Err(e) = process.signal(Signal::Term).await {
                 error!("Failed to send SIGTERM to ffmepg: {}", e);
             }
+
+            // A suspended ffmpeg only reacts to the SIGTERM once it continues
+            if let Err(e) = control.release().await {
+                error!("Failed to release paused ffmpeg: {}", e);
+            }
         };
 
         // 2. Wait for ffmpeg to finish up the recording in response to the SIGTERM
@@ -0,0 +1,186 @@
+use domain::event::SessionIdentifier;
+use domain::recording::store_object;
+use library::storage::StorageBackend;
+use library::EmptyResult;
+use std::fmt::Write;
+use std::path::Path;
+use tempfile::TempDir;
+use thiserror::Error;
+use tokio::fs;
+use tokio::process::Command;
+use tracing::debug;
+
+/// Name of the thumbnail image in the storage directory of the session
+pub const THUMBNAIL_FILENAME: &str = "thumbnail.jpg";
+
+/// Name of the sprite sheet image in the storage directory of the session
+pub const SPRITES_FILENAME: &str = "sprites.jpg";
+
+/// Name of the WebVTT index of the sprite sheet in the storage directory of the session
+pub const SPRITES_INDEX_FILENAME: &str = "sprites.vtt";
+
+const THUMBNAIL_WIDTH: usize = 320;
+const SPRITE_WIDTH: usize = 160;
+const SPRITE_HEIGHT: usize = 90;
+const SPRITE_COLUMNS: usize = 10;
+const SPRITE_COUNT_MAX: usize = 100;
+const SPRITE_INTERVAL_MIN: usize = 5;
+
+#[derive(Debug, Error)]
+enum PreviewError {
+    #[error("ffmpeg exited with {0}")]
+    FfmpegFailed(std::process::ExitStatus),
+}
+
+/// Arrangement of the frames in the sprite sheet
+#[derive(Debug, PartialEq)]
+struct SpriteLayout {
+    /// Seconds between two frames
+    interval: usize,
+    count: usize,
+    rows: usize,
+}
+
+impl SpriteLayout {
+    fn new(duration: f64) -> Self {
+        let duration = duration.ceil().max(1.0) as usize;
+        let interval =
+            SPRITE_INTERVAL_MIN.max((duration + SPRITE_COUNT_MAX - 1) / SPRITE_COUNT_MAX);
+        let count = (duration + interval - 1) / interval;
+
+        Self {
+            interval,
+            count,
+            rows: (count + SPRITE_COLUMNS - 1) / SPRITE_COLUMNS,
+        }
+    }
+
+    fn filter(&self) -> String {
+        format!(
+            "fps=1/{interval},scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,tile={columns}x{rows}",
+            interval = self.interval,
+            w = SPRITE_WIDTH,
+            h = SPRITE_HEIGHT,
+            columns = SPRITE_COLUMNS,
+            rows = self.rows,
+        )
+    }
+
+    /// Builds a WebVTT file which maps each interval to its frame in the sprite sheet
+    fn index(&self) -> String {
+        let mut index = String::from("WEBVTT\n");
+
+        for i in 0..self.count {
+            let x = (i % SPRITE_COLUMNS) * SPRITE_WIDTH;
+            let y = (i / SPRITE_COLUMNS) * SPRITE_HEIGHT;
+
+            write!(
+                index,
+                "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+                timestamp(i * self.interval),
+                timestamp((i + 1) * self.interval),
+                SPRITES_FILENAME,
+                x,
+                y,
+                SPRITE_WIDTH,
+                SPRITE_HEIGHT
+            )
+            .ok();
+        }
+
+        index
+    }
+}
+
+fn timestamp(seconds: usize) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.000",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    )
+}
+
+async fn ffmpeg(input: &Path, filter: &str, output: &Path) -> EmptyResult {
+    let status = Command::new("ffmpeg")
+        .arg("-y")
+        .arg("-i")
+        .arg(input)
+        .args(&["-vf", filter, "-frames:v", "1"])
+        .arg(output)
+        .status()
+        .await?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(PreviewError::FfmpegFailed(status).into())
+    }
+}
+
+/// Extracts a thumbnail and a sprite sheet with WebVTT index from a local copy of the recording and uploads them
+pub async fn generate_previews<S: StorageBackend>(
+    session_id: SessionIdentifier,
+    storage: &S,
+    video: &Path,
+    duration: f64,
+) -> EmptyResult {
+    let layout = SpriteLayout::new(duration);
+
+    let directory = TempDir::new()?;
+    let thumbnail = directory.path().join(THUMBNAIL_FILENAME);
+    let sprites = directory.path().join(SPRITES_FILENAME);
+
+    let thumbnail_filter = format!("thumbnail,scale={}:-2", THUMBNAIL_WIDTH);
+    ffmpeg(video, &thumbnail_filter, &thumbnail).await?;
+    ffmpeg(video, &layout.filter(), &sprites).await?;
+
+    let objects = vec![
+        (THUMBNAIL_FILENAME, fs::read(&thumbnail).await?),
+        (SPRITES_FILENAME, fs::read(&sprites).await?),
+        (SPRITES_INDEX_FILENAME, layout.index().into_bytes()),
+    ];
+
+    for (name, content) in objects {
+        store_object(storage, session_id, name, &content).await?;
+    }
+
+    debug!(frames = layout.count, "Uploaded recording previews");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn layout_short_recordings() {
+        assert_eq!(
+            SpriteLayout::new(12.0),
+            SpriteLayout {
+                interval: SPRITE_INTERVAL_MIN,
+                count: 3,
+                rows: 1
+            }
+        );
+    }
+
+    #[test]
+    fn layout_long_recordings() {
+        let layout = SpriteLayout::new(3600.0);
+
+        assert_eq!(layout.interval, 36);
+        assert_eq!(layout.count, SPRITE_COUNT_MAX);
+        assert_eq!(layout.rows, SPRITE_COUNT_MAX / SPRITE_COLUMNS);
+    }
+
+    #[test]
+    fn index_sprites() {
+        let index = SpriteLayout::new(60.0).index();
+
+        assert!(index.starts_with("WEBVTT\n"));
+        assert!(index.contains("00:00:00.000 --> 00:00:05.000\nsprites.jpg#xywh=0,0,160,90\n"));
+        assert!(index.contains("00:00:55.000 --> 00:01:00.000\nsprites.jpg#xywh=160,90,160,90\n"));
+    }
+}
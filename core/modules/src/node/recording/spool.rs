@@ -0,0 +1,354 @@
+use async_trait::async_trait;
+use domain::event::SessionIdentifier;
+use domain::recording::store_object;
+use jatsl::Job;
+use library::storage::StorageBackend;
+use library::{BoxedError, EmptyResult};
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::fs;
+use tokio::sync::{Mutex, Notify};
+use tokio::time::{sleep, timeout};
+use tracing::{debug, warn};
+
+use super::staging::stage_object;
+
+/// Delay before the first retry of a failed upload, doubled with every subsequent failure
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Upper bound for the delay between two retries
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct SpoolState {
+    /// Names of spooled objects in the order they have to be uploaded, along with the revision they have been spooled at
+    pending: Vec<(String, u64)>,
+    /// Names of objects which could neither be uploaded nor spooled
+    lost: Vec<String>,
+    /// Revision assigned to the next spooled object
+    revision: u64,
+}
+
+/// Local disk buffer for recording objects which could not be uploaded to the storage backend
+///
+/// Once an upload failed, all following objects are spooled as well until the spool has been drained.
+/// This keeps the upload order intact so that playlists never reference segments which are not yet available.
+#[derive(Clone)]
+pub struct UploadSpool {
+    directory: Arc<TempDir>,
+    state: Arc<Mutex<SpoolState>>,
+    enqueued: Arc<Notify>,
+}
+
+impl UploadSpool {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            directory: Arc::new(TempDir::new()?),
+            state: Arc::new(Mutex::new(SpoolState::default())),
+            enqueued: Arc::new(Notify::new()),
+        })
+    }
+
+    /// Uploads an object or spools it if the upload failed or earlier objects are still pending
+    pub async fn store<S: StorageBackend>(
+        &self,
+        storage: &S,
+        session_id: SessionIdentifier,
+        name: &str,
+        content: &[u8],
+    ) -> EmptyResult {
+        let name = name.trim_start_matches('/');
+
+        // The lock is released during the upload so that hanging retries in the background do not hold up new objects
+        let spooling = !self.state.lock().await.pending.is_empty();
+
+        if !spooling {
+            match store_object(storage, session_id, name, content).await {
+                Ok(_) => return Ok(()),
+                Err(error) => warn!(
+                    ?error,
+                    ?name,
+                    "Failed to upload recording object, spooling it"
+                ),
+            }
+        }
+
+        let mut state = self.state.lock().await;
+
+        if let Err(error) = stage_object(self.directory.path(), name, content).await {
+            state.lost.push(name.to_owned());
+            return Err(error);
+        }
+
+        // Newer versions of an object (e.g. a playlist) supersede pending ones
+        let revision = state.revision;
+        state.revision += 1;
+        state.pending.retain(|(pending, _)| pending != name);
+        state.pending.push((name.to_owned(), revision));
+        self.enqueued.notify_one();
+
+        Ok(())
+    }
+
+    /// Attempts to upload the oldest spooled object and returns whether the spool is empty afterwards
+    async fn upload_next<S: StorageBackend>(
+        &self,
+        storage: &S,
+        session_id: SessionIdentifier,
+    ) -> Result<bool, BoxedError> {
+        let (entry, path, content) = {
+            let mut state = self.state.lock().await;
+
+            let entry = match state.pending.first() {
+                Some(entry) => entry.clone(),
+                None => return Ok(true),
+            };
+
+            // Reading while holding the lock prevents a newer version from being staged halfway through
+            let path = self.directory.path().join(&entry.0);
+            match fs::read(&path).await {
+                Ok(content) => (entry, path, content),
+                Err(error) => {
+                    warn!(?error, name = ?entry.0, "Spooled recording object is not readable");
+                    state.pending.remove(0);
+                    state.lost.push(entry.0);
+                    return Ok(state.pending.is_empty());
+                }
+            }
+        };
+
+        store_object(storage, session_id, &entry.0, &content).await?;
+        debug!(name = ?entry.0, "Uploaded spooled recording object");
+
+        let mut state = self.state.lock().await;
+
+        // A newer version may have been spooled during the upload, it stays pending along with its file
+        if state.pending.first() == Some(&entry) {
+            state.pending.remove(0);
+            fs::remove_file(&path).await.ok();
+        }
+
+        Ok(state.pending.is_empty())
+    }
+
+    /// Uploads spooled objects until the spool is empty, backing off exponentially after failures
+    pub async fn drain<S: StorageBackend>(&self, storage: &S, session_id: SessionIdentifier) {
+        let mut backoff = INITIAL_BACKOFF;
+
+        loop {
+            match self.upload_next(storage, session_id).await {
+                Ok(true) => return,
+                Ok(false) => backoff = INITIAL_BACKOFF,
+                Err(error) => {
+                    debug!(
+                        ?error,
+                        ?backoff,
+                        "Failed to upload spooled recording object"
+                    );
+                    sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    /// Drains the spool within the given budget and returns the names of all objects which have been lost
+    pub async fn flush<S: StorageBackend>(
+        &self,
+        storage: &S,
+        session_id: SessionIdentifier,
+        budget: Duration,
+    ) -> Vec<String> {
+        if timeout(budget, self.drain(storage, session_id))
+            .await
+            .is_err()
+        {
+            warn!(?budget, "Failed to flush upload spool in time");
+        }
+
+        let mut state = self.state.lock().await;
+        let pending = std::mem::take(&mut state.pending);
+        let mut lost = std::mem::take(&mut state.lost);
+        lost.extend(pending.into_iter().map(|(name, _)| name));
+
+        lost
+    }
+}
+
+/// Retries spooled uploads in the background while the recording is running
+pub struct UploadSpoolJob<S: StorageBackend> {
+    spool: UploadSpool,
+    storage: S,
+    session_id: SessionIdentifier,
+}
+
+impl<S: StorageBackend> UploadSpoolJob<S> {
+    pub fn new(spool: UploadSpool, storage: S, session_id: SessionIdentifier) -> Self {
+        Self {
+            spool,
+            storage,
+            session_id,
+        }
+    }
+}
+
+#[async_trait]
+impl<S> Job for UploadSpoolJob<S>
+where
+    S: StorageBackend + Send + Sync + 'static,
+{
+    const NAME: &'static str = module_path!();
+    const SUPPORTS_GRACEFUL_TERMINATION: bool = true;
+
+    async fn execute(&self, manager: jatsl::JobManager) -> EmptyResult {
+        manager.ready().await;
+
+        let termination = manager.termination_signal();
+        tokio::pin!(termination);
+
+        // Whatever is left once the job terminates is flushed by the node before it shuts down
+        loop {
+            tokio::select! {
+                _ = self.spool.enqueued.notified() => {
+                    debug!("Retrying spooled recording uploads");
+                }
+                _ = &mut termination => break,
+            }
+
+            tokio::select! {
+                _ = self.spool.drain(&self.storage, self.session_id) => {}
+                _ = &mut termination => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use library::storage::s3::S3StorageURL;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use uuid::Uuid;
+
+    /// In-memory storage which rejects all writes while it is unavailable
+    #[derive(Clone, Default)]
+    struct FlakyStorage {
+        unavailable: Arc<AtomicBool>,
+        objects: Arc<std::sync::Mutex<HashMap<String, Vec<u8>>>>,
+    }
+
+    #[async_trait]
+    impl StorageBackend for FlakyStorage {
+        type URL = S3StorageURL;
+
+        fn new(_url: Self::URL) -> Result<Self, BoxedError> {
+            Ok(Self::default())
+        }
+
+        fn presign_get(&self, path: &str, _expiry_secs: u32) -> Result<String, BoxedError> {
+            Ok(path.to_owned())
+        }
+
+        fn presign_put(
+            &self,
+            path: &str,
+            _expiry_secs: u32,
+            _content_type: &str,
+        ) -> Result<String, BoxedError> {
+            Ok(path.to_owned())
+        }
+
+        async fn get_object(&self, path: &str) -> Result<Vec<u8>, BoxedError> {
+            let objects = self.objects.lock().unwrap();
+            objects.get(path).cloned().ok_or_else(|| "not found".into())
+        }
+
+        async fn put_object(&self, path: &str, content: &[u8]) -> Result<(), BoxedError> {
+            if self.unavailable.load(Ordering::SeqCst) {
+                return Err("storage unavailable".into());
+            }
+
+            let mut objects = self.objects.lock().unwrap();
+            objects.insert(path.to_owned(), content.to_vec());
+            Ok(())
+        }
+
+        async fn put_object_with_content_type(
+            &self,
+            path: &str,
+            content: &[u8],
+            _content_type: &str,
+        ) -> Result<(), BoxedError> {
+            self.put_object(path, content).await
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_spooled_objects_once_storage_recovers() {
+        let storage = FlakyStorage::default();
+        let spool = UploadSpool::new().unwrap();
+        let id = Uuid::new_v4();
+        let object = |name: &str| storage_path(id, name);
+
+        storage.unavailable.store(true, Ordering::SeqCst);
+        spool
+            .store(&storage, id, "/screen0.m4s", b"0")
+            .await
+            .unwrap();
+        spool
+            .store(&storage, id, "/screen.m3u8", b"old")
+            .await
+            .unwrap();
+
+        // Objects queue up behind spooled ones even if the storage is available again
+        storage.unavailable.store(false, Ordering::SeqCst);
+        spool
+            .store(&storage, id, "/screen1.m4s", b"1")
+            .await
+            .unwrap();
+        spool
+            .store(&storage, id, "/screen.m3u8", b"new")
+            .await
+            .unwrap();
+        assert!(storage.get_object(&object("screen1.m4s")).await.is_err());
+
+        let lost = spool.flush(&storage, id, Duration::from_secs(1)).await;
+
+        assert!(lost.is_empty());
+        assert_eq!(
+            storage.get_object(&object("screen1.m4s")).await.unwrap(),
+            b"1"
+        );
+        assert_eq!(
+            storage.get_object(&object("screen.m3u8")).await.unwrap(),
+            b"new"
+        );
+    }
+
+    #[tokio::test]
+    async fn report_objects_which_could_not_be_flushed() {
+        let storage = FlakyStorage::default();
+        let spool = UploadSpool::new().unwrap();
+        let id = Uuid::new_v4();
+
+        storage.unavailable.store(true, Ordering::SeqCst);
+        spool
+            .store(&storage, id, "/screen0.m4s", b"0")
+            .await
+            .unwrap();
+
+        let lost = spool.flush(&storage, id, Duration::from_millis(50)).await;
+
+        assert_eq!(lost, vec!["screen0.m4s".to_owned()]);
+    }
+
+    fn storage_path(id: SessionIdentifier, name: &str) -> String {
+        domain::storage_path(id, name)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
@@ -0,0 +1,179 @@
+use domain::event::SessionIdentifier;
+use domain::recording::{store_object, TIME_LAPSE_INDEX_FILENAME};
+use library::storage::StorageBackend;
+use library::{BoxedError, EmptyResult};
+use std::path::Path;
+use std::time::Instant;
+use tokio::fs;
+use tokio::time::timeout_at;
+use tracing::{debug, warn};
+
+/// Writes a recorded object to the local staging directory instead of the storage backend
+pub async fn stage_object(directory: &Path, name: &str, content: &[u8]) -> EmptyResult {
+    let name = name.trim_start_matches('/');
+
+    // Objects are created by ffmpeg and only consist of plain file names
+    if name.is_empty() || name.contains('/') || name.contains("..") {
+        return Err(format!("invalid object name {}", name).into());
+    }
+
+    fs::write(directory.join(name), content).await?;
+    Ok(())
+}
+
+/// Outcome of uploading a staged recording
+#[derive(Debug, Default)]
+pub struct Promotion {
+    /// Number of video bytes which have been uploaded
+    pub byte_count: usize,
+    /// Names of staged objects which could not be uploaded before the deadline
+    pub unpromoted: Vec<String>,
+}
+
+/// Uploads all staged objects to the storage backend until the deadline has passed
+///
+/// Playlists and indices are uploaded last so that they never reference objects which are not yet available.
+pub async fn promote_staged<S: StorageBackend>(
+    directory: &Path,
+    session_id: SessionIdentifier,
+    storage: &S,
+    deadline: Instant,
+) -> Result<Promotion, BoxedError> {
+    let mut names = Vec::new();
+    let mut entries = fs::read_dir(directory).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        names.push(entry.file_name().to_string_lossy().into_owned());
+    }
+
+    names.sort_by_key(|name| (is_index(name), name.clone()));
+
+    let mut promotion = Promotion::default();
+
+    for name in names {
+        let upload = async {
+            let content = fs::read(directory.join(&name)).await?;
+            store_object(storage, session_id, &name, &content).await?;
+            Ok::<_, BoxedError>(content.len())
+        };
+
+        match timeout_at(deadline.into(), upload).await {
+            Ok(Ok(size)) if !is_index(&name) => promotion.byte_count += size,
+            Ok(Ok(_)) => {}
+            Ok(Err(error)) => {
+                warn!(?error, ?name, "Failed to promote staged object");
+                promotion.unpromoted.push(name);
+            }
+            Err(_) => promotion.unpromoted.push(name),
+        }
+    }
+
+    debug!(
+        byte_count = promotion.byte_count,
+        unpromoted = promotion.unpromoted.len(),
+        "Promoted staged recording"
+    );
+
+    Ok(promotion)
+}
+
+fn is_index(name: &str) -> bool {
+    name.ends_with(".m3u8") || name == TIME_LAPSE_INDEX_FILENAME
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn reject_nested_objects() {
+        let directory = TempDir::new().unwrap();
+
+        assert!(stage_object(directory.path(), "/screen0.m4s", b"video")
+            .await
+            .is_ok());
+        assert!(stage_object(directory.path(), "/../screen.m3u8", b"")
+            .await
+            .is_err());
+        assert!(stage_object(directory.path(), "/nested/screen.m3u8", b"")
+            .await
+            .is_err());
+
+        assert_eq!(
+            fs::read(directory.path().join("screen0.m4s"))
+                .await
+                .unwrap(),
+            b"video"
+        );
+    }
+
+    #[derive(Clone)]
+    struct UnresponsiveStorage;
+
+    #[async_trait::async_trait]
+    impl StorageBackend for UnresponsiveStorage {
+        type URL = library::storage::s3::S3StorageURL;
+
+        fn new(_url: Self::URL) -> Result<Self, BoxedError> {
+            Ok(Self)
+        }
+
+        fn presign_get(&self, path: &str, _expiry_secs: u32) -> Result<String, BoxedError> {
+            Ok(path.to_owned())
+        }
+
+        fn presign_put(
+            &self,
+            path: &str,
+            _expiry_secs: u32,
+            _content_type: &str,
+        ) -> Result<String, BoxedError> {
+            Ok(path.to_owned())
+        }
+
+        async fn get_object(&self, _path: &str) -> Result<Vec<u8>, BoxedError> {
+            futures::future::pending().await
+        }
+
+        async fn put_object(&self, _path: &str, _content: &[u8]) -> Result<(), BoxedError> {
+            futures::future::pending().await
+        }
+
+        async fn put_object_with_content_type(
+            &self,
+            _path: &str,
+            _content: &[u8],
+            _content_type: &str,
+        ) -> Result<(), BoxedError> {
+            futures::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn report_objects_which_could_not_be_promoted() {
+        let directory = TempDir::new().unwrap();
+        stage_object(directory.path(), "screen0.m4s", b"video")
+            .await
+            .unwrap();
+        stage_object(directory.path(), "screen.m3u8", b"playlist")
+            .await
+            .unwrap();
+
+        let deadline = Instant::now() + std::time::Duration::from_millis(50);
+        let promotion = promote_staged(
+            directory.path(),
+            uuid::Uuid::new_v4(),
+            &UnresponsiveStorage,
+            deadline,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(promotion.byte_count, 0);
+        assert_eq!(
+            promotion.unpromoted,
+            vec!["screen0.m4s".to_owned(), "screen.m3u8".to_owned()]
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use async_trait::async_trait;
-use domain::{event::SessionIdentifier, storage_path};
+use domain::event::SessionIdentifier;
+use domain::recording::annotate_pauses;
 use futures::Future;
 use hyper::http::{request::Parts, Method, Response, StatusCode};
 use hyper::{body, Body};
@@ -7,15 +8,16 @@ use library::http::Responder;
 use library::storage::StorageBackend;
 use std::convert::Infallible;
 use std::net::IpAddr;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::atomic::Ordering;
 use tokio::sync::Semaphore;
 use tracing::{trace, warn};
 
+use super::RecordingContext;
+
 pub struct StorageResponder<S: StorageBackend> {
-    byte_count_total: Arc<AtomicUsize>,
     session_id: SessionIdentifier,
     storage: S,
+    context: RecordingContext,
     semaphore: Semaphore,
 }
 
@@ -23,15 +25,11 @@ impl<S> StorageResponder<S>
 where
     S: StorageBackend,
 {
-    pub fn new(
-        session_id: SessionIdentifier,
-        storage: S,
-        byte_count_total: Arc<AtomicUsize>,
-    ) -> Self {
+    pub fn new(session_id: SessionIdentifier, storage: S, context: RecordingContext) -> Self {
         Self {
-            byte_count_total,
             session_id,
             storage,
+            context,
             semaphore: Semaphore::new(1),
         }
     }
@@ -77,21 +75,30 @@ where
             let _permit = self.semaphore.acquire().await;
 
             match body::to_bytes(body).await {
-                Ok(content) => {
-                    if !parts.uri.path().ends_with(".m3u8") {
-                        self.byte_count_total
+                Ok(mut content) => {
+                    if parts.uri.path().ends_with(".m3u8") {
+                        // Mark the pauses so that players and the dashboard can point them out
+                        let pauses = self.context.control.pauses().await;
+                        if !pauses.is_empty() {
+                            content =
+                                annotate_pauses(&String::from_utf8_lossy(&content), &pauses).into();
+                        }
+                    } else {
+                        self.context
+                            .byte_count_total
                             .fetch_add(content.len(), Ordering::Relaxed);
                     }
 
-                    let path = storage_path(self.session_id, parts.uri.path())
-                        .to_string_lossy()
-                        .into_owned();
+                    let result = self
+                        .context
+                        .store(&self.storage, self.session_id, parts.uri.path(), &content)
+                        .await;
 
-                    if let Err(e) = self.storage.put_object(&path, &content).await {
+                    if let Err(e) = result {
                         warn!(error = ?e, "Failed to write video fragment");
                         Ok(self.new_error_response(&format!("object not writable {}", e)))
                     } else {
-                        trace!(?path, "Stored video fragment");
+                        trace!(path = parts.uri.path(), "Stored video fragment");
                         Ok(self.new_response())
                     }
                 }
@@ -0,0 +1,141 @@
+use super::recording::RecordingControl;
+use domain::{event::SessionIdentifier, storage_path};
+use hyper::body::to_bytes;
+use hyper::{Body, Client, Method, Request};
+use library::storage::s3::S3StorageBackend;
+use library::storage::StorageBackend;
+use library::BoxedError;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// Name of the screenshot taken right before the session terminates
+pub const FINAL_SCREENSHOT: &str = "final.png";
+
+#[derive(Deserialize)]
+struct ScreenshotResponse {
+    value: String,
+}
+
+/// Captures screenshots through the webdriver and uploads them to the storage backend
+#[derive(Clone)]
+pub struct ScreenshotCollector {
+    session_id: SessionIdentifier,
+    storage: Option<S3StorageBackend>,
+    error_limit: usize,
+    recording: RecordingControl,
+    error_count: Arc<AtomicUsize>,
+    final_requested: Arc<AtomicBool>,
+    driver: Arc<Mutex<Option<String>>>,
+    captured: Arc<Mutex<Vec<String>>>,
+}
+
+impl ScreenshotCollector {
+    pub fn new(
+        session_id: SessionIdentifier,
+        storage: Option<S3StorageBackend>,
+        error_limit: usize,
+        recording: RecordingControl,
+    ) -> Self {
+        Self {
+            session_id,
+            storage,
+            error_limit,
+            recording,
+            error_count: Arc::new(AtomicUsize::new(0)),
+            final_requested: Arc::new(AtomicBool::new(false)),
+            driver: Arc::new(Mutex::new(None)),
+            captured: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Sets the screenshot URL of the driver session, e.g. `http://127.0.0.1:4444/session/<id>/screenshot`
+    pub async fn attach(&self, screenshot_url: String) {
+        *self.driver.lock().await = Some(screenshot_url);
+    }
+
+    /// Names of all screenshots which have been uploaded, relative to the storage directory of the session
+    pub async fn captured(&self) -> Vec<String> {
+        self.captured.lock().await.clone()
+    }
+
+    /// Captures the final screenshot unless it has already been attempted
+    pub async fn capture_final(&self) {
+        if !self.final_requested.swap(true, Ordering::SeqCst) {
+            self.capture(FINAL_SCREENSHOT.to_owned()).await;
+        }
+    }
+
+    /// Captures a screenshot after a command failed, as long as the configured limit has not been reached
+    pub async fn capture_error(&self) {
+        let index = self.error_count.fetch_add(1, Ordering::Relaxed);
+
+        if index < self.error_limit {
+            self.capture(format!("error-{}.png", index + 1)).await;
+        }
+    }
+
+    async fn capture(&self, name: String) {
+        let storage = match &self.storage {
+            Some(storage) => storage,
+            None => return,
+        };
+
+        let url = match self.driver.lock().await.clone() {
+            Some(url) => url,
+            None => return,
+        };
+
+        // Whatever the client did not want to be recorded should not end up in a screenshot either
+        let interval = match self.recording.recording_interval().await {
+            Some(interval) => interval,
+            None => {
+                debug!(?name, "Skipping screenshot while recording is paused");
+                return;
+            }
+        };
+
+        let result = match Self::fetch(&url).await {
+            Ok(_) if self.recording.recording_interval().await != Some(interval) => {
+                debug!(?name, "Discarding screenshot which overlaps with a pause");
+                return;
+            }
+            Ok(png) => {
+                let path = storage_path(self.session_id, &name)
+                    .to_string_lossy()
+                    .into_owned();
+
+                storage.put_object(&path, &png).await
+            }
+            Err(error) => Err(error),
+        };
+
+        match result {
+            Ok(_) => {
+                debug!(?name, "Uploaded screenshot");
+                self.captured.lock().await.push(name);
+            }
+            Err(error) => warn!(?error, ?name, "Failed to capture screenshot"),
+        }
+    }
+
+    async fn fetch(url: &str) -> Result<Vec<u8>, BoxedError> {
+        let req = Request::builder()
+            .method(Method::GET)
+            .uri(url)
+            .body(Body::empty())?;
+
+        let response = Client::new().request(req).await?;
+
+        if !response.status().is_success() {
+            return Err(format!("driver responded with {}", response.status()).into());
+        }
+
+        let body = to_bytes(response.into_body()).await?;
+        let screenshot: ScreenshotResponse = serde_json::from_slice(&body)?;
+
+        Ok(base64::decode(screenshot.value)?)
+    }
+}
@@ -0,0 +1,238 @@
+use super::journal::JournalEntry;
+use chrono::{DateTime, Duration, Utc};
+use domain::event::SessionIdentifier;
+use domain::recording::{attach_subtitles, recording_start};
+use library::storage::StorageBackend;
+use library::EmptyResult;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Identifier of web element references as defined by the WebDriver specification
+const ELEMENT_IDENTIFIER: &str = "element-6066-11e4-a52e-4f735466cecf";
+
+/// Minimum time a caption stays visible, so that fast commands remain readable
+const MIN_CUE_DURATION_MS: i64 = 1500;
+
+struct Cue {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    text: String,
+}
+
+#[derive(Default)]
+struct SubtitleState {
+    cues: Vec<Cue>,
+    /// Selectors used to locate elements, keyed by element reference
+    selectors: HashMap<String, String>,
+}
+
+/// Human readable captions of the commands executed in a session, overlaid onto the recording
+#[derive(Clone, Default)]
+pub struct SubtitleTrack {
+    state: Arc<Mutex<SubtitleState>>,
+}
+
+impl SubtitleTrack {
+    /// Adds a caption for the given command, if it is worth showing
+    pub async fn record(&self, entry: &JournalEntry) {
+        let mut state = self.state.lock().await;
+
+        if entry.method == "POST" && (entry.path == "/element" || entry.path == "/elements") {
+            remember_selectors(&mut state.selectors, entry);
+        }
+
+        if let Some(text) = describe(entry, &state.selectors) {
+            let duration = Duration::from_std(entry.duration).unwrap_or_else(|_| Duration::zero());
+
+            state.cues.push(Cue {
+                start: entry.started_at,
+                end: entry.started_at + duration.max(Duration::milliseconds(MIN_CUE_DURATION_MS)),
+                text,
+            });
+        }
+    }
+
+    /// Renders all captions as WebVTT relative to the given start of the recording
+    async fn to_webvtt(&self, origin: DateTime<Utc>) -> String {
+        let mut webvtt = String::from("WEBVTT\n");
+
+        for cue in self.state.lock().await.cues.iter() {
+            write!(
+                webvtt,
+                "\n{} --> {}\n{}\n",
+                timestamp(cue.start - origin),
+                timestamp(cue.end - origin),
+                escape(&cue.text)
+            )
+            .ok();
+        }
+
+        webvtt
+    }
+
+    /// Attaches the captions as a subtitles rendition to the finished recording of the session
+    pub async fn upload<S: StorageBackend>(
+        &self,
+        session_id: SessionIdentifier,
+        storage: &S,
+        bandwidth: usize,
+    ) -> EmptyResult {
+        let origin = recording_start(storage, session_id).await?;
+        let webvtt = self.to_webvtt(origin).await;
+
+        attach_subtitles(storage, session_id, &webvtt, bandwidth).await
+    }
+}
+
+/// Stores the selector used in an element lookup for all elements it returned
+fn remember_selectors(selectors: &mut HashMap<String, String>, entry: &JournalEntry) {
+    let selector = serde_json::from_str::<Value>(&entry.request)
+        .ok()
+        .and_then(|request| request["value"].as_str().map(str::to_owned));
+
+    let (selector, response) = match (selector, serde_json::from_str::<Value>(&entry.response)) {
+        (Some(selector), Ok(response)) => (selector, response),
+        _ => return,
+    };
+
+    let elements = match &response["value"] {
+        Value::Array(elements) => elements.iter().collect(),
+        element => vec![element],
+    };
+
+    for element in elements {
+        if let Some(reference) = element[ELEMENT_IDENTIFIER].as_str() {
+            selectors.insert(reference.to_owned(), selector.clone());
+        }
+    }
+}
+
+/// Describes commands which change the state of the browser, queries are omitted
+fn describe(entry: &JournalEntry, selectors: &HashMap<String, String>) -> Option<String> {
+    let request: Value = serde_json::from_str(&entry.request).unwrap_or(Value::Null);
+    let segments: Vec<&str> = entry.path.trim_start_matches('/').split('/').collect();
+
+    let element = |reference: &str| {
+        selectors
+            .get(reference)
+            .cloned()
+            .unwrap_or_else(|| "element".to_owned())
+    };
+
+    let text = match (entry.method.as_str(), segments.as_slice()) {
+        ("DELETE", [""]) => "quit".to_owned(),
+        ("POST", ["url"]) => format!("navigate {}", request["url"].as_str().unwrap_or_default()),
+        ("POST", ["back"]) => "back".to_owned(),
+        ("POST", ["forward"]) => "forward".to_owned(),
+        ("POST", ["refresh"]) => "refresh".to_owned(),
+        ("POST", ["element"]) | ("POST", ["elements"]) => {
+            format!("find {}", request["value"].as_str().unwrap_or_default())
+        }
+        ("POST", ["element", reference, "click"]) => format!("click {}", element(reference)),
+        ("POST", ["element", reference, "clear"]) => format!("clear {}", element(reference)),
+        ("POST", ["element", reference, "value"]) => format!("type into {}", element(reference)),
+        ("POST", ["execute", _]) => "execute script".to_owned(),
+        ("POST", ["actions"]) => "perform actions".to_owned(),
+        ("POST", ["window"]) => "switch window".to_owned(),
+        ("POST", ["frame"]) => "switch frame".to_owned(),
+        ("GET", _) => return None,
+        (method, _) => format!("{} {}", method, entry.path),
+    };
+
+    if entry.status >= 400 {
+        Some(format!("{} (failed with {})", text, entry.status))
+    } else {
+        Some(text)
+    }
+}
+
+/// Makes arbitrary text safe to use as a single line of cue payload
+fn escape(text: &str) -> String {
+    text.replace(['\r', '\n'], " ")
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn timestamp(offset: Duration) -> String {
+    let millis = offset.num_milliseconds().max(0);
+
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        (millis / 60_000) % 60,
+        (millis / 1000) % 60,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    fn entry(method: &str, path: &str, request: &str, response: &str) -> JournalEntry {
+        JournalEntry {
+            method: method.to_owned(),
+            path: path.to_owned(),
+            started_at: "2021-03-01T10:00:05Z".parse().unwrap(),
+            duration: std::time::Duration::from_millis(200),
+            status: 200,
+            request: request.to_owned(),
+            response: response.to_owned(),
+        }
+    }
+
+    #[test]
+    fn describe_commands() {
+        let selectors = HashMap::new();
+
+        assert_eq!(
+            describe(
+                &entry("POST", "/url", r#"{"url":"https://example.com"}"#, ""),
+                &selectors
+            ),
+            Some("navigate https://example.com".into())
+        );
+        assert_eq!(describe(&entry("GET", "/title", "", ""), &selectors), None);
+        assert_eq!(
+            describe(&entry("DELETE", "", "", ""), &selectors),
+            Some("quit".into())
+        );
+    }
+
+    #[test]
+    fn escape_cue_text() {
+        assert_eq!(
+            escape("find a[title=\"x --> y\"] &\n<b>"),
+            "find a[title=\"x --&gt; y\"] &amp; &lt;b&gt;"
+        );
+    }
+
+    #[tokio::test]
+    async fn name_elements_by_selector() {
+        let track = SubtitleTrack::default();
+
+        track
+            .record(&entry(
+                "POST",
+                "/element",
+                r##"{"using":"css selector","value":"#submit"}"##,
+                r#"{"value":{"element-6066-11e4-a52e-4f735466cecf":"abc"}}"#,
+            ))
+            .await;
+        track
+            .record(&entry("POST", "/element/abc/click", "{}", ""))
+            .await;
+
+        let webvtt = track
+            .to_webvtt("2021-03-01T10:00:00Z".parse().unwrap())
+            .await;
+
+        assert!(webvtt.starts_with("WEBVTT\n"));
+        assert!(webvtt.contains("00:00:05.000 --> 00:00:06.500\nfind #submit\n"));
+        assert!(webvtt.contains("\nclick #submit\n"));
+    }
+}
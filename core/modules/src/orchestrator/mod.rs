@@ -6,13 +6,16 @@ use self::provisioner::DockerProvisioner;
 use self::provisioner::KubernetesProvisioner;
 use self::provisioner::SessionProvisioner;
 use async_trait::async_trait;
+use domain::container::ContainerImageSet;
 use harness::{Heart, Module, ServiceRunner};
 use jatsl::{schedule, JobScheduler};
 use library::communication::event::ConsumerGroupDescriptor;
 use library::BoxedError;
-use options::{OrchestratorOptions, ProvisionerCommand};
+use options::{ImageLoadError, OrchestratorOptions, ProvisionerCommand};
 use services::*;
-use tracing::debug;
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, info};
 
 mod options;
 mod provisioner;
@@ -20,6 +23,23 @@ mod services;
 
 pub use options::Options;
 
+/// Delay between attempts to make all images available on startup
+const IMAGE_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Error thrown while setting up the orchestrator
+#[derive(Debug, Error)]
+pub enum OrchestratorError {
+    /// Images available to sessions could not be loaded
+    #[error("failed to load images")]
+    Images(#[from] ImageLoadError),
+    /// Connection to the local docker daemon could not be established
+    #[error("failed to connect to docker")]
+    Docker(#[from] bollard::errors::Error),
+    /// Adaptive capacity has been requested for a provisioner which does not run sessions on the local host
+    #[error("adaptive capacity is not supported by the {0} provisioner")]
+    AdaptiveCapacityUnsupported(&'static str),
+}
+
 type BoxedProvisioner = Arc<Box<dyn SessionProvisioner + Send + Sync>>;
 type BoxedMatchingStrategy = Arc<Box<dyn MatchingStrategy + Send + Sync>>;
 
@@ -28,56 +48,64 @@ pub struct Orchestrator {
     options: OrchestratorOptions,
     provisioner: BoxedProvisioner,
     matching_strategy: BoxedMatchingStrategy,
+    images: ContainerImageSet,
 }
 
 impl Orchestrator {
-    /// Creates a new instance from raw parts
-    pub fn new(command: Options) -> Self {
-        let (options, provisioner, matching_strategy): (
+    /// Creates a new instance from raw parts, failing if the images or provisioner are unavailable
+    pub fn new(command: Options) -> Result<Self, OrchestratorError> {
+        let (options, provisioner, matching_strategy, images): (
             OrchestratorOptions,
             BoxedProvisioner,
             BoxedMatchingStrategy,
+            ContainerImageSet,
         ) = match command.provisioner {
             ProvisionerCommand::Kubernetes(provisioner_options) => {
+                if provisioner_options.orchestrator.adaptive_capacity {
+                    return Err(OrchestratorError::AdaptiveCapacityUnsupported("kubernetes"));
+                }
+
+                let images = provisioner_options.images.load()?;
                 let provisioner = KubernetesProvisioner::new(
-                    provisioner_options.images.clone(),
+                    images.clone(),
                     provisioner_options.orchestrator.queueing.id.clone(),
+                    provisioner_options.orchestrator.screen_resolutions.clone(),
                 );
 
                 (
                     provisioner_options.orchestrator,
                     Arc::new(Box::new(provisioner)),
-                    Arc::new(Box::new(ContainerMatchingStrategy::new(
-                        provisioner_options.images,
-                    ))),
+                    Arc::new(Box::new(ContainerMatchingStrategy::new(images.clone()))),
+                    images,
                 )
             }
             ProvisionerCommand::Docker(provisioner_options) => {
+                let images = provisioner_options.images.load()?;
                 let provisioner = DockerProvisioner::new(
-                    provisioner_options.images.clone(),
+                    images.clone(),
                     provisioner_options.orchestrator.queueing.id.clone(),
                     !provisioner_options.retain_exited_sessions,
                     provisioner_options.storage,
                     provisioner_options.volume,
                     provisioner_options.log,
-                )
-                .unwrap();
+                    provisioner_options.orchestrator.screen_resolutions.clone(),
+                )?;
 
                 (
                     provisioner_options.orchestrator,
                     Arc::new(Box::new(provisioner)),
-                    Arc::new(Box::new(ContainerMatchingStrategy::new(
-                        provisioner_options.images,
-                    ))),
+                    Arc::new(Box::new(ContainerMatchingStrategy::new(images.clone()))),
+                    images,
                 )
             }
         };
 
-        Self {
+        Ok(Self {
             options,
             provisioner,
             matching_strategy,
-        }
+            images,
+        })
     }
 }
 
@@ -85,7 +113,13 @@ impl Orchestrator {
 impl Module for Orchestrator {
     async fn run(&mut self, scheduler: &JobScheduler) -> Result<Option<Heart>, BoxedError> {
         let redis_url = &self.options.redis.url;
-        let state = ProvisioningState::new(self.options.permits);
+        let state =
+            ProvisioningState::new(self.options.permits).with_image_limits(self.images.clone());
+
+        // Sessions would likely time out while images are being pulled,
+        // so readiness is held off until all of them are present
+        info!("Pulling images");
+        await_images(self.provisioner.as_ref(), IMAGE_RETRY_INTERVAL).await;
 
         let matching_service = ServiceRunner::<ProvisionerMatchingService<_>>::new(
             redis_url.clone(),
@@ -94,6 +128,7 @@ impl Module for Orchestrator {
             (
                 self.options.queueing.id.clone(),
                 self.matching_strategy.clone(),
+                state.clone(),
             ),
         );
 
@@ -103,20 +138,59 @@ impl Module for Orchestrator {
             provisioning_extension,
             ConsumerGroupDescriptor::default(),
             self.options.queueing.id.to_string(),
-            (state.clone(), self.provisioner.clone()),
+            (
+                self.options.queueing.id.clone(),
+                state.clone(),
+                self.provisioner.clone(),
+            ),
         );
 
+        let footprint = FootprintTracker::default();
         let termination_service = ServiceRunner::<SessionTerminationWatcherService>::new(
             redis_url.clone(),
             ConsumerGroupDescriptor::default(),
             self.options.queueing.id.to_string(),
-            state.clone(),
+            (state.clone(), footprint.clone()),
         );
 
+        if self.options.adaptive_capacity {
+            let min_permits = self.options.min_permits;
+            let max_permits = self.options.max_permits.max(min_permits);
+
+            let capacity_service = AdaptiveCapacityService::new(
+                state.clone(),
+                footprint,
+                min_permits..=max_permits,
+                self.options.capacity_interval,
+            );
+
+            schedule!(scheduler, { capacity_service });
+        }
+
         let sync_service = HardwareSynchronisationService::new(
+            state.clone(),
+            self.provisioner.clone(),
+            self.options.cleanup_interval,
+        );
+
+        let image_service = ImageRefreshService::new(
+            self.provisioner.clone(),
+            self.options.image_refresh_interval,
+        );
+
+        let heartbeat_service = HeartbeatService::new(
+            redis_url.clone(),
+            self.options.queueing.id.clone(),
+            self.options.heartbeat_interval,
+        );
+
+        let (heart, heart_stone) = Heart::new();
+        let drain_service = DrainService::new(
             state,
             self.provisioner.clone(),
+            heart_stone,
             self.options.cleanup_interval,
+            self.options.drain_deadline,
         );
 
         debug!("Scheduling jobs");
@@ -125,8 +199,11 @@ impl Module for Orchestrator {
             provisioning_service,
             termination_service,
             sync_service,
+            image_service,
+            heartbeat_service,
+            drain_service,
         });
 
-        Ok(Some(Heart::without_heart_stone()))
+        Ok(Some(heart))
     }
 }
@@ -1,9 +1,12 @@
 use std::time::Duration;
 
 use crate::options::{QueueingOptions, RedisOptions};
+use domain::container::catalog::ImageCatalogError;
 use domain::container::ContainerImageSet;
-use library::helpers::parse_seconds;
+use domain::webdriver::ScreenResolution;
+use library::helpers::{load_config, parse_seconds};
 use structopt::StructOpt;
+use thiserror::Error;
 
 /// Options for the orchestrator module and provisioner
 #[derive(Debug, StructOpt)]
@@ -19,12 +22,51 @@ pub struct OrchestratorOptions {
     /// Maximum number of sessions managed by this instance.
     /// When this number is reached, provisioning requests have to wait
     /// until a running session terminates or use another orchestrator.
+    /// With adaptive capacity enabled, this is only the initial number of permits.
     #[structopt(short, long, env)]
     pub permits: usize,
 
+    /// Adjusts the number of permits to the available memory and CPUs of the host,
+    /// based on the resources used by previous sessions. Requires session profiling.
+    /// Only supported by the docker provisioner.
+    #[structopt(long, env)]
+    pub adaptive_capacity: bool,
+
+    /// Lower bound for the number of permits when adaptive capacity is enabled
+    #[structopt(long, env, default_value = "1")]
+    pub min_permits: usize,
+
+    /// Upper bound for the number of permits when adaptive capacity is enabled
+    #[structopt(long, env, default_value = "100")]
+    pub max_permits: usize,
+
+    /// Interval in which the host resources are sampled when adaptive capacity is enabled
+    #[structopt(long, env, default_value = "30", parse(try_from_str = parse_seconds))]
+    pub capacity_interval: Duration,
+
     #[structopt(long, env, default_value = "30", parse(try_from_str = parse_seconds))]
     pub cleanup_interval: Duration,
 
+    /// Maximum time to wait for running sessions to terminate after a drain has been
+    /// requested (by sending SIGUSR1) before the orchestrator exits regardless
+    #[structopt(long, env, default_value = "3600", parse(try_from_str = parse_seconds))]
+    pub drain_deadline: Duration,
+
+    /// Interval in which the orchestrator announces that it is alive.
+    /// Should be well below the provisioner timeout of the manager.
+    #[structopt(long, env, default_value = "10", parse(try_from_str = parse_seconds))]
+    pub heartbeat_interval: Duration,
+
+    /// Interval in which new versions of the session images are pulled.
+    /// Regardless of this value, all images are pulled on startup.
+    #[structopt(long, env, default_value = "3600", parse(try_from_str = parse_seconds))]
+    pub image_refresh_interval: Duration,
+
+    /// Comma-separated screen resolutions (e.g. `1366x768,2560x1440`) which sessions may request.
+    /// Sessions that do not request a resolution use the default of the node.
+    #[structopt(long, env, use_delimiter = true)]
+    pub screen_resolutions: Vec<ScreenResolution>,
+
     #[allow(missing_docs)]
     #[structopt(flatten)]
     pub queueing: QueueingOptions,
@@ -34,6 +76,46 @@ pub struct OrchestratorOptions {
     pub redis: RedisOptions,
 }
 
+/// Error thrown while loading the images available to sessions
+#[derive(Debug, Error)]
+pub enum ImageLoadError {
+    /// Catalog file does not exist or can not be read
+    #[error("image catalog unreadable")]
+    CatalogUnreadable(#[from] std::io::Error),
+    /// Catalog contents are malformed or contain invalid entries
+    #[error("invalid image catalog")]
+    CatalogInvalid(#[from] ImageCatalogError),
+    /// Neither of the two image sources has been configured
+    #[error("neither an image list nor an image catalog has been provided")]
+    NoImages,
+}
+
+/// Options defining the images which are available to sessions
+#[derive(Debug, StructOpt)]
+pub struct ImageOptions {
+    /// List of images with associated browser versions that should be used.
+    /// For more details, please consult the WebGrid documentation regarding
+    /// the ContainerImageSet data structure. Ignored when an image catalog is set.
+    #[structopt(env)]
+    pub images: Option<ContainerImageSet>,
+
+    /// Name of a YAML image catalog within the config directory
+    /// which allows for additional settings per image
+    #[structopt(long, env)]
+    pub image_catalog: Option<String>,
+}
+
+impl ImageOptions {
+    /// Reads the image catalog or falls back to the list of images
+    pub fn load(&self) -> Result<ContainerImageSet, ImageLoadError> {
+        match (&self.image_catalog, &self.images) {
+            (Some(catalog), _) => Ok(ContainerImageSet::from_catalog(&load_config(catalog)?)?),
+            (None, Some(images)) => Ok(images.clone()),
+            (None, None) => Err(ImageLoadError::NoImages),
+        }
+    }
+}
+
 /// Variants of provisioners
 #[derive(Debug, StructOpt)]
 pub enum ProvisionerCommand {
@@ -48,11 +130,8 @@ pub struct DockerOptions {
     #[structopt(flatten)]
     pub orchestrator: OrchestratorOptions,
 
-    /// List of images with associated browser versions that should be used.
-    /// For more details, please consult the WebGrid documentation regarding
-    /// the ContainerImageSet data structure.
-    #[structopt(env)]
-    pub images: ContainerImageSet,
+    #[structopt(flatten)]
+    pub images: ImageOptions,
 
     /// When this flag is set, all session containers will be kept after they finished.
     /// Note that this may yield a vast amount of exited container so only use sparingly
@@ -87,9 +166,6 @@ pub struct KubernetesOptions {
     #[structopt(flatten)]
     pub orchestrator: OrchestratorOptions,
 
-    /// List of images with associated browser versions that should be used.
-    /// For more details, please consult the WebGrid documentation regarding
-    /// the ContainerImageSet data structure.
-    #[structopt(env)]
-    pub images: ContainerImageSet,
+    #[structopt(flatten)]
+    pub images: ImageOptions,
 }
@@ -1,7 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::str::FromStr;
+use std::time::Instant;
 
-use super::SessionProvisioner;
+use super::ImageRefreshOutcome;
+use super::{requested_screen_resolution, ScreenResolutionError, SessionProvisioner};
 use super::{CONTAINER_SESSION_ID_LABEL, PROVISIONER_INSTANCE_LABEL};
 use async_trait::async_trait;
 use bollard::container::{
@@ -12,11 +14,11 @@ use bollard::models::HostConfig;
 use bollard::Docker;
 use domain::container::ContainerImageSet;
 use domain::event::{ProvisionedSessionMetadata, SessionIdentifier};
-use domain::webdriver::RawCapabilitiesRequest;
+use domain::webdriver::{RawCapabilitiesRequest, ScreenResolution};
 use futures::StreamExt;
 use library::{BoxedError, EmptyResult};
 use thiserror::Error;
-use tracing::{debug, instrument, warn};
+use tracing::{debug, info, instrument, warn};
 use uuid::Uuid;
 
 #[derive(Error, Debug)]
@@ -35,6 +37,12 @@ enum DockerProvisionerError {
 
     #[error("invalid capabilities")]
     InvalidCapabilities(#[from] serde_json::Error),
+
+    #[error("unavailable screen resolution")]
+    UnavailableScreenResolution(#[from] ScreenResolutionError),
+
+    #[error("images not available: {0}")]
+    ImagesMissing(String),
 }
 
 /// Implementation based on [Docker](https://www.docker.com/) containers
@@ -46,6 +54,7 @@ pub struct DockerProvisioner {
     storage: Option<String>,
     binds: Vec<String>,
     log: String,
+    screen_resolutions: Vec<ScreenResolution>,
 }
 
 impl DockerProvisioner {
@@ -57,6 +66,7 @@ impl DockerProvisioner {
         storage: Option<String>,
         binds: Vec<String>,
         log: String,
+        screen_resolutions: Vec<ScreenResolution>,
     ) -> Result<Self, bollard::errors::Error> {
         if images.is_empty() {
             warn!("No images provided to provisioner. It won't be able to launch any sessions!");
@@ -72,6 +82,7 @@ impl DockerProvisioner {
             storage,
             binds,
             log,
+            screen_resolutions,
         })
     }
 
@@ -98,6 +109,38 @@ impl DockerProvisioner {
         Ok(())
     }
 
+    /// Identifier of the local image which changes whenever a new version has been pulled
+    async fn local_image_id(&self, image: &str) -> Option<String> {
+        self.docker
+            .inspect_image(image)
+            .await
+            .ok()
+            .and_then(|inspect| inspect.id)
+    }
+
+    #[instrument(err, skip(self))]
+    async fn pull_latest_image(&self, image: &str) -> Result<(), bollard::errors::Error> {
+        let options = Some(CreateImageOptions {
+            from_image: image,
+            ..Default::default()
+        });
+
+        let mut stream = self.docker.create_image(options, None, None);
+        let mut last_status = None;
+
+        while let Some(result) = stream.next().await {
+            let info = result?;
+
+            // Only log changes as docker reports the progress at a very high frequency
+            if info.status != last_status {
+                debug!(status = ?info.status, progress = ?info.progress, "Pulling image");
+                last_status = info.status;
+            }
+        }
+
+        Ok(())
+    }
+
     #[instrument(err, skip(self, raw_capabilities))]
     async fn create_container(
         &self,
@@ -105,6 +148,7 @@ impl DockerProvisioner {
         raw_capabilities: &RawCapabilitiesRequest,
     ) -> Result<ProvisionedSessionMetadata, DockerProvisionerError> {
         let request = raw_capabilities.parse()?;
+        let resolution = requested_screen_resolution(&request, &self.screen_resolutions)?;
         let image = self
             .images
             .match_against_capabilities(request)
@@ -115,10 +159,12 @@ impl DockerProvisioner {
             .await
             .map_err(DockerProvisionerError::ImagePullError)?;
 
+        let capabilities = image.inject_default_capabilities(raw_capabilities)?;
+
         let name = format!("webgrid-session-{}", session_id);
         let mut env: Vec<String> = vec![
             format!("ID={}", session_id),
-            format!("CAPABILITIES={}", raw_capabilities.as_str()),
+            format!("CAPABILITIES={}", capabilities.as_str()),
             format!("HOST={}", name.as_str()),
             format!("RUST_LOG={}", self.log),
         ];
@@ -127,6 +173,10 @@ impl DockerProvisioner {
             env.push(format!("STORAGE={}", storage));
         }
 
+        if let Some(resolution) = resolution {
+            env.push(format!("RESOLUTION={}", resolution));
+        }
+
         let mut labels = HashMap::<&str, &str>::new();
         let instance_id = self.instance.to_string();
         let session_id_label = session_id.to_string();
@@ -140,6 +190,8 @@ impl DockerProvisioner {
             network_mode: Some("webgrid".to_string()),
             shm_size: Some(1024 * 1024 * 1024 * 2),
             binds: Some(self.binds.clone()),
+            nano_cpus: image.resources.nano_cpus(),
+            memory: image.resources.memory_bytes(),
             ..Default::default()
         };
 
@@ -218,6 +270,58 @@ impl SessionProvisioner for DockerProvisioner {
     async fn purge_terminated(&self) -> EmptyResult {
         Ok(())
     }
+
+    #[instrument(skip(self))]
+    async fn refresh_images(&self) -> EmptyResult {
+        let identifiers = self
+            .images
+            .iter()
+            .map(|image| image.identifier.as_str())
+            .collect::<HashSet<_>>();
+
+        let refresh_start = Instant::now();
+        let mut updated = 0;
+        let mut failed = 0;
+        let mut missing = Vec::new();
+
+        for image in identifiers.iter() {
+            let previous = self.local_image_id(image).await;
+            let start = Instant::now();
+
+            if let Err(error) = self.pull_latest_image(image).await {
+                warn!(?image, ?error, "Failed to pull image");
+                failed += 1;
+            }
+
+            let duration = start.elapsed();
+            let current = self.local_image_id(image).await;
+            let outcome = ImageRefreshOutcome::classify(previous.as_deref(), current.as_deref());
+
+            match outcome {
+                ImageRefreshOutcome::Missing => missing.push(image.to_string()),
+                ImageRefreshOutcome::Changed => {
+                    info!(?image, ?previous, ?current, ?duration, "Image has changed");
+                    updated += 1;
+                }
+                ImageRefreshOutcome::Unchanged => {}
+            }
+        }
+
+        info!(
+            total = identifiers.len(),
+            updated,
+            failed,
+            missing = missing.len(),
+            duration = ?refresh_start.elapsed(),
+            "Refreshed images"
+        );
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(DockerProvisionerError::ImagesMissing(missing.join(", ")).into())
+        }
+    }
 }
 
 // TODO Write tests for the docker provisioner (using a dummy image and checking with the API)
@@ -0,0 +1,49 @@
+/// State of a local image after an attempt to pull its latest version
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ImageRefreshOutcome {
+    /// Image is not available locally
+    Missing,
+    /// A different version of the image is available locally
+    Changed,
+    /// Local image has not been altered
+    Unchanged,
+}
+
+impl ImageRefreshOutcome {
+    /// Compares the local image identifiers (digests) from before and after a pull
+    pub fn classify(previous: Option<&str>, current: Option<&str>) -> Self {
+        match current {
+            None => Self::Missing,
+            Some(current) if previous != Some(current) => Self::Changed,
+            Some(_) => Self::Unchanged,
+        }
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    #[test]
+    fn detect_digest_changes() {
+        use ImageRefreshOutcome::*;
+
+        assert_eq!(ImageRefreshOutcome::classify(None, None), Missing);
+        assert_eq!(
+            ImageRefreshOutcome::classify(Some("sha256:a"), None),
+            Missing
+        );
+        assert_eq!(
+            ImageRefreshOutcome::classify(None, Some("sha256:a")),
+            Changed
+        );
+        assert_eq!(
+            ImageRefreshOutcome::classify(Some("sha256:a"), Some("sha256:b")),
+            Changed
+        );
+        assert_eq!(
+            ImageRefreshOutcome::classify(Some("sha256:a"), Some("sha256:a")),
+            Unchanged
+        );
+    }
+}
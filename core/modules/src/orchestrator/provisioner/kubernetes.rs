@@ -1,9 +1,10 @@
-use super::SessionProvisioner;
+use super::{requested_screen_resolution, ScreenResolutionError, SessionProvisioner};
 use super::{CONTAINER_SESSION_ID_LABEL, PROVISIONER_INSTANCE_LABEL};
 use async_trait::async_trait;
+use domain::container::catalog::ResourceLimits;
 use domain::container::ContainerImageSet;
 use domain::event::{ProvisionedSessionMetadata, SessionIdentifier};
-use domain::webdriver::RawCapabilitiesRequest;
+use domain::webdriver::{RawCapabilitiesRequest, ScreenResolution};
 use k8s_openapi::{api::batch::v1::Job, Resource};
 use kube::api::{DeleteParams, ListParams, PropagationPolicy};
 use kube::{
@@ -14,6 +15,7 @@ use kube::{
 use library::helpers::{load_config, replace_config_variable};
 use library::{BoxedError, EmptyResult};
 use serde::{de::DeserializeOwned, ser::Serialize};
+use serde_yaml::{Mapping, Value as YamlValue};
 use std::fmt::Debug;
 use std::str::FromStr;
 use thiserror::Error;
@@ -36,6 +38,12 @@ enum KubernetesProvisionerError {
 
     #[error("invalid capabilities")]
     InvalidCapabilities(#[from] serde_json::Error),
+
+    #[error("job template contains no container")]
+    JobTemplateContainerMissing,
+
+    #[error("unavailable screen resolution")]
+    UnavailableScreenResolution(#[from] ScreenResolutionError),
 }
 
 /// Implementation based on [Kubernetes Jobs](https://kubernetes.io/docs/concepts/workloads/controllers/job/)
@@ -43,12 +51,17 @@ pub struct KubernetesProvisioner {
     namespace: String,
     images: ContainerImageSet,
     instance: String,
+    screen_resolutions: Vec<ScreenResolution>,
 }
 
 impl KubernetesProvisioner {
     /// Creates a new instance with the provided images, connecting to the default API endpoint drawn from the environment.
     /// By default, it uses the `webgrid` namespace unless the `NAMESPACE` variable is set (which it is by default in K8s pods).
-    pub fn new(images: ContainerImageSet, instance: String) -> Self {
+    pub fn new(
+        images: ContainerImageSet,
+        instance: String,
+        screen_resolutions: Vec<ScreenResolution>,
+    ) -> Self {
         if images.is_empty() {
             warn!("No images provided to provisioner. It won't be able to launch any sessions!");
         }
@@ -59,6 +72,7 @@ impl KubernetesProvisioner {
             namespace,
             images,
             instance,
+            screen_resolutions,
         }
     }
 
@@ -144,12 +158,71 @@ impl KubernetesProvisioner {
         Ok(())
     }
 
+    /// Retrieves the first container in the job template
+    fn template_container(job: &mut YamlValue) -> Result<&mut Mapping, KubernetesProvisionerError> {
+        job.get_mut("spec")
+            .and_then(|spec| spec.get_mut("template"))
+            .and_then(|template| template.get_mut("spec"))
+            .and_then(|spec| spec.get_mut("containers"))
+            .and_then(|containers| containers.get_mut(0))
+            .and_then(YamlValue::as_mapping_mut)
+            .ok_or(KubernetesProvisionerError::JobTemplateContainerMissing)
+    }
+
+    /// Overwrites the resource limits of the first container in the job template
+    fn apply_resource_limits(
+        job: &mut YamlValue,
+        resources: &ResourceLimits,
+    ) -> Result<(), KubernetesProvisionerError> {
+        if resources.cpu.is_none() && resources.memory.is_none() {
+            return Ok(());
+        }
+
+        let container = Self::template_container(job)?;
+        let limits = child_mapping(child_mapping(container, "resources"), "limits");
+
+        if let Some(cpu) = &resources.cpu {
+            limits.insert("cpu".into(), cpu.as_str().into());
+        }
+
+        if let Some(memory) = &resources.memory {
+            limits.insert("memory".into(), memory.as_str().into());
+        }
+
+        Ok(())
+    }
+
+    /// Overwrites the `RESOLUTION` variable of the first container in the job template
+    fn apply_screen_resolution(
+        job: &mut YamlValue,
+        resolution: ScreenResolution,
+    ) -> Result<(), KubernetesProvisionerError> {
+        let container = Self::template_container(job)?;
+        let key = YamlValue::from("env");
+
+        if !matches!(container.get(&key), Some(YamlValue::Sequence(_))) {
+            container.insert(key.clone(), YamlValue::Sequence(Vec::new()));
+        }
+
+        if let Some(YamlValue::Sequence(env)) = container.get_mut(&key) {
+            env.retain(|variable| variable.get("name") != Some(&YamlValue::from("RESOLUTION")));
+
+            let mut variable = Mapping::new();
+            variable.insert("name".into(), "RESOLUTION".into());
+            variable.insert("value".into(), resolution.to_string().into());
+            env.push(YamlValue::Mapping(variable));
+        }
+
+        Ok(())
+    }
+
     async fn create_job(
         &self,
         session_id: &SessionIdentifier,
         raw_capabilities: &RawCapabilitiesRequest,
     ) -> Result<ProvisionedSessionMetadata, KubernetesProvisionerError> {
         let request = raw_capabilities.parse()?;
+        let resolution = requested_screen_resolution(&request, &self.screen_resolutions)?;
         let image = self
             .images
             .match_against_capabilities(request)
@@ -157,6 +230,8 @@ impl KubernetesProvisioner {
 
         debug!(?session_id, image = ?image.identifier, browser = ?image.browser, "Creating job");
 
+        let capabilities = image.inject_default_capabilities(raw_capabilities)?;
+
         let name = Self::generate_name(session_id);
         let mut job_yaml = load_config("job.yaml")?;
         job_yaml = replace_config_variable(job_yaml, "job_name", &name);
@@ -164,11 +239,18 @@ impl KubernetesProvisioner {
         job_yaml = replace_config_variable(job_yaml, "image_name", &image.identifier);
         job_yaml =
             replace_config_variable(job_yaml, "provisioner_instance", &self.instance.to_string());
-        job_yaml = replace_config_variable(job_yaml, "capabilities", raw_capabilities.as_str());
+        job_yaml = replace_config_variable(job_yaml, "capabilities", capabilities.as_str());
 
         trace!("Job YAML {}", job_yaml);
 
-        let job: Job = serde_yaml::from_str(&job_yaml)?;
+        let mut job_value: YamlValue = serde_yaml::from_str(&job_yaml)?;
+        Self::apply_resource_limits(&mut job_value, &image.resources)?;
+
+        if let Some(resolution) = resolution {
+            Self::apply_screen_resolution(&mut job_value, resolution)?;
+        }
+
+        let job: Job = serde_yaml::from_value(job_value)?;
         let _resource = self.create_resource(&job).await?;
 
         // TODO Append more meaningful information
@@ -244,6 +326,20 @@ impl SessionProvisioner for KubernetesProvisioner {
     }
 }
 
+/// Retrieves a nested mapping by key, replacing values of any other type with an empty mapping
+fn child_mapping<'a>(mapping: &'a mut Mapping, key: &str) -> &'a mut Mapping {
+    let key = YamlValue::from(key);
+
+    if !matches!(mapping.get(&key), Some(YamlValue::Mapping(_))) {
+        mapping.insert(key.clone(), YamlValue::Mapping(Mapping::new()));
+    }
+
+    match mapping.get_mut(&key) {
+        Some(YamlValue::Mapping(child)) => child,
+        _ => unreachable!("mapping has been inserted above"),
+    }
+}
+
 // TODO Write tests for the K8s provisioner (using a dummy image and checking with the API)
 
 /// Helper methods for the Job type
@@ -2,13 +2,16 @@
 
 use async_trait::async_trait;
 use domain::event::{ProvisionedSessionMetadata, SessionIdentifier};
-use domain::webdriver::RawCapabilitiesRequest;
+use domain::webdriver::{CapabilitiesRequest, RawCapabilitiesRequest, ScreenResolution};
 use library::{BoxedError, EmptyResult};
+use thiserror::Error;
 
 mod docker;
+mod images;
 mod kubernetes;
 
 pub use docker::DockerProvisioner;
+pub use images::ImageRefreshOutcome;
 pub use kubernetes::KubernetesProvisioner;
 
 /// Label defining the instance which manages the container
@@ -16,6 +19,38 @@ pub const PROVISIONER_INSTANCE_LABEL: &str = "dev.webgrid/provisioner.instance";
 /// Label defining the session id the container is bound to
 pub const CONTAINER_SESSION_ID_LABEL: &str = "dev.webgrid/session.id";
 
+/// Error thrown when a session requests an unavailable screen resolution
+#[derive(Debug, Error)]
+pub enum ScreenResolutionError {
+    /// The requested value is not formatted like `1366x768`
+    #[error("invalid screen resolution {0:?}")]
+    Invalid(String),
+    /// The requested resolution is not in the list of allowed resolutions
+    #[error("screen resolution {0} is not allowed")]
+    NotAllowed(ScreenResolution),
+}
+
+/// Screen resolution requested through the `screenResolution` WebGrid option, if it is allowed
+fn requested_screen_resolution(
+    request: &CapabilitiesRequest,
+    allowed: &[ScreenResolution],
+) -> Result<Option<ScreenResolution>, ScreenResolutionError> {
+    let raw = match request.screen_resolution() {
+        Some(raw) => raw,
+        None => return Ok(None),
+    };
+
+    let resolution: ScreenResolution = raw
+        .parse()
+        .map_err(|_| ScreenResolutionError::Invalid(raw.to_owned()))?;
+
+    if allowed.contains(&resolution) {
+        Ok(Some(resolution))
+    } else {
+        Err(ScreenResolutionError::NotAllowed(resolution))
+    }
+}
+
 /// Intermediary providing indirect access to hardware on which sessions can run
 #[async_trait]
 pub trait SessionProvisioner {
@@ -31,6 +66,14 @@ pub trait SessionProvisioner {
 
     /// Instructs the provisioner to purge orphaned or dead resources
     async fn purge_terminated(&self) -> EmptyResult;
+
+    /// Makes sure that the latest version of every image is available locally.
+    /// Returns an error if at least one image is not available afterwards.
+    ///
+    /// Provisioners which can not influence image availability may skip this.
+    async fn refresh_images(&self) -> EmptyResult {
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -52,4 +95,49 @@ impl<'a> SessionProvisioner for Box<dyn SessionProvisioner + Send + Sync + 'a> {
     async fn purge_terminated(&self) -> library::EmptyResult {
         self.as_ref().purge_terminated().await
     }
+
+    async fn refresh_images(&self) -> library::EmptyResult {
+        self.as_ref().refresh_images().await
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    fn request(resolution: &str) -> CapabilitiesRequest {
+        serde_json::from_str(&format!(
+            r#"{{"alwaysMatch":{{"webgrid:options":{{"screenResolution":"{}"}}}}}}"#,
+            resolution
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn accept_allowed_screen_resolution() {
+        let allowed = vec!["1366x768".parse().unwrap()];
+
+        assert_eq!(
+            requested_screen_resolution(&request("1366x768"), &allowed).unwrap(),
+            Some(allowed[0])
+        );
+        assert_eq!(
+            requested_screen_resolution(&CapabilitiesRequest::default(), &allowed).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn reject_unavailable_screen_resolution() {
+        let allowed = vec!["1366x768".parse().unwrap()];
+
+        assert!(matches!(
+            requested_screen_resolution(&request("2560x1440"), &allowed),
+            Err(ScreenResolutionError::NotAllowed(_))
+        ));
+        assert!(matches!(
+            requested_screen_resolution(&request("huge"), &allowed),
+            Err(ScreenResolutionError::Invalid(_))
+        ));
+    }
 }
@@ -0,0 +1,250 @@
+use super::ProvisioningState;
+use async_trait::async_trait;
+use jatsl::{Job, JobManager};
+use library::{AccumulatedPerformanceMetrics, EmptyResult, HostResources, PerformanceMonitor};
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+/// Label of the profiling entry covering the whole session container
+const CGROUP_PROFILING_LABEL: &str = "cgroup";
+
+/// Weight of a new measurement in the moving average of the session footprint
+const FOOTPRINT_SMOOTHING: f64 = 0.2;
+
+/// Resources consumed by a single session
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SessionFootprint {
+    /// Peak resident memory in bytes
+    pub memory: f64,
+    /// Average number of CPUs in use
+    pub cpu: f64,
+}
+
+impl SessionFootprint {
+    /// Derives the footprint from the profiling data of a terminated session
+    ///
+    /// Prefers the measurements of the session container and falls back to the sum of all
+    /// profiled processes. Returns `None` if the session has not been profiled.
+    pub fn from_profiling_data(
+        data: &HashMap<String, AccumulatedPerformanceMetrics>,
+    ) -> Option<Self> {
+        let metrics = match data.get(CGROUP_PROFILING_LABEL) {
+            Some(cgroup) => vec![cgroup],
+            None => data.values().collect(),
+        };
+
+        let memory = metrics.iter().map(|m| m.memory_rss_max).sum::<u64>() as f64;
+        let cpu_time = metrics
+            .iter()
+            .map(|m| m.cpu_time_usr + m.cpu_time_sys)
+            .sum::<f64>();
+        let wall_time = metrics.iter().map(|m| m.wall_time).fold(0.0, f64::max);
+
+        if memory > 0.0 && wall_time > 0.0 {
+            Some(Self {
+                memory,
+                cpu: cpu_time / wall_time,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Number of sessions the host can run in total, given that `running` sessions already consume resources
+    pub fn sessions_supported_by(&self, host: &HostResources, running: usize) -> usize {
+        let by_memory = running + (host.memory_available as f64 / self.memory).floor() as usize;
+
+        if self.cpu > 0.0 {
+            let by_cpu = (host.cpu_count as f64 / self.cpu).floor() as usize;
+            by_memory.min(by_cpu)
+        } else {
+            by_memory
+        }
+    }
+}
+
+/// Moving average of the [`SessionFootprint`] across terminated sessions
+#[derive(Clone, Default)]
+pub struct FootprintTracker {
+    footprint: Arc<Mutex<Option<SessionFootprint>>>,
+}
+
+impl FootprintTracker {
+    /// Incorporates the footprint of a terminated session
+    pub async fn record(&self, sample: SessionFootprint) {
+        let mut footprint = self.footprint.lock().await;
+
+        *footprint = Some(match *footprint {
+            Some(average) => SessionFootprint {
+                memory: average.memory + FOOTPRINT_SMOOTHING * (sample.memory - average.memory),
+                cpu: average.cpu + FOOTPRINT_SMOOTHING * (sample.cpu - average.cpu),
+            },
+            None => sample,
+        });
+    }
+
+    /// Current estimate, if any session has been measured yet
+    pub async fn current(&self) -> Option<SessionFootprint> {
+        *self.footprint.lock().await
+    }
+}
+
+/// Periodically adapts the number of permits to the resources available on the host
+///
+/// The capacity is derived from the available memory and CPUs of the host in relation to the
+/// [`SessionFootprint`] measured by profiling terminated sessions. Until the first measurement
+/// arrives, the capacity remains unchanged.
+pub struct AdaptiveCapacityService {
+    state: ProvisioningState,
+    footprint: FootprintTracker,
+    bounds: RangeInclusive<usize>,
+    interval: Duration,
+}
+
+impl AdaptiveCapacityService {
+    pub fn new(
+        state: ProvisioningState,
+        footprint: FootprintTracker,
+        bounds: RangeInclusive<usize>,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            state,
+            footprint,
+            bounds,
+            interval,
+        }
+    }
+
+    async fn adapt(&self) -> EmptyResult {
+        let current = self.state.capacity().await;
+
+        let target = match self.footprint.current().await {
+            Some(footprint) => {
+                let host = PerformanceMonitor::sample_host().await?;
+                let running = self.state.session_count().await;
+                debug!(?footprint, ?host, running, "Sampled host resources");
+                footprint.sessions_supported_by(&host, running)
+            }
+            None => current,
+        };
+
+        let target = target.clamp(*self.bounds.start(), *self.bounds.end());
+
+        if target != current {
+            let capacity = self.state.set_capacity(target).await;
+            info!(previous = current, target, capacity, "Adjusted capacity");
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Job for AdaptiveCapacityService {
+    const NAME: &'static str = module_path!();
+
+    async fn execute(&self, manager: JobManager) -> EmptyResult {
+        manager.ready().await;
+
+        loop {
+            if let Err(error) = self.adapt().await {
+                warn!(?error, "Failed to adapt capacity");
+            }
+
+            sleep(self.interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+
+    fn metrics(memory: u64, cpu_time: f64, wall_time: f64) -> AccumulatedPerformanceMetrics {
+        let mut metrics = AccumulatedPerformanceMetrics::default();
+        metrics.memory_rss_max = memory;
+        metrics.cpu_time_usr = cpu_time;
+        metrics.wall_time = wall_time;
+        metrics
+    }
+
+    #[test]
+    fn prefer_cgroup_measurements() {
+        let mut data = HashMap::new();
+        data.insert("cgroup".to_owned(), metrics(1_000, 30.0, 60.0));
+        data.insert("webdriver".to_owned(), metrics(500, 10.0, 60.0));
+
+        assert_eq!(
+            SessionFootprint::from_profiling_data(&data),
+            Some(SessionFootprint {
+                memory: 1_000.0,
+                cpu: 0.5
+            })
+        );
+    }
+
+    #[test]
+    fn sum_process_measurements() {
+        let mut data = HashMap::new();
+        data.insert("webdriver".to_owned(), metrics(500, 10.0, 40.0));
+        data.insert("browser".to_owned(), metrics(1_500, 30.0, 20.0));
+
+        assert_eq!(
+            SessionFootprint::from_profiling_data(&data),
+            Some(SessionFootprint {
+                memory: 2_000.0,
+                cpu: 1.0
+            })
+        );
+        assert_eq!(SessionFootprint::from_profiling_data(&HashMap::new()), None);
+    }
+
+    #[test]
+    fn limit_sessions_by_scarcest_resource() {
+        let footprint = SessionFootprint {
+            memory: 1_000.0,
+            cpu: 0.5,
+        };
+        let host = HostResources {
+            memory_total: 16_000,
+            memory_available: 8_500,
+            cpu_count: 8,
+        };
+
+        assert_eq!(footprint.sessions_supported_by(&host, 2), 10);
+        assert_eq!(footprint.sessions_supported_by(&host, 10), 16);
+    }
+
+    #[tokio::test]
+    async fn smooth_footprint_measurements() {
+        let tracker = FootprintTracker::default();
+        assert_eq!(tracker.current().await, None);
+
+        tracker
+            .record(SessionFootprint {
+                memory: 1_000.0,
+                cpu: 1.0,
+            })
+            .await;
+        tracker
+            .record(SessionFootprint {
+                memory: 2_000.0,
+                cpu: 1.0,
+            })
+            .await;
+
+        assert_eq!(
+            tracker.current().await,
+            Some(SessionFootprint {
+                memory: 1_200.0,
+                cpu: 1.0
+            })
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use super::{super::provisioner::SessionProvisioner, ProvisioningState};
+use async_trait::async_trait;
+use harness::HeartStone;
+use jatsl::{Job, JobManager};
+use library::EmptyResult;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+use tracing::{debug, info, warn};
+
+/// Drains the provisioner upon receiving `SIGUSR1`
+///
+/// Once triggered, no new sessions are matched or provisioned and jobs still queued
+/// for this provisioner are handed back for rescheduling. The job then waits until
+/// all sessions managed by the provisioner have terminated or the deadline has been
+/// reached and kills the module heart afterwards.
+pub struct DrainService<S: SessionProvisioner> {
+    state: ProvisioningState,
+    provisioner: Arc<S>,
+    heart_stone: Mutex<HeartStone>,
+    interval: Duration,
+    deadline: Duration,
+}
+
+impl<S: SessionProvisioner> DrainService<S> {
+    pub fn new(
+        state: ProvisioningState,
+        provisioner: Arc<S>,
+        heart_stone: HeartStone,
+        interval: Duration,
+        deadline: Duration,
+    ) -> Self {
+        Self {
+            state,
+            provisioner,
+            heart_stone: Mutex::new(heart_stone),
+            interval,
+            deadline,
+        }
+    }
+}
+
+#[async_trait]
+impl<S> Job for DrainService<S>
+where
+    S: SessionProvisioner + Send + Sync,
+{
+    const NAME: &'static str = module_path!();
+
+    async fn execute(&self, manager: JobManager) -> EmptyResult {
+        let mut drain_signal = signal(SignalKind::user_defined1())?;
+        manager.ready().await;
+
+        drain_signal.recv().await;
+        info!("Draining provisioner");
+        self.state.start_draining();
+
+        let start = Instant::now();
+        loop {
+            let alive_count = self.provisioner.alive_sessions().await?.len();
+
+            if alive_count == 0 {
+                info!("All sessions have terminated, provisioner is drained");
+                break;
+            } else if start.elapsed() > self.deadline {
+                warn!(
+                    alive_count,
+                    "Drain deadline exceeded, leaving sessions behind"
+                );
+                break;
+            }
+
+            debug!(alive_count, "Waiting for sessions to terminate");
+            sleep(self.interval).await;
+        }
+
+        self.heart_stone
+            .lock()
+            .await
+            .kill("Provisioner drained".into())
+            .await;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use async_trait::async_trait;
+use domain::event::{ProvisionerHeartbeatNotification, ProvisionerIdentifier};
+use harness::RedisCommunicationFactory;
+use jatsl::{Job, JobManager};
+use library::communication::event::NotificationPublisher;
+use library::communication::CommunicationFactory;
+use library::EmptyResult;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{trace, warn};
+
+/// Periodically announces that the provisioner is alive
+///
+/// Publishes:
+/// - [`ProvisionerHeartbeatNotification`]
+pub struct HeartbeatService {
+    redis_url: String,
+    provisioner: ProvisionerIdentifier,
+    interval: Duration,
+}
+
+impl HeartbeatService {
+    pub fn new(redis_url: String, provisioner: ProvisionerIdentifier, interval: Duration) -> Self {
+        Self {
+            redis_url,
+            provisioner,
+            interval,
+        }
+    }
+}
+
+#[async_trait]
+impl Job for HeartbeatService {
+    const NAME: &'static str = module_path!();
+
+    async fn execute(&self, manager: JobManager) -> EmptyResult {
+        let manager = Arc::new(manager);
+        let factory = RedisCommunicationFactory::new(self.redis_url.clone(), manager.clone());
+        let publisher = factory.notification_publisher();
+        let notification = ProvisionerHeartbeatNotification {
+            provisioner: self.provisioner.clone(),
+        };
+
+        manager.ready().await;
+
+        loop {
+            trace!("Publishing heartbeat");
+            if let Err(error) = publisher.publish(&notification).await {
+                warn!(?error, "Failed to publish heartbeat");
+            }
+
+            sleep(self.interval).await;
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use super::super::provisioner::SessionProvisioner;
+use async_trait::async_trait;
+use jatsl::{Job, JobManager};
+use library::EmptyResult;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::warn;
+
+/// Blocks until every image used by the provisioner is available, retrying in the given interval
+pub async fn await_images<S: SessionProvisioner>(provisioner: &S, retry_interval: Duration) {
+    while let Err(error) = provisioner.refresh_images().await {
+        warn!(?error, "Images are not available yet, retrying");
+        sleep(retry_interval).await;
+    }
+}
+
+/// Periodically pulls new versions of the images used by the provisioner
+pub struct ImageRefreshService<S: SessionProvisioner> {
+    provisioner: Arc<S>,
+    interval: Duration,
+}
+
+impl<S: SessionProvisioner> ImageRefreshService<S> {
+    pub fn new(provisioner: Arc<S>, interval: Duration) -> Self {
+        Self {
+            provisioner,
+            interval,
+        }
+    }
+}
+
+#[async_trait]
+impl<S> Job for ImageRefreshService<S>
+where
+    S: SessionProvisioner + Send + Sync,
+{
+    const NAME: &'static str = module_path!();
+
+    async fn execute(&self, manager: JobManager) -> EmptyResult {
+        manager.ready().await;
+
+        loop {
+            sleep(self.interval).await;
+
+            if let Err(error) = self.provisioner.refresh_images().await {
+                warn!(?error, "Failed to refresh images");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod does {
+    use super::*;
+    use domain::event::{ProvisionedSessionMetadata, SessionIdentifier};
+    use domain::webdriver::RawCapabilitiesRequest;
+    use library::BoxedError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Provisioner whose images become available after a number of refreshes
+    struct DelayedImageProvisioner {
+        refreshes: AtomicUsize,
+        required_refreshes: usize,
+    }
+
+    #[async_trait]
+    impl SessionProvisioner for DelayedImageProvisioner {
+        async fn provision(
+            &self,
+            _session_id: &SessionIdentifier,
+            _capabilities: &RawCapabilitiesRequest,
+        ) -> Result<ProvisionedSessionMetadata, BoxedError> {
+            unimplemented!()
+        }
+
+        async fn alive_sessions(&self) -> Result<Vec<SessionIdentifier>, BoxedError> {
+            Ok(Vec::new())
+        }
+
+        async fn purge_terminated(&self) -> EmptyResult {
+            Ok(())
+        }
+
+        async fn refresh_images(&self) -> EmptyResult {
+            let refreshes = self.refreshes.fetch_add(1, Ordering::SeqCst) + 1;
+
+            if refreshes < self.required_refreshes {
+                Err("images missing".into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn wait_until_images_are_present() {
+        let provisioner = DelayedImageProvisioner {
+            refreshes: AtomicUsize::new(0),
+            required_refreshes: 3,
+        };
+
+        await_images(&provisioner, Duration::from_millis(1)).await;
+
+        assert_eq!(provisioner.refreshes.load(Ordering::SeqCst), 3);
+    }
+}
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use super::super::ProvisioningState;
 use super::MatchingStrategy;
 use async_trait::async_trait;
 use domain::event::ProvisionerIdentifier;
@@ -12,6 +13,9 @@ use tracing::trace;
 
 /// Matches a provisioner using a [`MatchingStrategy`]
 ///
+/// While the provisioner is draining, all requests are ignored. Requests for images which
+/// have reached their concurrency limit are ignored as well, leaving them to other provisioners.
+///
 /// Consumes:
 /// - [`ProvisionerMatchRequest`]
 ///
@@ -20,6 +24,7 @@ use tracing::trace;
 pub struct ProvisionerMatchingService<M: MatchingStrategy> {
     strategy: Arc<M>,
     provisioner: ProvisionerIdentifier,
+    state: ProvisioningState,
 }
 
 impl<F, M> Service<F> for ProvisionerMatchingService<M>
@@ -34,13 +39,14 @@ where
         <F as CommunicationFactory>::ResponsePublisher,
     >;
 
-    type Config = (ProvisionerIdentifier, Arc<M>);
+    type Config = (ProvisionerIdentifier, Arc<M>, ProvisioningState);
 
     fn instantiate(factory: F, config: &Self::Config) -> Self::Instance {
         let publisher = factory.response_publisher();
         let processor = Self {
             provisioner: config.0.clone(),
             strategy: config.1.clone(),
+            state: config.2.clone(),
         };
 
         Responder::new(processor, publisher)
@@ -58,6 +64,16 @@ where
         &self,
         request: Self::Request,
     ) -> Result<Option<<Self::Request as Request>::Response>, BoxedError> {
+        if self.state.is_draining() {
+            trace!(?request.capabilities, "Ignoring request while draining");
+            return Ok(None);
+        }
+
+        if !self.state.has_image_capacity(request.capabilities.clone()) {
+            trace!(?request.capabilities, "Ignoring request for image without capacity");
+            return Ok(None);
+        }
+
         trace!(?request.capabilities, "Matching request");
         let response = if self.strategy.matches(request.capabilities) {
             Some(ProvisionerMatchResponse {
@@ -74,7 +90,9 @@ where
 #[cfg(test)]
 mod does {
     use super::*;
-    use domain::webdriver::{Capabilities, CapabilitiesRequest};
+    use domain::container::ContainerImageSet;
+    use domain::webdriver::{Capabilities, CapabilitiesRequest, RawCapabilitiesRequest};
+    use uuid::Uuid;
 
     impl<M> ProvisionerMatchingService<M>
     where
@@ -85,6 +103,7 @@ mod does {
             Self {
                 strategy: Arc::new(strategy),
                 provisioner,
+                state: ProvisioningState::new(1),
             }
         }
     }
@@ -117,6 +136,40 @@ mod does {
         assert!(processor.maybe_process(request).await.unwrap().is_none());
     }
 
+    #[tokio::test]
+    async fn ignore_request_while_draining() {
+        let strategy = BooleanMatchingStrategy(true);
+        let processor = ProvisionerMatchingService::new("some-id".into(), strategy);
+        let request = ProvisionerMatchRequest::new(CapabilitiesRequest {
+            first_match: None,
+            always_match: None,
+        });
+
+        processor.state.start_draining();
+        assert!(processor.maybe_process(request).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn ignore_request_for_exhausted_image() {
+        let images = ContainerImageSet::from_catalog(
+            "images: [{ image: firefox, browser: { name: firefox, version: '102.0' }, maxSessions: 1 }]",
+        )
+        .unwrap();
+        let capabilities = RawCapabilitiesRequest::new("{}".into());
+
+        let mut processor =
+            ProvisionerMatchingService::new("some-id".into(), BooleanMatchingStrategy(true));
+        processor.state = ProvisioningState::new(2).with_image_limits(images);
+        processor
+            .state
+            .acquire_permit(Uuid::new_v4(), &capabilities)
+            .await
+            .unwrap();
+
+        let request = ProvisionerMatchRequest::new(capabilities.parse().unwrap());
+        assert!(processor.maybe_process(request).await.unwrap().is_none());
+    }
+
     #[tokio::test]
     async fn reply_to_matching_request() {
         let provisioner: String = "some-id".into();
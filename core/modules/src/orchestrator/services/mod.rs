@@ -1,11 +1,19 @@
 //! Services to provision new browsers
 
+mod capacity;
+mod drain;
+mod heartbeat;
+mod images;
 mod matching;
 mod provisioning;
 mod state;
 mod sync;
 mod termination;
 
+pub use capacity::{AdaptiveCapacityService, FootprintTracker, SessionFootprint};
+pub use drain::DrainService;
+pub use heartbeat::HeartbeatService;
+pub use images::{await_images, ImageRefreshService};
 pub use matching::{ContainerMatchingStrategy, MatchingStrategy, ProvisionerMatchingService};
 pub use provisioning::ProvisioningService;
 pub use state::ProvisioningState;
@@ -1,8 +1,9 @@
 use super::{super::provisioner::SessionProvisioner, ProvisioningState};
 use async_trait::async_trait;
 use domain::event::{
-    ProvisionedSessionMetadata, ProvisioningJobAssignedNotification,
-    SessionProvisionedNotification, SessionTerminatedNotification,
+    ProvisionedSessionMetadata, ProvisionerIdentifier, ProvisioningJobAssignedNotification,
+    ProvisioningJobReturnedNotification, SessionProvisionedNotification,
+    SessionTerminatedNotification,
 };
 use harness::Service;
 use library::communication::event::{Consumer, NotificationFrame, NotificationPublisher};
@@ -22,12 +23,19 @@ enum ProvisioningServiceError {
     #[error("unable to acquire permit")]
     NoPermit(#[from] AcquireError),
 
+    #[error("provisioner is draining")]
+    Draining,
+
     #[error("provisioning request failed")]
     RequestFailure(#[from] RequestError),
 }
 
 /// Provisions new sessions using an underlying [`SessionProvisioner`]
+///
+/// Jobs received while the provisioner is draining are handed back
+/// by publishing a [`ProvisioningJobReturnedNotification`].
 pub struct ProvisioningService<S: SessionProvisioner, F: CommunicationFactory> {
+    id: ProvisionerIdentifier,
     state: ProvisioningState,
     provisioner: Arc<S>,
     publisher: <F as CommunicationFactory>::NotificationPublisher,
@@ -40,12 +48,13 @@ where
 {
     const NAME: &'static str = "ProvisioningService";
     type Instance = ProvisioningService<S, F>;
-    type Config = (ProvisioningState, Arc<S>);
+    type Config = (ProvisionerIdentifier, ProvisioningState, Arc<S>);
 
     fn instantiate(factory: F, config: &Self::Config) -> Self::Instance {
         Self {
-            state: config.0.clone(),
-            provisioner: config.1.clone(),
+            id: config.0.clone(),
+            state: config.1.clone(),
+            provisioner: config.2.clone(),
             publisher: factory.notification_publisher(),
         }
     }
@@ -63,7 +72,13 @@ where
     ) -> Result<ProvisionedSessionMetadata, ProvisioningServiceError> {
         // Get a permit so we don't deploy infinitely many sessions
         debug!("Acquiring permit");
-        self.state.acquire_permit(notification.session_id).await?;
+        if !self
+            .state
+            .acquire_permit(notification.session_id, &notification.capabilities)
+            .await?
+        {
+            return Err(ProvisioningServiceError::Draining);
+        }
 
         // Provision the session
         debug!("Provisioning session");
@@ -89,6 +104,17 @@ where
     async fn consume(&self, notification: NotificationFrame<Self::Notification>) -> EmptyResult {
         match self.provision(&notification).await {
             Err(ProvisioningServiceError::RequestFailure(e)) => Err(e.into()),
+            Err(ProvisioningServiceError::Draining) => {
+                // Hand the job back so it can be scheduled somewhere else
+                debug!(id = ?notification.session_id, "Returning job while draining");
+                let returned = ProvisioningJobReturnedNotification {
+                    session_id: notification.session_id,
+                    capabilities: notification.capabilities.clone(),
+                    provisioner: self.id.clone(),
+                };
+
+                self.publisher.publish(&returned).await
+            }
             Err(e) => {
                 // Tell everybody that we have failed them :(
                 let terminated_notification =
@@ -126,6 +152,8 @@ mod does {
         static ref SESSION_ID: Uuid = Uuid::new_v4();
     }
 
+    const PROVISIONER_ID: &str = "some-provisioner";
+
     #[derive(Debug, Error)]
     enum MockError {
         #[error("some error")]
@@ -171,8 +199,18 @@ mod does {
     ) where
         F: Fn() -> Result<ProvisionedSessionMetadata, BoxedError> + Send + Sync,
     {
-        let state = ProvisioningState::new(1);
-        let service = ProvisioningService::instantiate(factory, &(state, provisioner));
+        run_with_state(ProvisioningState::new(1), provisioner, factory).await;
+    }
+
+    async fn run_with_state<F>(
+        state: ProvisioningState,
+        provisioner: Arc<MockProvisioner<F>>,
+        factory: impl CommunicationFactory + Send + Sync,
+    ) where
+        F: Fn() -> Result<ProvisionedSessionMetadata, BoxedError> + Send + Sync,
+    {
+        let config = (PROVISIONER_ID.to_owned(), state, provisioner);
+        let service = ProvisioningService::instantiate(factory, &config);
         let notification = ProvisioningJobAssignedNotification {
             session_id: *SESSION_ID,
             capabilities: RawCapabilitiesRequest::new("{}".into()),
@@ -214,4 +252,22 @@ mod does {
 
         run_with_provisioner(provisioner, factory).await;
     }
+
+    #[tokio::test]
+    async fn return_job_while_draining() {
+        let expected = ProvisioningJobReturnedNotification {
+            session_id: *SESSION_ID,
+            capabilities: RawCapabilitiesRequest::new("{}".into()),
+            provisioner: PROVISIONER_ID.into(),
+        };
+
+        let state = ProvisioningState::new(1);
+        state.start_draining();
+
+        let provisioner = MockProvisioner::new(|| unreachable!());
+        let factory = MockCommunicationFactory::default();
+        factory.expect(&expected);
+
+        run_with_state(state, provisioner, factory).await;
+    }
 }
@@ -1,15 +1,36 @@
+use domain::container::ContainerImageSet;
 use domain::event::SessionIdentifier;
+use domain::webdriver::{CapabilitiesRequest, RawCapabilitiesRequest};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
-use tokio::sync::{AcquireError, Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio::sync::{AcquireError, Mutex, Notify, OwnedSemaphorePermit, Semaphore};
+
+/// Permits held by a single session, returned to their respective pools when dropped
+struct SessionPermits {
+    _global: OwnedSemaphorePermit,
+    _image: Option<OwnedSemaphorePermit>,
+}
 
 /// Keeps track of deployed sessions and manages permits for new ones
 #[derive(Clone)]
 pub struct ProvisioningState {
     /// Provides permits for new sessions
     semaphore: Arc<Semaphore>,
+    /// Number of permits the semaphore has been provided with in total
+    total_permits: Arc<AtomicUsize>,
+    /// Permits taken out of circulation to reduce the capacity at runtime
+    withheld: Arc<Mutex<Vec<OwnedSemaphorePermit>>>,
+    /// Images used to determine which pool a session belongs to
+    images: Option<ContainerImageSet>,
+    /// Provides permits for sessions of images with a concurrency limit, keyed by image identifier
+    image_semaphores: Arc<HashMap<String, Arc<Semaphore>>>,
     /// Holds the semaphore permits held by each session managed by this provisioner
-    managed: Arc<Mutex<HashMap<SessionIdentifier, OwnedSemaphorePermit>>>,
+    managed: Arc<Mutex<HashMap<SessionIdentifier, SessionPermits>>>,
+    /// Whether the provisioner is being drained and thus no longer accepts new sessions
+    draining: Arc<AtomicBool>,
+    /// Wakes up tasks waiting for a permit once draining starts
+    drain: Arc<Notify>,
 }
 
 impl ProvisioningState {
@@ -17,18 +38,149 @@ impl ProvisioningState {
     pub fn new(permits: usize) -> Self {
         Self {
             semaphore: Arc::new(Semaphore::new(permits)),
+            total_permits: Arc::new(AtomicUsize::new(permits)),
+            withheld: Arc::new(Mutex::new(Vec::new())),
+            images: None,
+            image_semaphores: Arc::new(HashMap::new()),
             managed: Arc::new(Mutex::new(HashMap::new())),
+            draining: Arc::new(AtomicBool::new(false)),
+            drain: Arc::new(Notify::new()),
+        }
+    }
+
+    /// Additionally limits the number of concurrent sessions per image to its `max_sessions`
+    ///
+    /// Sessions are assigned to the image the request would be [matched](ContainerImageSet::match_against_capabilities)
+    /// against. If an image is listed multiple times, the limit of the first definition is used.
+    pub fn with_image_limits(mut self, images: ContainerImageSet) -> Self {
+        let mut image_semaphores = HashMap::new();
+
+        for image in images.iter() {
+            if let Some(limit) = image.max_sessions {
+                image_semaphores
+                    .entry(image.identifier.clone())
+                    .or_insert_with(|| Arc::new(Semaphore::new(limit)));
+            }
         }
+
+        self.images = Some(images);
+        self.image_semaphores = Arc::new(image_semaphores);
+        self
+    }
+
+    /// Semaphore of the image a request would be provisioned with, if that image is limited
+    fn image_semaphore(&self, request: CapabilitiesRequest) -> Option<Arc<Semaphore>> {
+        let image = self.images.as_ref()?.match_against_capabilities(request)?;
+        self.image_semaphores.get(&image.identifier).cloned()
+    }
+
+    /// Whether the image a request would be provisioned with has a permit available
+    pub fn has_image_capacity(&self, request: CapabilitiesRequest) -> bool {
+        self.image_semaphore(request)
+            .map(|semaphore| semaphore.available_permits() > 0)
+            .unwrap_or(true)
     }
 
     /// Acquires a permit for a new session with the given identifier.
     /// If all permits have been used up, it waits asynchronously until one is released.
     ///
-    /// Internally, this process relies on a [`Semaphore`].
-    pub async fn acquire_permit(&self, session: SessionIdentifier) -> Result<(), AcquireError> {
-        let permit = self.semaphore.clone().acquire_owned().await?;
-        self.managed.lock().await.insert(session, permit);
-        Ok(())
+    /// Returns `false` without acquiring a permit if the provisioner is draining,
+    /// regardless of whether it started before or while waiting for a permit.
+    ///
+    /// Internally, this process relies on a [`Semaphore`]. When the image used for the given
+    /// capabilities has a concurrency limit, a permit from its own semaphore is acquired first.
+    pub async fn acquire_permit(
+        &self,
+        session: SessionIdentifier,
+        capabilities: &RawCapabilitiesRequest,
+    ) -> Result<bool, AcquireError> {
+        // Register interest before checking the flag so a concurrent drain can not slip through
+        let drained = self.drain.notified();
+
+        if self.is_draining() {
+            return Ok(false);
+        }
+
+        // Unparsable requests will be rejected by the provisioner later on
+        let image_semaphore = capabilities
+            .parse()
+            .ok()
+            .and_then(|request| self.image_semaphore(request));
+
+        let permits = async {
+            let image = match image_semaphore {
+                Some(semaphore) => Some(semaphore.acquire_owned().await?),
+                None => None,
+            };
+
+            let global = self.semaphore.clone().acquire_owned().await?;
+
+            Ok::<_, AcquireError>(SessionPermits {
+                _global: global,
+                _image: image,
+            })
+        };
+
+        tokio::select! {
+            permits = permits => {
+                self.managed.lock().await.insert(session, permits?);
+                Ok(true)
+            }
+            _ = drained => Ok(false),
+        }
+    }
+
+    /// Puts the provisioner into drain mode, rejecting all pending and future permit requests
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+        self.drain.notify_waiters();
+    }
+
+    /// Whether or not the provisioner is currently being drained
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Number of permits currently in circulation, regardless of whether they are in use
+    pub async fn capacity(&self) -> usize {
+        self.total_permits.load(Ordering::SeqCst) - self.withheld.lock().await.len()
+    }
+
+    /// Grows or shrinks the number of permits in circulation towards the given capacity
+    ///
+    /// Growing is instantaneous. Shrinking takes permits out of circulation which are currently
+    /// available, permits held by sessions can not be revoked. Returns the resulting capacity which
+    /// may thus still be above the requested one until further sessions terminate.
+    pub async fn set_capacity(&self, capacity: usize) -> usize {
+        let mut withheld = self.withheld.lock().await;
+        let current = self.total_permits.load(Ordering::SeqCst) - withheld.len();
+
+        if capacity > current {
+            let mut missing = capacity - current;
+
+            while missing > 0 && withheld.pop().is_some() {
+                missing -= 1;
+            }
+
+            if missing > 0 {
+                self.semaphore.add_permits(missing);
+                self.total_permits.fetch_add(missing, Ordering::SeqCst);
+            }
+        } else {
+            for _ in capacity..current {
+                match self.semaphore.clone().try_acquire_owned() {
+                    Ok(permit) => withheld.push(permit),
+                    Err(_) => break,
+                }
+            }
+        }
+
+        self.total_permits.load(Ordering::SeqCst) - withheld.len()
+    }
+
+    /// Number of sessions currently holding a permit
+    pub async fn session_count(&self) -> usize {
+        self.managed.lock().await.len()
     }
 
     /// Releases a permit held by a session with the given identifier.
@@ -38,6 +190,8 @@ impl ProvisioningState {
     }
 
     /// Releases all permits held by sessions that are not in the `alive_sessions` list passed in
+    ///
+    /// Permits are returned to both the global and, if applicable, the image specific pool.
     pub async fn release_dead_sessions(&self, alive_sessions: Vec<SessionIdentifier>) {
         let mut managed = self.managed.lock().await;
         let mut dead = Vec::new();
@@ -58,6 +212,14 @@ impl ProvisioningState {
     pub fn available_permits(&self) -> usize {
         self.semaphore.available_permits()
     }
+
+    /// Returns the number of currently available permits for a limited image
+    #[cfg(test)]
+    pub fn available_image_permits(&self, image: &str) -> Option<usize> {
+        self.image_semaphores
+            .get(image)
+            .map(|semaphore| semaphore.available_permits())
+    }
 }
 
 #[cfg(test)]
@@ -65,13 +227,43 @@ mod does {
     use super::*;
     use uuid::Uuid;
 
+    fn any_capabilities() -> RawCapabilitiesRequest {
+        RawCapabilitiesRequest::new("{}".into())
+    }
+
+    fn browser_capabilities(browser: &str) -> RawCapabilitiesRequest {
+        RawCapabilitiesRequest::new(format!(
+            "{{\"firstMatch\":[{{\"browserName\":\"{}\"}}]}}",
+            browser
+        ))
+    }
+
+    fn limited_state(permits: usize) -> ProvisioningState {
+        let images = ContainerImageSet::from_catalog(
+            r#"
+images:
+  - image: chrome
+    browser: { name: chrome, version: "114.0" }
+  - image: firefox
+    browser: { name: firefox, version: 102.0esr }
+    maxSessions: 1
+"#,
+        )
+        .unwrap();
+
+        ProvisioningState::new(permits).with_image_limits(images)
+    }
+
     #[tokio::test]
     async fn provide_the_correct_number_of_permits() {
         let permits = 10;
         let state = ProvisioningState::new(permits);
 
         for _ in 0..permits {
-            state.acquire_permit(Uuid::new_v4()).await.unwrap();
+            state
+                .acquire_permit(Uuid::new_v4(), &any_capabilities())
+                .await
+                .unwrap();
         }
 
         assert_eq!(state.available_permits(), 0);
@@ -82,7 +274,7 @@ mod does {
         let id = Uuid::new_v4();
         let state = ProvisioningState::new(1);
 
-        state.acquire_permit(id).await.unwrap();
+        state.acquire_permit(id, &any_capabilities()).await.unwrap();
         assert_eq!(state.available_permits(), 0);
 
         assert!(state.release_permit(&id).await.is_some());
@@ -95,11 +287,123 @@ mod does {
         let id2 = Uuid::new_v4();
         let state = ProvisioningState::new(2);
 
-        state.acquire_permit(id1).await.unwrap();
-        state.acquire_permit(id2).await.unwrap();
+        state
+            .acquire_permit(id1, &any_capabilities())
+            .await
+            .unwrap();
+        state
+            .acquire_permit(id2, &any_capabilities())
+            .await
+            .unwrap();
         assert_eq!(state.available_permits(), 0);
 
         state.release_dead_sessions(vec![id2]).await;
         assert_eq!(state.available_permits(), 1);
     }
+
+    #[tokio::test]
+    async fn reject_permits_while_draining() {
+        let state = ProvisioningState::new(1);
+        state.start_draining();
+
+        assert!(!state
+            .acquire_permit(Uuid::new_v4(), &any_capabilities())
+            .await
+            .unwrap());
+        assert_eq!(state.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn abort_pending_acquisition_when_draining() {
+        let state = ProvisioningState::new(1);
+        assert!(state
+            .acquire_permit(Uuid::new_v4(), &any_capabilities())
+            .await
+            .unwrap());
+
+        let pending = {
+            let state = state.clone();
+            tokio::spawn(async move {
+                state
+                    .acquire_permit(Uuid::new_v4(), &any_capabilities())
+                    .await
+            })
+        };
+
+        tokio::task::yield_now().await;
+        state.start_draining();
+
+        assert!(!pending.await.unwrap().unwrap());
+    }
+
+    #[tokio::test]
+    async fn limit_sessions_per_image() {
+        let state = limited_state(3);
+        let firefox = browser_capabilities("firefox");
+
+        assert!(state.has_image_capacity(firefox.parse().unwrap()));
+        assert!(state
+            .acquire_permit(Uuid::new_v4(), &firefox)
+            .await
+            .unwrap());
+        assert!(!state.has_image_capacity(firefox.parse().unwrap()));
+        assert_eq!(state.available_image_permits("firefox"), Some(0));
+
+        // Unlimited images may still use the remaining global permits
+        let chrome = browser_capabilities("chrome");
+        assert!(state.has_image_capacity(chrome.parse().unwrap()));
+        assert!(state.acquire_permit(Uuid::new_v4(), &chrome).await.unwrap());
+        assert_eq!(state.available_permits(), 1);
+    }
+
+    #[tokio::test]
+    async fn release_dead_permits_to_image_pool() {
+        let id = Uuid::new_v4();
+        let state = limited_state(2);
+
+        state
+            .acquire_permit(id, &browser_capabilities("firefox"))
+            .await
+            .unwrap();
+        assert_eq!(state.available_image_permits("firefox"), Some(0));
+
+        state.release_dead_sessions(Vec::new()).await;
+        assert_eq!(state.available_image_permits("firefox"), Some(1));
+        assert_eq!(state.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn grow_and_shrink_capacity() {
+        let state = ProvisioningState::new(2);
+
+        assert_eq!(state.set_capacity(4).await, 4);
+        assert_eq!(state.available_permits(), 4);
+
+        assert_eq!(state.set_capacity(1).await, 1);
+        assert_eq!(state.available_permits(), 1);
+
+        // Withheld permits are put back into circulation first
+        assert_eq!(state.set_capacity(3).await, 3);
+        assert_eq!(state.available_permits(), 3);
+    }
+
+    #[tokio::test]
+    async fn not_revoke_permits_held_by_sessions() {
+        let state = ProvisioningState::new(2);
+        state
+            .acquire_permit(Uuid::new_v4(), &any_capabilities())
+            .await
+            .unwrap();
+        state
+            .acquire_permit(Uuid::new_v4(), &any_capabilities())
+            .await
+            .unwrap();
+
+        assert_eq!(state.set_capacity(1).await, 2);
+        assert_eq!(state.capacity().await, 2);
+
+        state.release_dead_sessions(Vec::new()).await;
+        assert_eq!(state.set_capacity(1).await, 1);
+        assert_eq!(state.available_permits(), 1);
+    }
 }
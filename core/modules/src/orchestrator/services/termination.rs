@@ -1,4 +1,4 @@
-use super::ProvisioningState;
+use super::{FootprintTracker, ProvisioningState, SessionFootprint};
 use async_trait::async_trait;
 use domain::event::SessionTerminatedNotification;
 use harness::Service;
@@ -8,8 +8,11 @@ use library::EmptyResult;
 use tracing::debug;
 
 /// Watches for terminated sessions and releases the semaphore permits held by them
+///
+/// The resource usage of profiled sessions owned by this orchestrator is recorded in the [`FootprintTracker`].
 pub struct SessionTerminationWatcherService {
     state: ProvisioningState,
+    footprint: FootprintTracker,
 }
 
 impl<F> Service<F> for SessionTerminationWatcherService
@@ -18,11 +21,12 @@ where
 {
     const NAME: &'static str = "SessionTerminationWatcherService";
     type Instance = SessionTerminationWatcherService;
-    type Config = ProvisioningState;
+    type Config = (ProvisioningState, FootprintTracker);
 
-    fn instantiate(_factory: F, state: &Self::Config) -> Self::Instance {
+    fn instantiate(_factory: F, config: &Self::Config) -> Self::Instance {
         Self {
-            state: state.clone(),
+            state: config.0.clone(),
+            footprint: config.1.clone(),
         }
     }
 }
@@ -32,8 +36,18 @@ impl Consumer for SessionTerminationWatcherService {
     type Notification = SessionTerminatedNotification;
 
     async fn consume(&self, notification: NotificationFrame<Self::Notification>) -> EmptyResult {
-        debug!(id = ?notification.id, "Session terminated, releasing permit");
-        self.state.release_permit(&notification.id).await;
+        // Sessions of other orchestrators ran on different hardware, so their footprint is irrelevant
+        if self.state.release_permit(&notification.id).await.is_none() {
+            return Ok(());
+        }
+
+        debug!(id = ?notification.id, "Session terminated, released permit");
+
+        if let Some(footprint) = SessionFootprint::from_profiling_data(&notification.profiling_data)
+        {
+            self.footprint.record(footprint).await;
+        }
+
         Ok(())
     }
 }
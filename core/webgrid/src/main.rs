@@ -19,7 +19,7 @@ async fn main() -> Result<()> {
     match command {
         Command::Node(options) => runner.run(Node::new(options)).await,
         Command::Manager(options) => runner.run(Manager::new(options)).await,
-        Command::Orchestrator(options) => runner.run(Orchestrator::new(options)).await,
+        Command::Orchestrator(options) => runner.run(Orchestrator::new(options)?).await,
         Command::Gangway(options) => runner.run(Gangway::new(options)).await,
         Command::Collector(options) => runner.run(Collector::new(options)).await,
         Command::Api(options) => runner.run(Api::new(options)).await,
@@ -17,13 +17,14 @@ trap onerr ERR
 source /env.sh
 
 export DISPLAY=:42
-# TODO Use the RESOLUTION env variable instead
-export ON_SESSION_CREATE="xwit -display $DISPLAY -all -resize 1920 1080"
+SCREEN_WIDTH=${RESOLUTION%x*}
+SCREEN_HEIGHT=${RESOLUTION#*x}
+export ON_SESSION_CREATE="xwit -display $DISPLAY -all -resize $SCREEN_WIDTH $SCREEN_HEIGHT"
 
 ./start-xvfb.sh
 
 # Move the cursor out of the way
-xwit -display $DISPLAY -root -warp 1920 1080
+xwit -display $DISPLAY -root -warp $SCREEN_WIDTH $SCREEN_HEIGHT
 
 echo "Executing node service ..."
 webgrid node